@@ -0,0 +1,93 @@
+//! Compares the async and thread-pool walk engines on a tree of many small directories,
+//! and `--flat` against `--no-recurse` on a directory of repos.
+//!
+//! Run with `cargo bench --bench walk`.
+
+use std::path::Path;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use lsproj::metadata::ExtractOptions;
+use lsproj::walk::{self, Engine, WalkOptions};
+use tempfile::TempDir;
+
+/// 20 collections of 20 collections, each holding a small project.
+fn build_tree(root: &Path) {
+    for i in 0..20 {
+        for j in 0..20 {
+            let proj = root.join(format!("group{i}/sub{j}/proj"));
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        }
+    }
+}
+
+/// 100 repos side by side with 100 non-repo directories, each holding a few files and
+/// subdirectories the general walker has to read.
+fn build_flat_tree(root: &Path) {
+    for i in 0..100 {
+        git2::Repository::init(root.join(format!("repo{i}"))).unwrap();
+        let other = root.join(format!("other{i}"));
+        for j in 0..5 {
+            std::fs::create_dir_all(other.join(format!("dir{j}"))).unwrap();
+            std::fs::write(other.join(format!("file{j}.txt")), "notes\n").unwrap();
+        }
+    }
+}
+
+fn bench_engines(c: &mut Criterion) {
+    let tree = TempDir::new().unwrap();
+    build_tree(tree.path());
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("walk");
+    group.sample_size(10);
+    for (name, engine) in [("async", Engine::Async), ("threads", Engine::Threads)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let opts = WalkOptions {
+                    engine,
+                    extract: ExtractOptions {
+                        skip_loc: true,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+                runtime
+                    .block_on(walk::scan(tree.path().to_path_buf(), opts))
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_flat(c: &mut Criterion) {
+    let tree = TempDir::new().unwrap();
+    build_flat_tree(tree.path());
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("flat");
+    group.sample_size(10);
+    for (name, flat) in [("no_recurse", false), ("flat", true)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let opts = WalkOptions {
+                    max_depth: Some(1),
+                    flat,
+                    extract: ExtractOptions {
+                        skip_loc: true,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+                runtime
+                    .block_on(walk::scan(tree.path().to_path_buf(), opts))
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_engines, bench_flat);
+criterion_main!(benches);
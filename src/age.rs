@@ -0,0 +1,52 @@
+use chrono::Duration;
+
+/// Parse a human-friendly age such as `90d`, `6w`, `2y` or `12h`.
+///
+/// Units: `s`econds, `m`inutes, `h`ours, `d`ays, `w`eeks, `y`ears (365 days).
+/// Used as a clap value parser, hence the `String` error.
+pub fn parse_age(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing unit in {s:?} (expected e.g. 90d, 6w, 2y)"))?;
+    let (num, unit) = s.split_at(split);
+    let n: i64 = num
+        .parse()
+        .map_err(|_| format!("invalid number in {s:?}"))?;
+    let secs_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "unknown unit {unit:?} (expected s, m, h, d, w or y)"
+            ));
+        }
+    };
+    n.checked_mul(secs_per_unit)
+        .map(Duration::seconds)
+        .ok_or_else(|| format!("age {s:?} is too large"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_age_units() {
+        assert_eq!(parse_age("90d"), Ok(Duration::days(90)));
+        assert_eq!(parse_age("6w"), Ok(Duration::weeks(6)));
+        assert_eq!(parse_age("12h"), Ok(Duration::hours(12)));
+        assert_eq!(parse_age("1y"), Ok(Duration::days(365)));
+    }
+
+    #[test]
+    fn test_parse_age_rejects_garbage() {
+        assert!(parse_age("90").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3fortnights").is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use regex::Regex;
+
+/// Match against a committer email (`--last-author`): an exact, case-insensitive address,
+/// or a regex when written between slashes, e.g. `/@oldco\.com$/`.
+#[derive(Debug, Clone)]
+pub enum AuthorPattern {
+    Exact(String),
+    Regex(Regex),
+}
+
+impl AuthorPattern {
+    pub fn matches(&self, email: &str) -> bool {
+        match self {
+            AuthorPattern::Exact(want) => want.eq_ignore_ascii_case(email),
+            AuthorPattern::Regex(re) => re.is_match(email),
+        }
+    }
+}
+
+/// Parse an [`AuthorPattern`]. Used as a clap value parser, hence the `String` error.
+pub fn parse_author_pattern(s: &str) -> Result<AuthorPattern, String> {
+    match s.strip_prefix('/').and_then(|rest| rest.strip_suffix('/')) {
+        Some(re) => Regex::new(re)
+            .map(AuthorPattern::Regex)
+            .map_err(|e| format!("invalid regex {re:?}: {e}")),
+        None => Ok(AuthorPattern::Exact(s.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_match_ignores_case() {
+        let p = parse_author_pattern("Pat@OldCo.com").unwrap();
+        assert!(p.matches("pat@oldco.com"));
+        assert!(!p.matches("pat@newco.com"));
+    }
+
+    #[test]
+    fn test_regex_form() {
+        let p = parse_author_pattern(r"/@oldco\.com$/").unwrap();
+        assert!(p.matches("pat@oldco.com"));
+        assert!(!p.matches("pat@oldco.com.au"));
+        assert!(parse_author_pattern("/[/").is_err());
+    }
+}
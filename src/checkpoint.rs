@@ -0,0 +1,166 @@
+//! Resumable scans (`--checkpoint`, `--resume`): records which top-level subtrees of the
+//! scan root are fully processed, along with their results, so an interrupted scan can
+//! pick up where it left off.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::ProjectMetadata;
+
+/// On-disk form: the scan root and, per completed subtree, the records found in it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointState {
+    root: PathBuf,
+    completed: BTreeMap<PathBuf, Vec<ProjectMetadata>>,
+}
+
+/// A checkpoint file, rewritten whenever a subtree completes.
+pub struct Checkpoint {
+    path: PathBuf,
+    state: Mutex<CheckpointState>,
+}
+
+impl Checkpoint {
+    /// Start a checkpoint for a scan of `root` at `path`. With `resume`, an existing file
+    /// is loaded (it must be for the same root); otherwise any old file is replaced.
+    pub fn open(path: &Path, root: &Path, resume: bool) -> Result<Checkpoint> {
+        let state = if resume && path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Cannot read checkpoint {}", path.display()))?;
+            let state: CheckpointState = serde_json::from_str(&text)
+                .with_context(|| format!("Invalid checkpoint {}", path.display()))?;
+            if state.root != root {
+                bail!(
+                    "checkpoint {} is for {}, not {}",
+                    path.display(),
+                    state.root.display(),
+                    root.display()
+                );
+            }
+            state
+        } else {
+            CheckpointState {
+                root: root.to_path_buf(),
+                ..Default::default()
+            }
+        };
+        Ok(Checkpoint {
+            path: path.to_path_buf(),
+            state: Mutex::new(state),
+        })
+    }
+
+    pub fn is_complete(&self, subtree: &Path) -> bool {
+        self.state.lock().unwrap().completed.contains_key(subtree)
+    }
+
+    /// Records saved from subtrees completed in earlier runs.
+    pub fn completed_records(&self) -> Vec<ProjectMetadata> {
+        let state = self.state.lock().unwrap();
+        state.completed.values().flatten().cloned().collect()
+    }
+
+    /// Mark `subtree` done and persist. Written to a temp file and renamed, so an
+    /// interruption mid-write leaves the previous checkpoint intact.
+    pub fn complete(&self, subtree: &Path, records: Vec<ProjectMetadata>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.completed.insert(subtree.to_path_buf(), records);
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&*state)?)
+            .with_context(|| format!("Cannot write checkpoint {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Cannot write checkpoint {}", self.path.display()))
+    }
+}
+
+/// One top-level subtree in progress. Every task working inside it holds a clone of the
+/// `Arc`; when the last one finishes, the drop records the subtree as complete unless
+/// part of it was abandoned.
+pub struct Subtree {
+    path: PathBuf,
+    records: Mutex<Vec<ProjectMetadata>>,
+    checkpoint: Arc<Checkpoint>,
+    abandoned: AtomicBool,
+}
+
+impl Subtree {
+    pub fn new(path: PathBuf, checkpoint: Arc<Checkpoint>) -> Self {
+        Subtree {
+            path,
+            records: Mutex::new(Vec::new()),
+            checkpoint,
+            abandoned: AtomicBool::new(false),
+        }
+    }
+
+    pub fn push(&self, meta: ProjectMetadata) {
+        self.records.lock().unwrap().push(meta);
+    }
+
+    /// Something in the subtree was not processed (a failed read or extraction, a panic,
+    /// or a cancelled scan), so it stays out of the checkpoint and a resume walks it again.
+    pub fn abandon(&self) {
+        self.abandoned.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Subtree {
+    fn drop(&mut self) {
+        if *self.abandoned.get_mut() || std::thread::panicking() {
+            return;
+        }
+        let records = std::mem::take(self.records.get_mut().unwrap());
+        if let Err(e) = self.checkpoint.complete(&self.path, records) {
+            eprintln!("Error saving checkpoint: {e:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resume_loads_completed_subtrees() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("scan.checkpoint");
+        let root = Path::new("/projects");
+
+        let checkpoint = Arc::new(Checkpoint::open(&file, root, false).unwrap());
+        let subtree = Subtree::new(root.join("a"), checkpoint.clone());
+        subtree.push(ProjectMetadata {
+            path: "a/one".to_string(),
+            ..Default::default()
+        });
+        assert!(!checkpoint.is_complete(&root.join("a")));
+        drop(subtree);
+        assert!(checkpoint.is_complete(&root.join("a")));
+
+        let resumed = Checkpoint::open(&file, root, true).unwrap();
+        assert!(resumed.is_complete(&root.join("a")));
+        assert!(!resumed.is_complete(&root.join("b")));
+        assert_eq!(resumed.completed_records()[0].path, "a/one");
+
+        assert!(Checkpoint::open(&file, Path::new("/elsewhere"), true).is_err());
+        let fresh = Checkpoint::open(&file, root, false).unwrap();
+        assert!(!fresh.is_complete(&root.join("a")));
+    }
+
+    #[test]
+    fn test_abandoned_subtree_is_not_completed() {
+        let dir = TempDir::new().unwrap();
+        let root = Path::new("/projects");
+        let checkpoint =
+            Arc::new(Checkpoint::open(&dir.path().join("scan.checkpoint"), root, false).unwrap());
+        let subtree = Subtree::new(root.join("a"), checkpoint.clone());
+        subtree.abandon();
+        drop(subtree);
+        assert!(!checkpoint.is_complete(&root.join("a")));
+    }
+}
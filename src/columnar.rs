@@ -0,0 +1,153 @@
+//! Parquet export (`--format parquet`) for loading inventories into DuckDB and friends.
+
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BooleanBuilder, StringBuilder, TimestampSecondBuilder, UInt32Builder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::DateTime;
+use parquet::arrow::ArrowWriter;
+
+use crate::metadata::ProjectMetadata;
+
+fn schema() -> Schema {
+    let timestamp = || DataType::Timestamp(TimeUnit::Second, Some("UTC".into()));
+    Schema::new(vec![
+        Field::new("path", DataType::Utf8, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("is_git", DataType::Boolean, false),
+        Field::new("origin_url", DataType::Utf8, true),
+        Field::new("total_commits", DataType::UInt32, true),
+        Field::new("unpushed_count", DataType::UInt32, true),
+        Field::new("oldest_unpushed", timestamp(), true),
+        Field::new("newest_unpushed", timestamp(), true),
+        Field::new("newest_commit", timestamp(), true),
+        Field::new("last_modified", timestamp(), true),
+        Field::new("primary_language", DataType::Utf8, true),
+        Field::new("loc", DataType::UInt64, false),
+        Field::new("repostatus_state", DataType::Utf8, false),
+        Field::new("is_shallow", DataType::Boolean, false),
+    ])
+}
+
+/// Seconds since the epoch for an RFC 3339 date as stored in [`ProjectMetadata`].
+fn epoch_seconds(iso: &Option<String>) -> Option<i64> {
+    iso.as_deref()
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.timestamp())
+}
+
+/// Write `records` as a single-row-group Parquet file.
+///
+/// Parquet's footer needs the whole file laid out first, so it is assembled in memory and
+/// then copied to `out`, which can be a non-seekable stream such as stdout.
+pub fn write_parquet<W: Write>(records: &[ProjectMetadata], mut out: W) -> io::Result<()> {
+    let mut path = StringBuilder::new();
+    let mut name = StringBuilder::new();
+    let mut is_git = BooleanBuilder::new();
+    let mut origin_url = StringBuilder::new();
+    let mut total_commits = UInt32Builder::new();
+    let mut unpushed_count = UInt32Builder::new();
+    let mut dates: [TimestampSecondBuilder; 4] =
+        std::array::from_fn(|_| TimestampSecondBuilder::new().with_timezone("UTC"));
+    let mut primary_language = StringBuilder::new();
+    let mut loc = UInt64Builder::new();
+    let mut repostatus_state = StringBuilder::new();
+    let mut is_shallow = BooleanBuilder::new();
+
+    for p in records {
+        path.append_value(&p.path);
+        name.append_value(&p.name);
+        is_git.append_value(p.is_git);
+        origin_url.append_option(p.origin_url.as_deref());
+        total_commits.append_option(p.total_commits);
+        unpushed_count.append_option(p.unpushed_count);
+        let values = [
+            &p.oldest_unpushed,
+            &p.newest_unpushed,
+            &p.newest_commit,
+            &p.last_modified,
+        ];
+        for (builder, value) in dates.iter_mut().zip(values) {
+            builder.append_option(epoch_seconds(value));
+        }
+        primary_language.append_option(p.primary_language.as_deref());
+        loc.append_value(p.languages.iter().map(|l| l.code).sum());
+        repostatus_state.append_value(&p.repostatus_state);
+        is_shallow.append_value(p.is_shallow);
+    }
+
+    let [mut oldest, mut newest, mut newest_commit, mut last_modified] = dates;
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(path.finish()),
+        Arc::new(name.finish()),
+        Arc::new(is_git.finish()),
+        Arc::new(origin_url.finish()),
+        Arc::new(total_commits.finish()),
+        Arc::new(unpushed_count.finish()),
+        Arc::new(oldest.finish()),
+        Arc::new(newest.finish()),
+        Arc::new(newest_commit.finish()),
+        Arc::new(last_modified.finish()),
+        Arc::new(primary_language.finish()),
+        Arc::new(loc.finish()),
+        Arc::new(repostatus_state.finish()),
+        Arc::new(is_shallow.finish()),
+    ];
+    let batch = RecordBatch::try_new(Arc::new(schema()), columns).map_err(io::Error::other)?;
+
+    let mut buffer = Vec::new();
+    let mut writer = ArrowWriter::try_new(&mut buffer, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    out.write_all(&buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{TimestampSecondType, UInt32Type};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_parquet_round_trip() {
+        let records = vec![
+            ProjectMetadata {
+                path: "alpha".to_string(),
+                is_git: true,
+                total_commits: Some(3),
+                oldest_unpushed: Some("2023-11-14T22:13:20Z".to_string()),
+                ..Default::default()
+            },
+            ProjectMetadata {
+                path: "tools/beta".to_string(),
+                ..Default::default()
+            },
+        ];
+        let mut file = tempfile::tempfile().unwrap();
+        write_parquet(&records, &mut file).unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(Result::unwrap).collect();
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 2);
+
+        let batch = &batches[0];
+        let commits = batch.column_by_name("total_commits").unwrap();
+        assert_eq!(commits.as_primitive::<UInt32Type>().value(0), 3);
+        let oldest = batch.column_by_name("oldest_unpushed").unwrap();
+        let oldest = oldest.as_primitive::<TimestampSecondType>();
+        assert_eq!(oldest.value(0), 1_700_000_000);
+        assert!(oldest.is_null(1));
+        let paths = batch.column_by_name("path").unwrap().as_string::<i32>();
+        assert_eq!(paths.value(1), "tools/beta");
+    }
+}
@@ -0,0 +1,187 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Seek, SeekFrom, Write};
+
+use crate::metadata::ProjectMetadata;
+
+/// Collects scan results and yields them sorted by `path`.
+///
+/// Records are buffered in memory until `limit` is reached, then sorted and spilled to an
+/// anonymous temp file as JSON lines. `into_sorted` k-way merges the spilled runs, so memory
+/// stays proportional to `limit` plus one record per run no matter how large the tree is.
+pub struct ExternalSorter {
+    limit: usize,
+    buffer: Vec<ProjectMetadata>,
+    runs: Vec<File>,
+}
+
+impl ExternalSorter {
+    /// `limit` is the number of records held in memory before spilling; `None` never spills.
+    pub fn new(limit: Option<usize>) -> Self {
+        ExternalSorter {
+            limit: limit.unwrap_or(usize::MAX).max(1),
+            buffer: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, meta: ProjectMetadata) -> anyhow::Result<()> {
+        self.buffer.push(meta);
+        if self.buffer.len() >= self.limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Number of runs written to disk so far.
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    fn spill(&mut self) -> anyhow::Result<()> {
+        self.buffer.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut file = tempfile::tempfile()?;
+        {
+            let mut writer = BufWriter::new(&mut file);
+            for meta in self.buffer.drain(..) {
+                serde_json::to_writer(&mut writer, &meta)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        }
+        file.seek(SeekFrom::Start(0))?;
+        self.runs.push(file);
+        Ok(())
+    }
+
+    pub fn into_sorted(mut self) -> anyhow::Result<SortedRecords> {
+        if self.runs.is_empty() {
+            self.buffer.sort_by(|a, b| a.path.cmp(&b.path));
+            return Ok(SortedRecords::Memory(self.buffer.into_iter()));
+        }
+        if !self.buffer.is_empty() {
+            self.spill()?;
+        }
+
+        let mut readers: Vec<Lines<BufReader<File>>> = self
+            .runs
+            .into_iter()
+            .map(|f| BufReader::new(f).lines())
+            .collect();
+        let mut heap = BinaryHeap::new();
+        for (run, reader) in readers.iter_mut().enumerate() {
+            if let Some(meta) = next_record(reader)? {
+                heap.push(Reverse(RunHead { meta, run }));
+            }
+        }
+        Ok(SortedRecords::Merge { readers, heap })
+    }
+}
+
+/// Sorted output of an [`ExternalSorter`].
+pub enum SortedRecords {
+    Memory(std::vec::IntoIter<ProjectMetadata>),
+    Merge {
+        readers: Vec<Lines<BufReader<File>>>,
+        heap: BinaryHeap<Reverse<RunHead>>,
+    },
+}
+
+impl Iterator for SortedRecords {
+    type Item = anyhow::Result<ProjectMetadata>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            SortedRecords::Memory(iter) => iter.next().map(Ok),
+            SortedRecords::Merge { readers, heap } => {
+                let Reverse(RunHead { meta, run }) = heap.pop()?;
+                match next_record(&mut readers[run]) {
+                    Ok(Some(next)) => heap.push(Reverse(RunHead { meta: next, run })),
+                    Ok(None) => {}
+                    Err(e) => return Some(Err(e)),
+                }
+                Some(Ok(meta))
+            }
+        }
+    }
+}
+
+fn next_record(reader: &mut Lines<BufReader<File>>) -> anyhow::Result<Option<ProjectMetadata>> {
+    match reader.next() {
+        Some(line) => Ok(Some(serde_json::from_str(&line?)?)),
+        None => Ok(None),
+    }
+}
+
+/// Smallest unmerged record of one spilled run.
+pub struct RunHead {
+    meta: ProjectMetadata,
+    run: usize,
+}
+
+impl Ord for RunHead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.meta
+            .path
+            .cmp(&other.meta.path)
+            .then(self.run.cmp(&other.run))
+    }
+}
+
+impl PartialOrd for RunHead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for RunHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for RunHead {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(path: &str) -> ProjectMetadata {
+        ProjectMetadata {
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn sorted_paths(sorter: ExternalSorter) -> Vec<String> {
+        sorter
+            .into_sorted()
+            .unwrap()
+            .map(|m| m.unwrap().path)
+            .collect()
+    }
+
+    #[test]
+    fn test_in_memory_sort() {
+        let mut sorter = ExternalSorter::new(None);
+        for p in ["c", "a", "b"] {
+            sorter.push(meta(p)).unwrap();
+        }
+        assert_eq!(sorter.spilled_runs(), 0);
+        assert_eq!(sorted_paths(sorter), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_tiny_buffer_spills_and_merges_in_order() {
+        let mut sorter = ExternalSorter::new(Some(2));
+        for p in ["g", "c", "e", "a", "f", "b", "d"] {
+            sorter.push(meta(p)).unwrap();
+        }
+        assert_eq!(sorter.spilled_runs(), 3);
+        assert_eq!(
+            sorted_paths(sorter),
+            vec!["a", "b", "c", "d", "e", "f", "g"]
+        );
+    }
+}
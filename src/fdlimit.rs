@@ -0,0 +1,87 @@
+//! Startup check that the scan's concurrency fits the open-file limit, so a low
+//! `ulimit -n` is reported up front instead of as "Too many open files" mid-scan.
+
+/// Descriptors a scan needs regardless of concurrency: stdio, the output file, sort
+/// spill files, the runtime's own.
+const BASE_FDS: u64 = 32;
+
+/// Descriptors one metadata extraction may hold at once: git opens the repo's config,
+/// index and pack files, and line counting opens source files.
+const FDS_PER_REPO: u64 = 8;
+
+/// Estimated use above this share of the limit gets a warning, leaving room for
+/// unusually large repos.
+const SAFE_FRACTION: f64 = 0.75;
+
+/// The process's soft limit on open files, or `None` when unlimited or unknown.
+pub fn soft_limit() -> Option<u64> {
+    #[cfg(unix)]
+    {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: getrlimit only writes to the struct passed in
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } != 0
+            || limit.rlim_cur == libc::RLIM_INFINITY
+        {
+            return None;
+        }
+        #[allow(clippy::unnecessary_cast)] // rlim_t is not u64 on every unix
+        Some(limit.rlim_cur as u64)
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// True when `e` is "Too many open files", for the process (`EMFILE`) or the whole
+/// system (`ENFILE`).
+pub fn is_exhausted(e: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        matches!(e.raw_os_error(), Some(libc::EMFILE | libc::ENFILE))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = e;
+        false
+    }
+}
+
+/// Descriptors a scan may hold open at its peak: one per directory read in flight plus
+/// git's and line counting's share per concurrent extraction.
+pub fn estimated_fds(dir_concurrency: usize, repo_concurrency: usize) -> u64 {
+    BASE_FDS + dir_concurrency as u64 + FDS_PER_REPO * repo_concurrency as u64
+}
+
+/// An actionable warning when the estimate exceeds a safe share of `limit`.
+pub fn check(dir_concurrency: usize, repo_concurrency: usize, limit: u64) -> Option<String> {
+    let needed = estimated_fds(dir_concurrency, repo_concurrency);
+    if needed as f64 <= limit as f64 * SAFE_FRACTION {
+        return None;
+    }
+    Some(format!(
+        "--dir-concurrency {dir_concurrency} and --repo-concurrency {repo_concurrency} may \
+         need ~{needed} open files, near the limit of {limit}; raise it (e.g. `ulimit -n \
+         {}`) or lower the concurrency",
+        (needed * 2).next_power_of_two()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warns_near_limit() {
+        // 32 + 100 + 8 * 8 = 196
+        assert_eq!(estimated_fds(100, 8), 196);
+        assert!(check(100, 8, 1024).is_none());
+        let warning = check(100, 8, 256).unwrap();
+        assert!(warning.contains("~196"), "{warning}");
+        assert!(warning.contains("ulimit -n 512"), "{warning}");
+        assert!(check(10, 2, 256).is_none());
+    }
+}
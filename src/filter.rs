@@ -1,5 +1,10 @@
 use std::path::Path;
 
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+
+use crate::git_info::first_commit_time;
+
 pub enum EntryKind {
     Skip,
     Project,
@@ -25,6 +30,54 @@ const SKIP_COMPONENTS: &[&str] = &[
 // Final-component suffix matches (e.g. "foo.xcodeproj").
 const SKIP_SUFFIXES: &[&str] = &[".xcodeproj", ".xcworkspace", ".noindex"];
 
+/// Extra directory names to skip, loaded from a `--deny-from` file: one name or glob
+/// (`*`, `?`, `[...]`) per line, matched against a directory's own name. Blank lines and
+/// lines starting with `#` are ignored.
+#[derive(Debug, Default, Clone)]
+pub struct DenyList {
+    patterns: Vec<glob::Pattern>,
+}
+
+impl DenyList {
+    pub fn load(path: &Path) -> anyhow::Result<DenyList> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read deny list {}", path.display()))?;
+        DenyList::parse(&text).with_context(|| format!("Invalid deny list {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<DenyList> {
+        let mut patterns = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let pattern = glob::Pattern::new(line)
+                .with_context(|| format!("line {}: bad pattern {line:?}", i + 1))?;
+            patterns.push(pattern);
+        }
+        Ok(DenyList { patterns })
+    }
+
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// True when `path`'s final component matches an entry. Names that aren't valid
+    /// UTF-8 are matched lossily.
+    pub fn denies(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name() else {
+            return false;
+        };
+        let name = name.to_string_lossy();
+        self.patterns.iter().any(|p| p.matches(&name))
+    }
+}
+
 /// Classify a directory entry for traversal.
 ///
 /// Returns:
@@ -32,11 +85,21 @@ const SKIP_SUFFIXES: &[&str] = &[".xcodeproj", ".xcworkspace", ".noindex"];
 /// - `Project` if the directory is a project root (contains non-hidden files)
 /// - `Collection` if the directory contains only subdirectories (descend into it)
 pub fn classify_entry(path: &Path) -> EntryKind {
-    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-        if SKIP_COMPONENTS.contains(&name)
+    classify_entry_with_markers(path, &[])
+}
+
+/// [`classify_entry`], additionally treating any directory that contains one of `markers`
+/// (e.g. `.hg`, `.svn`, `.project`) as a project root, even when it has no other files.
+///
+/// Names are compared as raw bytes, so directories whose names aren't valid UTF-8 are
+/// classified like any other.
+pub fn classify_entry_with_markers(path: &Path, markers: &[String]) -> EntryKind {
+    if let Some(name) = path.file_name() {
+        let bytes = name.as_encoded_bytes();
+        if SKIP_COMPONENTS.iter().any(|c| name == *c)
             || name.eq_ignore_ascii_case("build")
-            || SKIP_SUFFIXES.iter().any(|s| name.ends_with(s))
-            || name.contains(".sdk")
+            || SKIP_SUFFIXES.iter().any(|s| bytes.ends_with(s.as_bytes()))
+            || bytes.windows(4).any(|w| w == b".sdk")
         {
             return EntryKind::Skip;
         }
@@ -46,20 +109,22 @@ pub fn classify_entry(path: &Path) -> EntryKind {
             && path
                 .parent()
                 .and_then(|p| p.file_name())
-                .and_then(|n| n.to_str())
-                .map(|n| n.ends_with(".app"))
-                .unwrap_or(false)
+                .is_some_and(|n| n.as_encoded_bytes().ends_with(b".app"))
         {
             return EntryKind::Skip;
         }
     }
 
-    // Skip git worktrees: .git is a FILE (not a dir) in a linked worktree
+    // Skip git worktrees and submodule checkouts: .git is a FILE (not a dir) in both
     let git_path = path.join(".git");
     if git_path.is_file() {
         return EntryKind::Skip;
     }
 
+    if markers.iter().any(|m| path.join(m).exists()) {
+        return EntryKind::Project;
+    }
+
     // Git repos are always project roots, even if they have no non-hidden files yet
     if git_path.is_dir() {
         return EntryKind::Project;
@@ -82,6 +147,135 @@ pub fn classify_entry(path: &Path) -> EntryKind {
     }
 }
 
+/// True when `path/.git` is a gitdir file pointing into another repo's `.git/modules/`:
+/// a submodule (dependency) checkout rather than a project of its own.
+pub fn is_submodule_checkout(path: &Path) -> bool {
+    let Ok(contents) = std::fs::read_to_string(path.join(".git")) else {
+        return false;
+    };
+    let Some(gitdir) = contents.lines().find_map(|l| l.strip_prefix("gitdir:")) else {
+        return false;
+    };
+    let components: Vec<_> = Path::new(gitdir.trim())
+        .components()
+        .map(|c| c.as_os_str())
+        .collect();
+    components
+        .windows(2)
+        .any(|pair| pair[0] == ".git" && pair[1] == "modules")
+}
+
+/// A predicate selecting which `T`s to report: `true` means keep.
+///
+/// Object-safe, so filters chosen at runtime can be held as `Box<dyn Filter<T>>`. Any
+/// `Fn(&T) -> bool` closure is a filter.
+pub trait Filter<T: ?Sized> {
+    fn filter(&self, t: &T) -> bool;
+}
+
+impl<T: ?Sized, F: Fn(&T) -> bool> Filter<T> for F {
+    fn filter(&self, t: &T) -> bool {
+        self(t)
+    }
+}
+
+/// Keeps exactly what the inner filter rejects.
+pub struct Not<F>(pub F);
+
+impl<T: ?Sized, F: Filter<T>> Filter<T> for Not<F> {
+    fn filter(&self, t: &T) -> bool {
+        !self.0.filter(t)
+    }
+}
+
+/// Combinators available on every [`Filter`].
+pub trait FilterExt<T: ?Sized>: Filter<T> + Sized {
+    /// This filter, inverted: `dirty.not()` keeps what `dirty` drops.
+    fn not(self) -> Not<Self> {
+        Not(self)
+    }
+}
+
+impl<T: ?Sized, F: Filter<T>> FilterExt<T> for F {}
+
+/// Keeps repos whose first commit is before `cutoff`, e.g. projects started before 2020
+/// however active they are now. Non-repos and empty repos are dropped.
+///
+/// Opens the repo and walks its whole history to find the earliest commit: as slow as
+/// counting commits, so push it after cheaper filters in a chain.
+pub struct FirstCommitBefore {
+    pub cutoff: DateTime<Utc>,
+}
+
+impl Filter<Path> for FirstCommitBefore {
+    fn filter(&self, path: &Path) -> bool {
+        first_commit_time(path).is_some_and(|first| first < self.cutoff)
+    }
+}
+
+/// Keeps repos whose first commit is at or after `cutoff`; the complement of
+/// [`FirstCommitBefore`] among repos with commits, at the same cost.
+pub struct FirstCommitAfter {
+    pub cutoff: DateTime<Utc>,
+}
+
+impl Filter<Path> for FirstCommitAfter {
+    fn filter(&self, path: &Path) -> bool {
+        first_commit_time(path).is_some_and(|first| first >= self.cutoff)
+    }
+}
+
+/// Keeps repos with a remote called `name`, e.g. `upstream` or a `backup` mirror.
+/// Non-repos are dropped.
+pub struct HasRemoteNamed {
+    pub name: String,
+}
+
+impl Filter<Path> for HasRemoteNamed {
+    fn filter(&self, path: &Path) -> bool {
+        git2::Repository::open(path)
+            .and_then(|repo| repo.remotes())
+            .is_ok_and(|names| names.iter().flatten().any(|n| n == self.name))
+    }
+}
+
+/// ANDs a runtime-built list of filters. An empty chain keeps everything.
+pub struct FilterChain<T: ?Sized> {
+    filters: Vec<Box<dyn Filter<T>>>,
+}
+
+impl<T: ?Sized> FilterChain<T> {
+    pub fn new() -> Self {
+        FilterChain {
+            filters: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, filter: impl Filter<T> + 'static) {
+        self.filters.push(Box::new(filter));
+    }
+
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+}
+
+impl<T: ?Sized> Default for FilterChain<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ?Sized> Filter<T> for FilterChain<T> {
+    fn filter(&self, t: &T) -> bool {
+        self.filters.iter().all(|f| f.filter(t))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -143,6 +337,29 @@ mod tests {
         assert!(!matches!(classify_entry(path2), EntryKind::Skip));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn classifies_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+        let path = |name: &[u8]| Path::new("/some/root").join(std::ffi::OsStr::from_bytes(name));
+        assert!(matches!(
+            classify_entry(&path(b"caf\xe9.xcodeproj")),
+            EntryKind::Skip
+        ));
+        assert!(matches!(
+            classify_entry(&path(b"\xffOS.sdk")),
+            EntryKind::Skip
+        ));
+        assert!(!matches!(
+            classify_entry(&path(b"caf\xe9")),
+            EntryKind::Skip
+        ));
+        let contents = Path::new("/Applications")
+            .join(std::ffi::OsStr::from_bytes(b"\xe9.app"))
+            .join("Contents");
+        assert!(matches!(classify_entry(&contents), EntryKind::Skip));
+    }
+
     #[test]
     fn does_not_skip_normal_dirs() {
         // "dist" is a skip dir, but unrelated names should not be skipped by the name check.
@@ -152,4 +369,147 @@ mod tests {
             assert!(!skip(name), "unexpected Skip for {name}");
         }
     }
+
+    #[test]
+    fn marker_makes_project_root() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let hg = tmp.path().join("hgproj");
+        std::fs::create_dir_all(hg.join(".hg")).unwrap();
+        assert!(matches!(classify_entry(&hg), EntryKind::Collection));
+        assert!(matches!(
+            classify_entry_with_markers(&hg, &[".hg".to_string()]),
+            EntryKind::Project
+        ));
+    }
+
+    #[test]
+    fn not_inverts_closure_filter() {
+        let big = |n: &u32| *n > 10;
+        let small = big.not();
+        assert!(small.filter(&3));
+        assert!(!small.filter(&30));
+        assert!(
+            Not(small).filter(&30),
+            "double negation restores the original"
+        );
+    }
+
+    #[test]
+    fn filter_chain_ands_boxed_filters() {
+        struct HasGit;
+        impl Filter<Path> for HasGit {
+            fn filter(&self, path: &Path) -> bool {
+                path.join(".git").is_dir()
+            }
+        }
+
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (repo, plain, other) = (
+            tmp.path().join("keep-repo"),
+            tmp.path().join("keep-plain"),
+            tmp.path().join("other-repo"),
+        );
+        for dir in [&repo, &plain, &other] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::create_dir(repo.join(".git")).unwrap();
+        std::fs::create_dir(other.join(".git")).unwrap();
+
+        let mut chain: FilterChain<Path> = FilterChain::new();
+        assert!(chain.filter(&plain), "empty chain keeps everything");
+        chain.push(HasGit);
+        chain.push(|p: &Path| {
+            p.file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with("keep"))
+        });
+        assert_eq!(chain.len(), 2);
+        assert!(chain.filter(&repo));
+        assert!(!chain.filter(&plain));
+        assert!(!chain.filter(&other));
+    }
+
+    #[test]
+    fn first_commit_filters_straddle_cutoff() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        // First commit in 2019 with recent activity, first commit in 2021, and no commits
+        let commit = |repo: &git2::Repository, secs: i64| {
+            let sig = git2::Signature::new("T", "t@t.com", &git2::Time::new(secs, 0)).unwrap();
+            let tree = repo
+                .find_tree(repo.index().unwrap().write_tree().unwrap())
+                .unwrap();
+            let parent = repo.head().ok().map(|h| h.peel_to_commit().unwrap());
+            let parents: Vec<_> = parent.iter().collect();
+            repo.commit(Some("HEAD"), &sig, &sig, "c", &tree, &parents)
+                .unwrap();
+        };
+        let (old, new, empty) = (
+            tmp.path().join("old"),
+            tmp.path().join("new"),
+            tmp.path().join("empty"),
+        );
+        let repo = git2::Repository::init(&old).unwrap();
+        commit(&repo, 1_546_300_800); // 2019-01-01
+        commit(&repo, 1_735_689_600); // 2025-01-01
+        commit(&git2::Repository::init(&new).unwrap(), 1_609_459_200); // 2021-01-01
+        git2::Repository::init(&empty).unwrap();
+
+        let cutoff = DateTime::from_timestamp(1_577_836_800, 0).unwrap(); // 2020-01-01
+        let before = FirstCommitBefore { cutoff };
+        let after = FirstCommitAfter { cutoff };
+        assert!(before.filter(&old));
+        assert!(!after.filter(&old));
+        assert!(!before.filter(&new));
+        assert!(after.filter(&new));
+        assert!(!before.filter(&empty) && !after.filter(&empty));
+        assert!(!before.filter(tmp.path()), "not a repo");
+    }
+
+    #[test]
+    fn has_remote_named_matches_remote_names() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let (mirrored, plain) = (tmp.path().join("mirrored"), tmp.path().join("plain"));
+        let repo = git2::Repository::init(&mirrored).unwrap();
+        repo.remote("origin", "https://github.com/pete/app.git")
+            .unwrap();
+        repo.remote("backup", "ssh://nas/srv/git/app.git").unwrap();
+        git2::Repository::init(&plain)
+            .unwrap()
+            .remote("origin", "https://github.com/pete/plain.git")
+            .unwrap();
+
+        let backup = HasRemoteNamed {
+            name: "backup".to_string(),
+        };
+        assert!(backup.filter(&mirrored));
+        assert!(!backup.filter(&plain));
+        assert!(!backup.filter(tmp.path()), "not a repo");
+    }
+
+    #[test]
+    fn deny_list_parses_names_globs_and_comments() {
+        let deny = DenyList::parse("# shared skips\n\nnode_modules\n  scratch-*  \n").unwrap();
+        assert_eq!(deny.len(), 2);
+        assert!(deny.denies(Path::new("/src/app/node_modules")));
+        assert!(deny.denies(Path::new("/src/scratch-2024")));
+        assert!(!deny.denies(Path::new("/src/node_modules/app")));
+        assert!(!deny.denies(Path::new("/src/# shared skips")));
+        let err = DenyList::parse("ok\n[oops\n").unwrap_err();
+        assert!(format!("{err:#}").contains("line 2"), "{err:#}");
+    }
+
+    #[test]
+    fn detects_submodule_checkout() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dep = tmp.path().join("dep");
+        std::fs::create_dir(&dep).unwrap();
+        std::fs::write(dep.join(".git"), "gitdir: ../parent/.git/modules/dep\n").unwrap();
+        assert!(is_submodule_checkout(&dep));
+        assert!(matches!(classify_entry(&dep), EntryKind::Skip));
+
+        let worktree = tmp.path().join("wt");
+        std::fs::create_dir(&worktree).unwrap();
+        std::fs::write(worktree.join(".git"), "gitdir: ../repo/.git/worktrees/wt\n").unwrap();
+        assert!(!is_submodule_checkout(&worktree));
+        assert!(!is_submodule_checkout(tmp.path()));
+    }
 }
@@ -0,0 +1,72 @@
+//! External filter programs (`--filter-cmd`): a project is kept only when the program,
+//! run with the project's path as its one argument, exits 0. Anything it prints goes to
+//! stderr, so the report on stdout stays parseable.
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::process::Command;
+
+/// A user-supplied program consulted once per project.
+#[derive(Debug, Clone)]
+pub struct FilterCommand {
+    program: OsString,
+    timeout: Duration,
+}
+
+impl FilterCommand {
+    pub fn new(program: impl Into<OsString>, timeout: Duration) -> Self {
+        FilterCommand {
+            program: program.into(),
+            timeout,
+        }
+    }
+
+    /// True when the program exits 0 for `path`. A program that can't be started, exits
+    /// non-zero, or is still running after the timeout (it is killed) rejects the project.
+    pub async fn accepts(&self, path: &Path) -> bool {
+        let child = Command::new(&self.program)
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(std::io::stderr())
+            .kill_on_drop(true)
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Cannot run {}: {e}", self.program.to_string_lossy());
+                return false;
+            }
+        };
+        match tokio::time::timeout(self.timeout, child.wait()).await {
+            Ok(status) => status.is_ok_and(|s| s.success()),
+            Err(_) => {
+                eprintln!(
+                    "{} timed out on {}; leaving it out",
+                    self.program.to_string_lossy(),
+                    path.display()
+                );
+                false
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_exit_status_and_timeout() {
+        let accept = FilterCommand::new("true", Duration::from_secs(5));
+        assert!(accept.accepts(Path::new("/tmp")).await);
+        let reject = FilterCommand::new("false", Duration::from_secs(5));
+        assert!(!reject.accepts(Path::new("/tmp")).await);
+        let missing = FilterCommand::new("/nonexistent/filter", Duration::from_secs(5));
+        assert!(!missing.accepts(Path::new("/tmp")).await);
+        let slow = FilterCommand::new("sleep", Duration::from_millis(100));
+        assert!(!slow.accepts(Path::new("5")).await);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::Path;
 
 pub struct FsInfo {
@@ -37,6 +38,159 @@ pub fn extract_fs_info(path: &Path) -> FsInfo {
     }
 }
 
+/// Files sampled by [`dominant_extension`] before it stops looking.
+pub const EXTENSION_SAMPLE_LIMIT: usize = 500;
+
+/// Most common file extension (lowercased, without the dot) among the first `limit` files
+/// under `path`, skipping `.git` and other hidden directories. A cheap heuristic for
+/// `--show-language`, not real language detection; ties go to the alphabetically first.
+pub fn dominant_extension(path: &Path, limit: usize) -> Option<String> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut seen = 0;
+    let mut stack = vec![path.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(rd) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in rd.flatten() {
+            let Ok(ft) = entry.file_type() else { continue };
+            let entry_path = entry.path();
+            if ft.is_dir() {
+                if !entry.file_name().to_string_lossy().starts_with('.') {
+                    stack.push(entry_path);
+                }
+                continue;
+            }
+            if !ft.is_file() {
+                continue;
+            }
+            if let Some(ext) = entry_path.extension() {
+                *counts
+                    .entry(ext.to_string_lossy().to_lowercase())
+                    .or_default() += 1;
+            }
+            seen += 1;
+            if seen >= limit {
+                stack.clear();
+                break;
+            }
+        }
+    }
+    counts
+        .into_iter()
+        .max_by(|(a_ext, a), (b_ext, b)| a.cmp(b).then(b_ext.cmp(a_ext)))
+        .map(|(ext, _)| ext)
+}
+
+/// Id of the filesystem device holding `path`, or `None` where unsupported (non-Unix).
+pub fn device_id(path: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        std::fs::metadata(path).ok().map(|m| m.dev())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
+/// Where kernel pseudo-filesystems are mounted on Linux. Nothing under them is a project,
+/// and reading them can be slow or block.
+pub const SPECIAL_MOUNTS: &[&str] = &["/proc", "/sys", "/dev", "/run"];
+
+/// True when `dir` is one of [`SPECIAL_MOUNTS`] or, on Linux, sits on a pseudo-filesystem
+/// (procfs, sysfs, devpts, cgroup, ...) mounted elsewhere.
+pub fn is_special_fs(dir: &Path) -> bool {
+    SPECIAL_MOUNTS.iter().any(|m| dir == Path::new(m)) || is_pseudo_fs(dir)
+}
+
+#[cfg(target_os = "linux")]
+fn is_pseudo_fs(dir: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    let Ok(c_path) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: statfs is plain old data, and statfs only writes to the struct passed in
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return false;
+    }
+    [
+        libc::PROC_SUPER_MAGIC,
+        libc::SYSFS_MAGIC,
+        libc::DEVPTS_SUPER_MAGIC,
+        libc::CGROUP_SUPER_MAGIC,
+        libc::CGROUP2_SUPER_MAGIC,
+        libc::DEBUGFS_MAGIC,
+        libc::SECURITYFS_MAGIC,
+        libc::TRACEFS_MAGIC,
+        libc::BPF_FS_MAGIC,
+    ]
+    .contains(&stat.f_type)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_pseudo_fs(_dir: &Path) -> bool {
+    false
+}
+
+/// Newest modification time, in nanoseconds since the epoch, of `git_dir` and every
+/// directory under its `refs`. Git rewrites HEAD, the index and packed-refs by renaming
+/// into `git_dir`, and any other ref by renaming within its directory under `refs`, so a
+/// commit, fetch or push moves this forward even though `git_dir` itself may not change.
+pub fn git_dir_mtime(git_dir: &Path) -> Option<u64> {
+    let mut newest = mtime_nanos(git_dir)?;
+    let mut stack = vec![git_dir.join("refs")];
+    while let Some(dir) = stack.pop() {
+        let Ok(rd) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        newest = newest.max(mtime_nanos(&dir).unwrap_or(0));
+        for entry in rd.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                stack.push(entry.path());
+            }
+        }
+    }
+    Some(newest)
+}
+
+fn mtime_nanos(path: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_nanos() as u64)
+}
+
+/// True when a project's `device` is known and differs from the system's (`--external-only`).
+pub fn on_external_device(device: Option<u64>, system_device: Option<u64>) -> bool {
+    matches!((device, system_device), (Some(d), Some(s)) if d != s)
+}
+
+/// Total bytes of regular files under `path`, skipping any directory named `skip` directly
+/// inside it (e.g. `.git` for working-tree size). Symlinks are not followed.
+pub fn dir_size(path: &Path, skip: Option<&str>) -> u64 {
+    let mut total = 0;
+    let mut stack = vec![path.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(rd) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in rd.flatten() {
+            let Ok(ft) = entry.file_type() else { continue };
+            if ft.is_dir() {
+                if dir != path || skip.is_none_or(|s| entry.file_name() != s) {
+                    stack.push(entry.path());
+                }
+            } else if ft.is_file() {
+                total += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+    }
+    total
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,4 +238,79 @@ mod tests {
         assert!(!info.has_ci);
         assert!(!info.has_license);
     }
+
+    #[test]
+    fn test_dominant_extension() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::create_dir(tmp.path().join("src")).unwrap();
+        for name in ["main.rs", "lib.rs", "util.rs"] {
+            std::fs::write(tmp.path().join("src").join(name), "").unwrap();
+        }
+        std::fs::write(tmp.path().join("build.py"), "").unwrap();
+        std::fs::create_dir(tmp.path().join(".git")).unwrap();
+        for name in ["a.sample", "b.sample", "c.sample", "d.sample"] {
+            std::fs::write(tmp.path().join(".git").join(name), "").unwrap();
+        }
+        assert_eq!(
+            dominant_extension(tmp.path(), EXTENSION_SAMPLE_LIMIT).as_deref(),
+            Some("rs")
+        );
+        assert_eq!(
+            dominant_extension(tmp.path().join("nope").as_path(), 10),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dir_size_skips_named_dir() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::create_dir_all(tmp.path().join("src")).unwrap();
+        std::fs::create_dir_all(tmp.path().join(".git").join("objects")).unwrap();
+        std::fs::write(tmp.path().join("src").join("main.rs"), vec![b'x'; 100]).unwrap();
+        std::fs::write(
+            tmp.path().join(".git").join("objects").join("o"),
+            vec![0u8; 900],
+        )
+        .unwrap();
+        assert_eq!(dir_size(tmp.path(), None), 1000);
+        assert_eq!(dir_size(tmp.path(), Some(".git")), 100);
+        assert_eq!(dir_size(&tmp.path().join(".git"), None), 900);
+    }
+
+    #[test]
+    fn test_on_external_device() {
+        assert!(on_external_device(Some(7), Some(1)));
+        assert!(!on_external_device(Some(1), Some(1)));
+        assert!(!on_external_device(None, Some(1)));
+        assert!(!on_external_device(Some(7), None));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_device_id_same_filesystem() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::create_dir(tmp.path().join("sub")).unwrap();
+        assert!(device_id(tmp.path()).is_some());
+        assert_eq!(device_id(tmp.path()), device_id(&tmp.path().join("sub")));
+        assert_eq!(device_id(&tmp.path().join("missing")), None);
+    }
+
+    #[test]
+    fn test_special_mounts_by_path() {
+        assert!(is_special_fs(Path::new("/proc")));
+        assert!(is_special_fs(Path::new("/sys")));
+        assert!(!is_special_fs(Path::new("/home/pete/proc")));
+        let tmp = TempDir::new().unwrap();
+        assert!(!is_special_fs(tmp.path()));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_procfs_detected_by_type() {
+        // Not in SPECIAL_MOUNTS, so only the filesystem type can flag it
+        let self_dir = Path::new("/proc/self");
+        if self_dir.is_dir() {
+            assert!(is_special_fs(self_dir));
+        }
+    }
 }
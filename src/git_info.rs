@@ -1,27 +1,163 @@
 use std::collections::HashSet;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
 use std::time::{Duration, UNIX_EPOCH};
 
-use chrono::{DateTime, Utc};
-use git2::{BranchType, Repository};
+use chrono::{DateTime, FixedOffset, Utc};
+use git2::{
+    BranchType, Cred, CredentialType, Direction, RemoteCallbacks, Repository, RepositoryState,
+};
+
+use crate::metadata::{ExtractOptions, Remote};
 
 #[derive(Default)]
 pub struct GitInfo {
     pub is_git: bool,
     pub is_worktree: bool,
     pub has_remote: bool,
+    pub remote_branches: Option<u32>,
     pub origin_url: Option<String>,
     pub is_on_github: bool,
-    pub unpushed_count: u32,
+    pub trusted_remote: bool,
+    pub unpushed_count: Option<u32>,
     pub oldest_unpushed: Option<String>,
     pub newest_unpushed: Option<String>,
     pub branches_with_unpushed: Vec<String>,
-    pub total_commits: u32,
+    pub total_commits: Option<u32>,
+    pub newest_commit: Option<String>,
+    pub newest_committer: Option<String>,
     pub last_modified: Option<String>,
+    pub head_detached: bool,
+    pub upstream_default: Option<String>,
+    pub interrupted_state: Option<String>,
+    pub is_shallow: bool,
+    pub pack_size: Option<u64>,
+    pub is_dirty: Option<bool>,
+    pub healthy: Option<bool>,
+    pub health_note: Option<String>,
+    pub error: Option<String>,
+    pub unmerged_branches: Option<u32>,
+    pub behind: Option<u32>,
+    pub branch_ahead: Option<u32>,
+    pub branch_behind: Option<u32>,
+    pub uses_lfs: bool,
+    pub ci: Option<String>,
+    pub previously_uploaded: Option<bool>,
+    pub head_commit: Option<String>,
+    pub head_signed: Option<bool>,
+    pub locked: Option<bool>,
+    pub hooks: Option<bool>,
+    pub mailmap: Option<bool>,
+    pub classification: Option<String>,
+    pub user_name: Option<String>,
+    pub user_email: Option<String>,
+    pub repo_id: Option<String>,
+    pub worktrees: Option<Vec<String>>,
+    pub remotes: Option<Vec<Remote>>,
+    pub tag_count: Option<u32>,
+    pub head_on_remote: Option<bool>,
+    pub matching_commits: Option<u32>,
+    pub contributors: Option<u32>,
+    pub has_commit_graph: Option<bool>,
+    pub origin_reachable: Option<bool>,
+}
+
+pub fn extract_git_info(path: &Path, opts: &ExtractOptions) -> GitInfo {
+    let mut info = match Repository::open(path) {
+        // Still a repo, just one this git2 can't read: report it as unhealthy, and as an
+        // error so a normal run notices it too
+        Err(e) if is_unsupported_format(&e) => {
+            let note = format!("unsupported repository format: {}", e.message());
+            GitInfo {
+                is_git: true,
+                healthy: Some(false),
+                health_note: Some(note.clone()),
+                error: Some(note),
+                ..Default::default()
+            }
+        }
+        opened => match opened
+            .map_err(anyhow::Error::from)
+            .and_then(|repo| inner(repo, opts))
+        {
+            Ok(info) => info,
+            Err(e) if path.join(".git").exists() => GitInfo {
+                error: Some(e.to_string()),
+                ..Default::default()
+            },
+            // Not a repo: no commits to count
+            _ => GitInfo {
+                total_commits: Some(0),
+                unpushed_count: Some(0),
+                ..Default::default()
+            },
+        },
+    };
+    // Corruption usually makes `inner` bail out, so verification runs on its own
+    if opts.verify
+        && let Ok(repo) = Repository::open(path)
+    {
+        info.is_git = true;
+        let health = verify(&repo);
+        info.healthy = Some(health.is_ok());
+        info.health_note = health.err();
+    }
+    info
 }
 
-pub fn extract_git_info(path: &Path) -> GitInfo {
-    inner(path).unwrap_or_default()
+/// True for libgit2's refusal to open a repo written by a newer git: a
+/// `core.repositoryformatversion` or `extensions.*` setting it doesn't know. Both come
+/// back from [`Repository::open`] as a generic error of the repository class; a repo
+/// that isn't there at all is `NotFound` instead.
+fn is_unsupported_format(e: &git2::Error) -> bool {
+    e.class() == git2::ErrorClass::Repository && e.code() == git2::ErrorCode::GenericError
+}
+
+/// Refs whose targets are looked up by [`verify`]; keeps the check bounded on huge repos.
+const VERIFY_REF_SAMPLE: usize = 32;
+
+/// Lightweight integrity check, far cheaper than `git fsck`: HEAD resolves to a readable
+/// commit and tree, the tree's top-level objects exist, and a sample of refs point at
+/// existing objects. Returns a note describing the first problem found.
+fn verify(repo: &Repository) -> Result<(), String> {
+    match repo.head() {
+        // A fresh repo with no commits is fine
+        Err(e) if e.code() == git2::ErrorCode::UnbornBranch => {}
+        Err(e) => return Err(format!("HEAD does not resolve: {}", e.message())),
+        Ok(head) => {
+            let commit = head
+                .peel_to_commit()
+                .map_err(|e| format!("HEAD commit unreadable: {}", e.message()))?;
+            let tree = commit
+                .tree()
+                .map_err(|e| format!("HEAD tree unreadable: {}", e.message()))?;
+            for entry in tree.iter() {
+                repo.find_object(entry.id(), None).map_err(|_| {
+                    format!(
+                        "missing object {} ({})",
+                        entry.id(),
+                        entry.name().unwrap_or("?")
+                    )
+                })?;
+            }
+        }
+    }
+
+    let refs = repo
+        .references()
+        .map_err(|e| format!("refs unreadable: {}", e.message()))?;
+    for reference in refs.take(VERIFY_REF_SAMPLE) {
+        let reference = reference.map_err(|e| format!("bad ref: {}", e.message()))?;
+        if let Some(oid) = reference.target() {
+            repo.find_object(oid, None).map_err(|_| {
+                format!(
+                    "{} points at missing object {oid}",
+                    reference.name().unwrap_or("?")
+                )
+            })?;
+        }
+    }
+    Ok(())
 }
 
 fn to_iso8601(secs: i64) -> String {
@@ -29,15 +165,46 @@ fn to_iso8601(secs: i64) -> String {
     dt.format("%Y-%m-%dT%H:%M:%SZ").to_string()
 }
 
-fn inner(path: &Path) -> anyhow::Result<GitInfo> {
-    let repo = Repository::open(path)?;
+/// A commit time as RFC 3339: UTC by default, or with `--author-local` in the offset the
+/// commit recorded, so the day matches what `git log` shows.
+fn commit_time_iso(secs: i64, offset_minutes: i32, author_local: bool) -> String {
+    let offset = FixedOffset::east_opt(offset_minutes * 60);
+    match (author_local, DateTime::from_timestamp(secs, 0), offset) {
+        (true, Some(utc), Some(offset)) => utc
+            .with_timezone(&offset)
+            .format("%Y-%m-%dT%H:%M:%S%:z")
+            .to_string(),
+        _ => to_iso8601(secs),
+    }
+}
 
+fn inner(repo: Repository, opts: &ExtractOptions) -> anyhow::Result<GitInfo> {
     // origin URL
     let origin_url = repo
         .find_remote("origin")
         .ok()
         .and_then(|r| r.url().map(|s| s.to_string()));
-    let has_remote = !repo.remotes()?.is_empty();
+    let remote_names = repo.remotes()?;
+    let has_remote = !remote_names.is_empty();
+    let trusted_remote = !opts.trusted_remotes.is_empty()
+        && remote_names
+            .iter()
+            .flatten()
+            .filter_map(|name| repo.find_remote(name).ok())
+            .any(|r| {
+                r.url()
+                    .is_some_and(|url| is_trusted_url(url, &opts.trusted_remotes))
+            });
+    // Remote-tracking branches, not counting symbolic refs like origin/HEAD
+    let remote_branches = repo
+        .branches(Some(BranchType::Remote))
+        .ok()
+        .map(|branches| {
+            branches
+                .flatten()
+                .filter(|(b, _)| b.get().kind() == Some(git2::ReferenceType::Direct))
+                .count() as u32
+        });
     let is_on_github = origin_url
         .as_deref()
         .map(|u| u.contains("github.com"))
@@ -56,17 +223,64 @@ fn inner(path: &Path) -> anyhow::Result<GitInfo> {
         .collect();
 
     // Total commits: walk from all local branch tips
+    // Newest commit: latest branch tip time (commit dates are close enough to monotonic)
     let mut total_revwalk = repo.revwalk()?;
+    let mut newest: Option<(i64, Option<String>)> = None;
     for (branch, _) in repo.branches(Some(BranchType::Local))?.flatten() {
         if let Some(oid) = branch.get().target() {
             let _ = total_revwalk.push(oid);
+            if let Ok(commit) = repo.find_commit(oid) {
+                let secs = commit.time().seconds();
+                if newest.as_ref().is_none_or(|(best, _)| secs > *best) {
+                    let email = commit.committer().email().map(|e| e.to_string());
+                    newest = Some((secs, email));
+                }
+            }
         }
     }
-    let total_commits = total_revwalk.count() as u32;
+    // The same walk tests commit messages for --message-matches and collects authors
+    // for --show-contributors
+    let mut total_commits = 0;
+    let mut matching_commits = opts.message_pattern.as_ref().map(|_| 0);
+    let mut authors: Option<HashSet<String>> = opts.contributors.then(HashSet::new);
+    if !opts.newest_only || opts.message_pattern.is_some() || authors.is_some() {
+        let max_examined = opts.max_commits.unwrap_or(usize::MAX);
+        for (i, oid) in total_revwalk.enumerate() {
+            total_commits += 1;
+            // Without a count column the first match settles it
+            let test_message =
+                matching_commits.is_some_and(|n| opts.count_message_matches || n == 0);
+            if i >= max_examined || !(test_message || authors.is_some()) {
+                continue;
+            }
+            let Ok(commit) = repo.find_commit(oid?) else {
+                continue;
+            };
+            if let (Some(pattern), Some(matches)) =
+                (&opts.message_pattern, matching_commits.as_mut())
+                && test_message
+                && commit.message().is_some_and(|m| pattern.is_match(m))
+            {
+                *matches += 1;
+            }
+            if let Some(authors) = authors.as_mut()
+                && let Some(email) = commit.author().email()
+            {
+                authors.insert(email.to_lowercase());
+            }
+        }
+    }
+    let contributors = authors.map(|a| a.len() as u32);
+    let total_commits = (!opts.newest_only).then_some(total_commits);
+    let (newest_commit, newest_committer) = match newest {
+        Some((secs, email)) => (Some(to_iso8601(secs)), email),
+        None => (None, None),
+    };
 
     // Unpushed commits: per branch, hide all remote refs
     let mut branches_with_unpushed: Vec<String> = Vec::new();
-    let mut all_unpushed_secs: Vec<i64> = Vec::new();
+    // (seconds, recorded offset in minutes) per unpushed commit
+    let mut all_unpushed: Vec<(i64, i32)> = Vec::new();
 
     for branch_result in repo.branches(Some(BranchType::Local))? {
         let (branch, _) = branch_result?;
@@ -81,46 +295,602 @@ fn inner(path: &Path) -> anyhow::Result<GitInfo> {
         for &remote_oid in &remote_oids {
             let _ = revwalk.hide(remote_oid);
         }
+        // The default order yields the tip first and parses history lazily, so
+        // --newest-only can stop there without touching the (possibly huge) rest.
+        // Explicit time sorting would force a full walk up front.
+        let limit = if opts.newest_only { 1 } else { usize::MAX };
 
-        let mut branch_secs: Vec<i64> = Vec::new();
-        for oid_result in revwalk {
+        let mut branch_times: Vec<(i64, i32)> = Vec::new();
+        for oid_result in revwalk.take(limit) {
             let commit = repo.find_commit(oid_result?)?;
-            branch_secs.push(commit.time().seconds());
+            let time = commit.time();
+            branch_times.push((time.seconds(), time.offset_minutes()));
         }
 
-        if !branch_secs.is_empty() {
+        if !branch_times.is_empty() {
             branches_with_unpushed.push(branch_name);
-            all_unpushed_secs.extend(branch_secs);
+            all_unpushed.extend(branch_times);
         }
     }
 
-    let unpushed_count = all_unpushed_secs.len() as u32;
-    let oldest_unpushed = all_unpushed_secs.iter().copied().min().map(to_iso8601);
-    let newest_unpushed = all_unpushed_secs.iter().copied().max().map(to_iso8601);
-
-    // last_modified: most recent mtime in git index
-    let index = repo.index()?;
-    let last_modified = index
+    // With --newest-only the count and oldest are unknown; leave them empty
+    let (unpushed_count, oldest_unpushed) = if opts.newest_only {
+        (None, None)
+    } else {
+        (
+            Some(all_unpushed.len() as u32),
+            all_unpushed
+                .iter()
+                .min()
+                .map(|&(secs, offset)| commit_time_iso(secs, offset, opts.author_local)),
+        )
+    };
+    let newest_unpushed = all_unpushed
         .iter()
-        .map(|e| e.mtime.seconds() as i64)
         .max()
-        .map(to_iso8601);
+        .map(|&(secs, offset)| commit_time_iso(secs, offset, opts.author_local));
+
+    // last_modified: most recent mtime in git index (bare repos have none)
+    let last_modified = repo.index().ok().and_then(|index| {
+        index
+            .iter()
+            .map(|e| e.mtime.seconds() as i64)
+            .max()
+            .map(to_iso8601)
+    });
+
+    // Detached HEAD: left mid-bisect, mid-rebase, or after checking out a commit
+    let head_detached = repo.head_detached().unwrap_or(false);
+
+    let interrupted_state = interrupted_state_name(repo.state()).map(|s| s.to_string());
+
+    let pack_size = opts.pack_size.then(|| pack_size(repo.path()));
+
+    // Unborn repos have no HEAD commit yet
+    let head_oid = repo.head().ok().and_then(|h| h.target());
+    // A GPG or SSH signature lives in the commit's `gpgsig` header
+    let head_signed = head_oid
+        .filter(|_| opts.signed)
+        .map(|oid| repo.extract_signature(&oid, None).is_ok());
+
+    let locked = opts.locks.then(|| has_lock_file(repo.path()));
+    let hooks = opts.hooks.then(|| has_custom_hooks(&hooks_dir(&repo)));
+    let mailmap = opts.hooks.then(|| {
+        repo.workdir()
+            .unwrap_or(repo.path())
+            .join(".mailmap")
+            .exists()
+    });
+
+    let (user_name, user_email) = if opts.identity {
+        local_identity(&repo)
+    } else {
+        (None, None)
+    };
+
+    let classification = if opts.classify {
+        classify(&repo, origin_url.as_deref()).map(str::to_string)
+    } else {
+        None
+    };
+
+    let repo_id = opts
+        .repo_id
+        .then(|| root_commit(&repo))
+        .flatten()
+        .map(|oid| oid.to_string());
+
+    let worktrees = opts.worktrees.then(|| linked_worktrees(&repo));
+    let remotes = opts.remotes.then(|| {
+        remote_names
+            .iter()
+            .flatten()
+            .filter_map(|name| repo.find_remote(name).ok())
+            .filter_map(|r| {
+                Some(Remote {
+                    name: r.name()?.to_string(),
+                    url: r.url()?.to_string(),
+                })
+            })
+            .collect()
+    });
+
+    let tag_count = if opts.tags {
+        Some(repo.tag_names(None)?.len() as u32)
+    } else {
+        None
+    };
+
+    let head_on_remote = if opts.head_on_remote {
+        head_on_remote(&repo, &remote_oids)
+    } else {
+        None
+    };
+
+    let unmerged_branches = if opts.unmerged {
+        unmerged_branch_count(&repo)
+    } else {
+        None
+    };
+
+    let behind = opts.behind.then(|| behind_upstream(&repo)).flatten();
+    let (branch_ahead, branch_behind) = opts
+        .branch_delta
+        .as_ref()
+        .and_then(|(a, b)| branch_delta(&repo, a, b))
+        .unzip();
+
+    let is_dirty = if opts.dirty {
+        worktree_dirty(&repo)
+    } else {
+        None
+    };
+
+    let upstream_default = if opts.upstream_default {
+        upstream_default_branch(&repo, opts.remote_connect)
+    } else {
+        None
+    };
+
+    let previously_uploaded = opts
+        .check_reflog
+        .then(|| origin_url.is_some() || has_origin_reflog(&repo));
 
     Ok(GitInfo {
         is_git: true,
         is_worktree: false, // worktrees are skipped in traversal; never reported
         has_remote,
+        remote_branches,
         origin_url,
         is_on_github,
+        trusted_remote,
         unpushed_count,
         oldest_unpushed,
         newest_unpushed,
         branches_with_unpushed,
         total_commits,
+        newest_commit,
+        newest_committer,
         last_modified,
+        head_detached,
+        upstream_default,
+        interrupted_state,
+        // Grafted history: counts and dates only cover what was fetched
+        is_shallow: repo.is_shallow(),
+        pack_size,
+        is_dirty,
+        healthy: None, // filled in by extract_git_info
+        health_note: None,
+        error: None,
+        unmerged_branches,
+        behind,
+        branch_ahead,
+        branch_behind,
+        uses_lfs: uses_lfs(&repo),
+        ci: ci_system(&repo).map(str::to_string),
+        previously_uploaded,
+        head_commit: head_oid.map(|oid| oid.to_string()),
+        head_signed,
+        locked,
+        hooks,
+        mailmap,
+        classification,
+        user_name,
+        user_email,
+        repo_id,
+        worktrees,
+        remotes,
+        tag_count,
+        head_on_remote,
+        matching_commits,
+        contributors,
+        has_commit_graph: opts.commit_graph.then(|| has_commit_graph(repo.path())),
+        origin_reachable: opts
+            .validate_origin
+            .and_then(|timeout| origin_reachable(&repo, timeout)),
+    })
+}
+
+/// Total bytes of `objects/pack/*.pack` under `git_dir`: history size, as opposed to
+/// working-tree size. Loose objects are not counted.
+/// Whether the repo at `path` has uncommitted changes, re-checked for a record otherwise
+/// copied from an earlier scan (`--since-last-scan`).
+pub fn is_dirty(path: &Path) -> Option<bool> {
+    Repository::open(path)
+        .ok()
+        .and_then(|repo| worktree_dirty(&repo))
+}
+
+/// Bare repos have no work tree to be dirty.
+fn worktree_dirty(repo: &Repository) -> Option<bool> {
+    if repo.is_bare() {
+        return None;
+    }
+    let mut status_opts = git2::StatusOptions::new();
+    status_opts.include_untracked(true).include_ignored(false);
+    // Unknown rather than failing the whole repo when the index can't be read
+    repo.statuses(Some(&mut status_opts))
+        .ok()
+        .map(|statuses| !statuses.is_empty())
+}
+
+fn pack_size(git_dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(git_dir.join("objects").join("pack")) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "pack"))
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// A remote-tracking branch of `origin` has reflog entries: something was fetched from or
+/// pushed to an origin at some point, even if the remote has since been removed.
+fn has_origin_reflog(repo: &Repository) -> bool {
+    let Ok(refs) = repo.references_glob("refs/remotes/origin/*") else {
+        return false;
+    };
+    refs.flatten()
+        .filter_map(|r| r.name().map(str::to_string))
+        .any(|name| repo.reflog(&name).is_ok_and(|log| !log.is_empty()))
+}
+
+/// Whether the HEAD commit is reachable from some remote-tracking tip (is the tip or one of
+/// its ancestors), so it exists on a remote. `None` when HEAD is unborn.
+fn head_on_remote(repo: &Repository, remote_tips: &HashSet<git2::Oid>) -> Option<bool> {
+    let head = repo.head().ok()?.target()?;
+    Some(
+        remote_tips
+            .iter()
+            .any(|&tip| tip == head || repo.graph_descendant_of(tip, head).unwrap_or(false)),
+    )
+}
+
+/// A commit-graph file (single or split chain) exists under `git_dir`. libgit2 reads it
+/// automatically unless `core.commitGraph` is false, which makes history walks much cheaper
+/// on large repos; repos without one fall back to parsing every commit object.
+fn has_commit_graph(git_dir: &Path) -> bool {
+    let info = git_dir.join("objects").join("info");
+    info.join("commit-graph").is_file()
+        || info
+            .join("commit-graphs")
+            .join("commit-graph-chain")
+            .is_file()
+}
+
+/// Git LFS in use: `.gitattributes` routes something through `filter=lfs`, or LFS has
+/// stored objects under `.git/lfs`.
+fn uses_lfs(repo: &Repository) -> bool {
+    let attributes = repo
+        .workdir()
+        .and_then(|dir| std::fs::read_to_string(dir.join(".gitattributes")).ok())
+        .is_some_and(|text| {
+            text.lines()
+                .filter(|l| !l.trim_start().starts_with('#'))
+                .any(|l| l.split_whitespace().any(|attr| attr == "filter=lfs"))
+        });
+    attributes || repo.path().join("lfs").is_dir()
+}
+
+/// Where each CI system keeps its configuration, relative to the working tree.
+/// The first match wins.
+const CI_CONFIGS: &[(&str, &str)] = &[
+    (".github/workflows", "github-actions"),
+    (".gitlab-ci.yml", "gitlab-ci"),
+    (".circleci/config.yml", "circleci"),
+    (".travis.yml", "travis"),
+    ("azure-pipelines.yml", "azure-pipelines"),
+    ("bitbucket-pipelines.yml", "bitbucket-pipelines"),
+    ("Jenkinsfile", "jenkins"),
+    (".buildkite", "buildkite"),
+    (".drone.yml", "drone"),
+];
+
+/// CI system configured in the working tree, if any. Bare repos have none.
+fn ci_system(repo: &Repository) -> Option<&'static str> {
+    let workdir = repo.workdir()?;
+    CI_CONFIGS
+        .iter()
+        .find(|(config, _)| workdir.join(config).exists())
+        .map(|&(_, name)| name)
+}
+
+/// Local branch that counts as the default, the first of these that exists locally:
+/// origin's default (per the cached origin/HEAD), `init.defaultBranch`, the branch HEAD
+/// is on, `main`, `master`. Repos defaulting to `trunk` or `develop` resolve correctly.
+fn local_default_branch(repo: &Repository) -> Option<git2::Branch<'_>> {
+    let configured = repo
+        .config()
+        .and_then(|config| config.get_string("init.defaultBranch"))
+        .ok();
+    let head = repo
+        .head()
+        .ok()
+        .filter(|head| head.is_branch())
+        .and_then(|head| head.shorthand().map(str::to_string));
+    upstream_default_branch(repo, false)
+        .into_iter()
+        .chain(configured)
+        .chain(head)
+        .chain(["main".to_string(), "master".to_string()])
+        .find_map(|name| repo.find_branch(&name, BranchType::Local).ok())
+}
+
+/// Number of local branches whose tip is not contained in the default branch, or `None`
+/// when there is no default branch to compare against or the branches can't be read.
+fn unmerged_branch_count(repo: &Repository) -> Option<u32> {
+    let default_tip = local_default_branch(repo)?.get().target()?;
+    let mut count = 0;
+    for (branch, _) in repo.branches(Some(BranchType::Local)).ok()?.flatten() {
+        let Some(tip) = branch.get().target() else {
+            continue;
+        };
+        if tip != default_tip && !repo.graph_descendant_of(default_tip, tip).ok()? {
+            count += 1;
+        }
+    }
+    Some(count)
+}
+
+/// Commits on the default branch's upstream (as last fetched) that the branch lacks, or
+/// `None` when there is no default branch or it tracks nothing. Never touches the network.
+fn behind_upstream(repo: &Repository) -> Option<u32> {
+    let branch = local_default_branch(repo)?;
+    let local = branch.get().target()?;
+    let upstream = branch.upstream().ok()?.get().target()?;
+    let (_, behind) = repo.graph_ahead_behind(local, upstream).ok()?;
+    Some(behind as u32)
+}
+
+/// Commits on local branch `a` that `b` lacks, and on `b` that `a` lacks, counted from
+/// their merge base. `None` unless both branches exist.
+fn branch_delta(repo: &Repository, a: &str, b: &str) -> Option<(u32, u32)> {
+    let tip = |name| {
+        repo.find_branch(name, BranchType::Local)
+            .ok()?
+            .get()
+            .target()
+    };
+    let (ahead, behind) = repo.graph_ahead_behind(tip(a)?, tip(b)?).ok()?;
+    Some((ahead as u32, behind as u32))
+}
+
+/// Time of the earliest commit reachable from HEAD or any local branch, like
+/// `oldest_unpushed` but over all history. `None` for non-repos and repos without commits.
+///
+/// Walks the entire history, so on large repos this costs as much as counting commits.
+pub fn first_commit_time(path: &Path) -> Option<DateTime<Utc>> {
+    let repo = Repository::open(path).ok()?;
+    let mut revwalk = repo.revwalk().ok()?;
+    let _ = revwalk.push_head();
+    revwalk.push_glob("heads").ok()?;
+    let oldest = revwalk
+        .filter_map(|oid| repo.find_commit(oid.ok()?).ok())
+        .map(|commit| commit.time().seconds())
+        .min()?;
+    DateTime::from_timestamp(oldest, 0)
+}
+
+/// Reduce a remote URL to `host/owner/repo` form so that clones made over SSH, HTTPS or
+/// scp-style syntax, with or without `.git`, compare equal (`--dedup-by-origin`).
+pub fn normalize_origin_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest.to_string(),
+        // scp-like `git@host:owner/repo`
+        None => url.replacen(':', "/", 1),
+    };
+    let rest = rest.rsplit_once('@').map_or(rest.as_str(), |(_, r)| r);
+    let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+    // Drop any port; the same repo is reachable over several
+    let host = host.split(':').next().unwrap_or(host).to_lowercase();
+    format!("{host}/{path}")
+}
+
+/// Lock files git creates for the length of an operation; one left behind means the
+/// operation was interrupted or its process crashed, and git will refuse to start another.
+const LOCK_FILES: &[&str] = &[
+    "index.lock",
+    "HEAD.lock",
+    "ORIG_HEAD.lock",
+    "config.lock",
+    "packed-refs.lock",
+    "shallow.lock",
+];
+
+fn has_lock_file(git_dir: &Path) -> bool {
+    LOCK_FILES.iter().any(|name| git_dir.join(name).exists())
+}
+
+/// Where git looks for hooks: `core.hooksPath`, relative to the work tree (the git dir
+/// when bare), or else `hooks` in the common git dir, which linked worktrees share.
+fn hooks_dir(repo: &Repository) -> PathBuf {
+    match repo.config().and_then(|c| c.get_path("core.hooksPath")) {
+        Ok(path) => repo.workdir().unwrap_or(repo.path()).join(path),
+        Err(_) => repo.commondir().join("hooks"),
+    }
+}
+
+/// True when the hooks directory holds anything besides the `*.sample` files `git init`
+/// installs.
+fn has_custom_hooks(hooks: &Path) -> bool {
+    std::fs::read_dir(hooks).is_ok_and(|entries| {
+        entries
+            .flatten()
+            .any(|entry| !entry.file_name().as_encoded_bytes().ends_with(b".sample"))
     })
 }
 
+/// `user.name` and `user.email` set in the repo's own config. Global and system values
+/// are ignored: only a per-repo identity is of interest.
+fn local_identity(repo: &Repository) -> (Option<String>, Option<String>) {
+    let Ok(local) = repo
+        .config()
+        .and_then(|c| c.open_level(git2::ConfigLevel::Local))
+    else {
+        return (None, None);
+    };
+    (
+        local.get_string("user.name").ok(),
+        local.get_string("user.email").ok(),
+    )
+}
+
+/// How an uploaded repo relates to what it was cloned from: `mirror` when origin is
+/// configured as a mirror (`git clone --mirror`), `fork` when an `upstream` remote points
+/// somewhere other than origin, `original` otherwise. `None` without an origin.
+fn classify(repo: &Repository, origin_url: Option<&str>) -> Option<&'static str> {
+    let origin_url = origin_url?;
+    let mirror = repo
+        .config()
+        .and_then(|c| c.get_bool("remote.origin.mirror"))
+        .unwrap_or(false);
+    if mirror {
+        return Some("mirror");
+    }
+    let upstream = repo.find_remote("upstream").ok();
+    let forked = upstream
+        .as_ref()
+        .and_then(|r| r.url())
+        .is_some_and(|url| normalize_origin_url(url) != normalize_origin_url(origin_url));
+    Some(if forked { "fork" } else { "original" })
+}
+
+/// The root commit (one without parents) of HEAD's history, which every clone shares
+/// whatever its origin or host. Histories joined from several roots report the earliest,
+/// ties broken by SHA so the choice is the same everywhere. Walks the full history.
+fn root_commit(repo: &Repository) -> Option<git2::Oid> {
+    let mut revwalk = repo.revwalk().ok()?;
+    revwalk.push_head().ok()?;
+    revwalk
+        .filter_map(|oid| repo.find_commit(oid.ok()?).ok())
+        .filter(|commit| commit.parent_count() == 0)
+        .map(|commit| (commit.time().seconds(), commit.id()))
+        .min()
+        .map(|(_, oid)| oid)
+}
+
+/// Paths of the repo's linked worktrees (`git worktree add`), including ones whose
+/// directory has since been deleted but not pruned.
+fn linked_worktrees(repo: &Repository) -> Vec<String> {
+    let Ok(names) = repo.worktrees() else {
+        return Vec::new();
+    };
+    names
+        .iter()
+        .flatten()
+        .filter_map(|name| repo.find_worktree(name).ok())
+        .map(|wt| wt.path().display().to_string())
+        .collect()
+}
+
+/// True when `url` contains any of the `--trusted-remote` patterns, either as written or
+/// with both normalized, so `git@host:org` also trusts `https://host/org/repo`.
+fn is_trusted_url(url: &str, patterns: &[String]) -> bool {
+    let normalized = normalize_origin_url(url);
+    patterns.iter().any(|p| {
+        url.contains(p.as_str())
+            || normalized.contains(normalize_origin_url(p).trim_end_matches('/'))
+    })
+}
+
+/// Name of an unfinished merge/rebase/etc., or `None` when the repo is clean.
+fn interrupted_state_name(state: RepositoryState) -> Option<&'static str> {
+    match state {
+        RepositoryState::Clean => None,
+        RepositoryState::Merge => Some("merge"),
+        RepositoryState::Revert => Some("revert"),
+        RepositoryState::RevertSequence => Some("revert-sequence"),
+        RepositoryState::CherryPick => Some("cherry-pick"),
+        RepositoryState::CherryPickSequence => Some("cherry-pick-sequence"),
+        RepositoryState::Bisect => Some("bisect"),
+        RepositoryState::Rebase => Some("rebase"),
+        RepositoryState::RebaseInteractive => Some("rebase-interactive"),
+        RepositoryState::RebaseMerge => Some("rebase-merge"),
+        RepositoryState::ApplyMailbox => Some("am"),
+        RepositoryState::ApplyMailboxOrRebase => Some("am-or-rebase"),
+    }
+}
+
+/// Name of `origin`'s default branch.
+///
+/// Reads the cached `refs/remotes/origin/HEAD` symref (written by `git clone` or
+/// `git remote set-head`), so no network access is needed. With `connect`, asks the
+/// remote directly first and falls back to the cached ref if that fails.
+fn upstream_default_branch(repo: &Repository, connect: bool) -> Option<String> {
+    if connect && let Some(branch) = live_default_branch(repo) {
+        return Some(branch);
+    }
+    let head = repo.find_reference("refs/remotes/origin/HEAD").ok()?;
+    let target = head.symbolic_target()?;
+    target
+        .strip_prefix("refs/remotes/origin/")
+        .map(|s| s.to_string())
+}
+
+/// Connect to `origin` for fetching, which reads its ref advertisement as `git ls-remote`
+/// would. libgit2's server timeouts make a hung connection fail after `timeout`. SSH
+/// remotes authenticate through the ssh-agent and HTTPS ones through git's credential
+/// helpers. `None` when the repo has no origin.
+fn origin_reachable(repo: &Repository, timeout: Duration) -> Option<bool> {
+    let mut remote = repo.find_remote("origin").ok()?;
+    set_server_timeouts(timeout);
+    let config = repo.config().ok();
+    let mut tried = CredentialType::empty();
+    let mut callbacks = RemoteCallbacks::new();
+    // libgit2 asks again after a rejected credential; offer each kind once
+    callbacks.credentials(move |url, username, allowed| {
+        let username = username.unwrap_or("git");
+        let untried = allowed - tried;
+        tried |= allowed;
+        if untried.contains(CredentialType::USERNAME) {
+            Cred::username(username)
+        } else if untried.contains(CredentialType::SSH_KEY) {
+            Cred::ssh_key_from_agent(username)
+        } else if untried.contains(CredentialType::USER_PASS_PLAINTEXT)
+            && let Some(config) = &config
+        {
+            Cred::credential_helper(config, url, Some(username))
+        } else if untried.contains(CredentialType::DEFAULT) {
+            Cred::default()
+        } else {
+            Err(git2::Error::from_str("no usable credentials"))
+        }
+    });
+    Some(
+        remote
+            .connect_auth(Direction::Fetch, Some(callbacks), None)
+            .is_ok(),
+    )
+}
+
+/// Bound libgit2's connects and reads by `timeout`. The options are process-wide and
+/// unsynchronized, so they are set once, by the first probe; every probe in a run shares
+/// the same `--origin-timeout`.
+fn set_server_timeouts(timeout: Duration) {
+    static SET: Once = Once::new();
+    SET.call_once(|| {
+        let ms = timeout.as_millis().min(i32::MAX as u128) as std::ffi::c_int;
+        // SAFETY: plain integer options, written once before this probe's connection;
+        // connections made elsewhere only ever read them.
+        unsafe {
+            let _ = git2::opts::set_server_connect_timeout_in_milliseconds(ms);
+            let _ = git2::opts::set_server_timeout_in_milliseconds(ms);
+        }
+    });
+}
+
+fn live_default_branch(repo: &Repository) -> Option<String> {
+    let mut remote = repo.find_remote("origin").ok()?;
+    remote.connect(Direction::Fetch).ok()?;
+    let buf = remote.default_branch().ok()?;
+    let name = buf.as_str()?;
+    Some(name.strip_prefix("refs/heads/").unwrap_or(name).to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,22 +930,23 @@ mod tests {
     #[test]
     fn test_no_git_repo() {
         let tmp = TempDir::new().unwrap();
-        let info = extract_git_info(tmp.path());
+        let info = extract_git_info(tmp.path(), &ExtractOptions::default());
         assert!(!info.is_git);
-        assert_eq!(info.total_commits, 0);
-        assert_eq!(info.unpushed_count, 0);
+        assert_eq!(info.total_commits, Some(0));
+        assert_eq!(info.unpushed_count, Some(0));
     }
 
     #[test]
     fn test_git_repo_no_remote() {
         let tmp = TempDir::new().unwrap();
         make_repo(tmp.path(), &[1_700_000_000, 1_700_100_000]);
-        let info = extract_git_info(tmp.path());
+        let info = extract_git_info(tmp.path(), &ExtractOptions::default());
         assert!(info.is_git);
         assert!(!info.has_remote);
         assert!(!info.is_on_github);
-        assert_eq!(info.total_commits, 2);
-        assert_eq!(info.unpushed_count, 2); // no remote → all commits are unpushed
+        assert_eq!(info.total_commits, Some(2));
+        assert_eq!(info.newest_commit.as_deref(), Some("2023-11-16T02:00:00Z"));
+        assert_eq!(info.unpushed_count, Some(2)); // no remote → all commits are unpushed
         assert!(info.oldest_unpushed.is_some());
         assert!(info.newest_unpushed.is_some());
         assert!(info.branches_with_unpushed.contains(&"main".to_string()));
@@ -187,7 +958,7 @@ mod tests {
         let repo = make_repo(tmp.path(), &[1_700_000_000]);
         repo.remote("origin", "git@github.com:user/myrepo.git")
             .unwrap();
-        let info = extract_git_info(tmp.path());
+        let info = extract_git_info(tmp.path(), &ExtractOptions::default());
         assert!(info.is_git);
         assert!(info.has_remote);
         assert!(info.is_on_github);
@@ -198,4 +969,990 @@ mod tests {
         // No remote tracking refs → all local commits still count as unpushed
         // (in practice you'd need to fetch and have remote refs, but local-only remote is fine)
     }
+
+    #[test]
+    fn test_detached_head() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        repo.set_head("refs/heads/main").unwrap();
+        assert!(!extract_git_info(tmp.path(), &ExtractOptions::default()).head_detached);
+
+        let oid = repo.refname_to_id("refs/heads/main").unwrap();
+        repo.set_head_detached(oid).unwrap();
+        assert!(extract_git_info(tmp.path(), &ExtractOptions::default()).head_detached);
+    }
+
+    #[test]
+    fn test_interrupted_merge() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        let opts = ExtractOptions::default();
+        assert_eq!(extract_git_info(tmp.path(), &opts).interrupted_state, None);
+
+        let oid = repo.refname_to_id("refs/heads/main").unwrap();
+        std::fs::write(repo.path().join("MERGE_HEAD"), format!("{oid}\n")).unwrap();
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).interrupted_state,
+            Some("merge".to_string())
+        );
+    }
+
+    #[test]
+    fn test_trusted_remote_matches_any_remote() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        repo.remote("origin", "https://github.com/user/repo.git")
+            .unwrap();
+        repo.remote("backup", "ssh://git@nas.local/srv/repo.git")
+            .unwrap();
+        let opts = ExtractOptions {
+            trusted_remotes: vec!["nas.local".to_string()],
+            ..Default::default()
+        };
+        assert!(extract_git_info(tmp.path(), &opts).trusted_remote);
+        assert!(!extract_git_info(tmp.path(), &ExtractOptions::default()).trusted_remote);
+    }
+
+    #[test]
+    fn test_shallow_clone() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        assert!(!extract_git_info(tmp.path(), &ExtractOptions::default()).is_shallow);
+
+        let oid = repo.refname_to_id("refs/heads/main").unwrap();
+        std::fs::write(repo.path().join("shallow"), format!("{oid}\n")).unwrap();
+        assert!(extract_git_info(tmp.path(), &ExtractOptions::default()).is_shallow);
+    }
+
+    #[test]
+    fn test_pack_size_sums_pack_files() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        let pack_dir = repo.path().join("objects").join("pack");
+        std::fs::create_dir_all(&pack_dir).unwrap();
+        std::fs::write(pack_dir.join("pack-a.pack"), vec![0u8; 3000]).unwrap();
+        std::fs::write(pack_dir.join("pack-b.pack"), vec![0u8; 1000]).unwrap();
+        std::fs::write(pack_dir.join("pack-a.idx"), vec![0u8; 500]).unwrap();
+
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).pack_size,
+            None
+        );
+        let opts = ExtractOptions {
+            pack_size: true,
+            ..Default::default()
+        };
+        assert_eq!(extract_git_info(tmp.path(), &opts).pack_size, Some(4000));
+    }
+
+    #[test]
+    fn test_dirty_work_tree() {
+        let tmp = TempDir::new().unwrap();
+        make_repo(tmp.path(), &[1_700_000_000]);
+        let opts = ExtractOptions {
+            dirty: true,
+            ..Default::default()
+        };
+        assert_eq!(extract_git_info(tmp.path(), &opts).is_dirty, Some(false));
+
+        std::fs::write(tmp.path().join("scratch.txt"), "wip").unwrap();
+        assert_eq!(extract_git_info(tmp.path(), &opts).is_dirty, Some(true));
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).is_dirty,
+            None
+        );
+    }
+
+    #[test]
+    fn test_verify_flags_missing_head_object() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        repo.set_head("refs/heads/main").unwrap();
+        let opts = ExtractOptions {
+            verify: true,
+            ..Default::default()
+        };
+        let info = extract_git_info(tmp.path(), &opts);
+        assert_eq!(info.healthy, Some(true));
+        assert_eq!(info.health_note, None);
+
+        std::fs::write(
+            repo.path().join("refs").join("heads").join("main"),
+            "0123456789abcdef0123456789abcdef01234567\n",
+        )
+        .unwrap();
+        let info = extract_git_info(tmp.path(), &opts);
+        assert!(info.is_git);
+        assert_eq!(info.healthy, Some(false));
+        assert!(info.health_note.is_some());
+    }
+
+    #[test]
+    fn test_unreadable_index_leaves_only_dirty_unknown() {
+        let opts = ExtractOptions {
+            dirty: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        make_repo(tmp.path(), &[1_700_000_000, 1_700_000_100]);
+        std::fs::write(tmp.path().join(".git/index"), "not an index").unwrap();
+        let info = extract_git_info(tmp.path(), &opts);
+        assert!(info.is_git);
+        assert_eq!(info.is_dirty, None);
+        assert_eq!(info.total_commits, Some(2));
+    }
+
+    #[test]
+    fn test_counts_remote_branches() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).remote_branches,
+            Some(0)
+        );
+
+        repo.remote("origin", "https://github.com/pete/x.git")
+            .unwrap();
+        let oid = repo.refname_to_id("refs/heads/main").unwrap();
+        for name in ["main", "feature"] {
+            repo.reference(&format!("refs/remotes/origin/{name}"), oid, false, "test")
+                .unwrap();
+        }
+        repo.reference_symbolic(
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/main",
+            false,
+            "test",
+        )
+        .unwrap();
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).remote_branches,
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn test_newest_only_survives_corrupt_deep_history() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000, 1_700_050_000, 1_700_100_000]);
+        // Delete the root commit's loose object: a full walk can no longer finish
+        let tip = repo.refname_to_id("refs/heads/main").unwrap();
+        let root = repo
+            .find_commit(tip)
+            .unwrap()
+            .parent(0)
+            .unwrap()
+            .parent_id(0)
+            .unwrap();
+        let hex = root.to_string();
+        std::fs::remove_file(repo.path().join("objects").join(&hex[..2]).join(&hex[2..])).unwrap();
+
+        let full = extract_git_info(tmp.path(), &ExtractOptions::default());
+        assert_eq!(full.newest_unpushed, None);
+
+        let opts = ExtractOptions {
+            newest_only: true,
+            ..Default::default()
+        };
+        let info = extract_git_info(tmp.path(), &opts);
+        assert_eq!(
+            info.newest_unpushed.as_deref(),
+            Some("2023-11-16T02:00:00Z")
+        );
+        assert_eq!(info.oldest_unpushed, None);
+        assert_eq!(info.unpushed_count, None);
+        assert_eq!(info.total_commits, None);
+    }
+
+    #[test]
+    fn test_unmerged_branches() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000, 1_700_050_000]);
+        let opts = ExtractOptions {
+            unmerged: true,
+            ..Default::default()
+        };
+        // A branch at an older commit of main is merged
+        let main_tip = repo.refname_to_id("refs/heads/main").unwrap();
+        let older = repo.find_commit(main_tip).unwrap().parent(0).unwrap();
+        repo.branch("merged", &older, false).unwrap();
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).unmerged_branches,
+            Some(0)
+        );
+
+        // A commit on top of main that main doesn't have is not
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_100_000, 0)).unwrap();
+        let main_commit = repo.find_commit(main_tip).unwrap();
+        let tree = main_commit.tree().unwrap();
+        repo.commit(
+            Some("refs/heads/feature"),
+            &sig,
+            &sig,
+            "wip",
+            &tree,
+            &[&main_commit],
+        )
+        .unwrap();
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).unmerged_branches,
+            Some(1)
+        );
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).unmerged_branches,
+            None
+        );
+    }
+
+    #[test]
+    fn test_author_local_keeps_recorded_offset() {
+        let tmp = TempDir::new().unwrap();
+        let repo = Repository::init(tmp.path()).unwrap();
+        let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree_oid).unwrap();
+        // 2023-11-14T22:13:20Z, committed in UTC+14 where it was already the 15th
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_000_000, 14 * 60)).unwrap();
+        repo.commit(Some("refs/heads/main"), &sig, &sig, "t", &tree, &[])
+            .unwrap();
+
+        let utc = extract_git_info(tmp.path(), &ExtractOptions::default());
+        assert_eq!(utc.newest_unpushed.as_deref(), Some("2023-11-14T22:13:20Z"));
+        let opts = ExtractOptions {
+            author_local: true,
+            ..Default::default()
+        };
+        let local = extract_git_info(tmp.path(), &opts);
+        assert_eq!(
+            local.newest_unpushed.as_deref(),
+            Some("2023-11-15T12:13:20+14:00")
+        );
+    }
+
+    #[test]
+    fn test_detects_lfs() {
+        let tmp = TempDir::new().unwrap();
+        make_repo(tmp.path(), &[1_700_000_000]);
+        let opts = ExtractOptions::default();
+        std::fs::write(
+            tmp.path().join(".gitattributes"),
+            "*.txt text\n# *.psd filter=lfs\n",
+        )
+        .unwrap();
+        assert!(!extract_git_info(tmp.path(), &opts).uses_lfs);
+
+        std::fs::write(
+            tmp.path().join(".gitattributes"),
+            "*.psd filter=lfs diff=lfs merge=lfs -text\n",
+        )
+        .unwrap();
+        assert!(extract_git_info(tmp.path(), &opts).uses_lfs);
+
+        let other = TempDir::new().unwrap();
+        let repo = make_repo(other.path(), &[1_700_000_000]);
+        std::fs::create_dir_all(repo.path().join("lfs").join("objects")).unwrap();
+        assert!(extract_git_info(other.path(), &opts).uses_lfs);
+    }
+
+    #[test]
+    fn test_check_reflog_stale_origin_ref() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        let tip = repo.refname_to_id("refs/heads/main").unwrap();
+        // Left behind after `origin` was dropped from the config
+        repo.reference("refs/remotes/origin/main", tip, false, "push")
+            .unwrap();
+        assert!(repo.find_remote("origin").is_err());
+
+        let info = extract_git_info(tmp.path(), &ExtractOptions::default());
+        assert_eq!(info.previously_uploaded, None);
+
+        let opts = ExtractOptions {
+            check_reflog: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).previously_uploaded,
+            Some(true)
+        );
+
+        let fresh = TempDir::new().unwrap();
+        make_repo(fresh.path(), &[1_700_000_000]);
+        assert_eq!(
+            extract_git_info(fresh.path(), &opts).previously_uploaded,
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_head_commit() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        repo.set_head("refs/heads/main").unwrap();
+        let tip = repo.refname_to_id("refs/heads/main").unwrap();
+        let info = extract_git_info(tmp.path(), &ExtractOptions::default());
+        assert_eq!(info.head_commit, Some(tip.to_string()));
+
+        let unborn = TempDir::new().unwrap();
+        Repository::init(unborn.path()).unwrap();
+        let info = extract_git_info(unborn.path(), &ExtractOptions::default());
+        assert_eq!(info.head_commit, None);
+    }
+
+    #[test]
+    fn test_head_signed() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        repo.set_head("refs/heads/main").unwrap();
+        let opts = ExtractOptions {
+            signed: true,
+            ..Default::default()
+        };
+        assert_eq!(extract_git_info(tmp.path(), &opts).head_signed, Some(false));
+
+        // Only the header's presence matters, so any armored blob will do
+        let parent = repo.head().unwrap().peel_to_commit().unwrap();
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_000_100, 0)).unwrap();
+        let buf = repo
+            .commit_create_buffer(&sig, &sig, "signed", &parent.tree().unwrap(), &[&parent])
+            .unwrap();
+        let armor =
+            "-----BEGIN PGP SIGNATURE-----\n\niQEzBAABCAAdFiEE\n-----END PGP SIGNATURE-----";
+        let oid = repo
+            .commit_signed(buf.as_str().unwrap(), armor, None)
+            .unwrap();
+        repo.reference("refs/heads/main", oid, true, "signed")
+            .unwrap();
+        assert_eq!(extract_git_info(tmp.path(), &opts).head_signed, Some(true));
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).head_signed,
+            None
+        );
+
+        let unborn = TempDir::new().unwrap();
+        Repository::init(unborn.path()).unwrap();
+        assert_eq!(extract_git_info(unborn.path(), &opts).head_signed, None);
+    }
+
+    #[test]
+    fn test_tag_count() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        let opts = ExtractOptions {
+            tags: true,
+            ..Default::default()
+        };
+        assert_eq!(extract_git_info(tmp.path(), &opts).tag_count, Some(0));
+
+        let tip = repo.refname_to_id("refs/heads/main").unwrap();
+        let target = repo.find_object(tip, None).unwrap();
+        repo.tag_lightweight("v1.0", &target, false).unwrap();
+        assert_eq!(extract_git_info(tmp.path(), &opts).tag_count, Some(1));
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).tag_count,
+            None
+        );
+    }
+
+    #[test]
+    fn test_normalize_origin_url() {
+        let expected = "github.com/pete/lsproj";
+        for url in [
+            "git@github.com:pete/lsproj.git",
+            "https://github.com/pete/lsproj",
+            "https://user@GitHub.com/pete/lsproj.git/",
+            "ssh://git@github.com:22/pete/lsproj.git",
+        ] {
+            assert_eq!(normalize_origin_url(url), expected, "{url}");
+        }
+        assert_ne!(
+            normalize_origin_url("git@github.com:pete/other.git"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_trusted_url_matches_across_protocols() {
+        let patterns = ["git@mirror.example.com:backup".to_string()];
+        assert!(is_trusted_url(
+            "https://Mirror.example.com/backup/tool.git",
+            &patterns
+        ));
+        assert!(is_trusted_url(
+            "git@mirror.example.com:backup/tool.git",
+            &patterns
+        ));
+        assert!(!is_trusted_url("https://github.com/backup/tool", &patterns));
+        let plain = ["example.com".to_string()];
+        assert!(is_trusted_url("ssh://git@example.com:2222/a/b", &plain));
+    }
+
+    #[test]
+    fn test_head_on_remote() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000, 1_700_100_000]);
+        repo.set_head("refs/heads/main").unwrap();
+        let opts = ExtractOptions {
+            head_on_remote: true,
+            ..Default::default()
+        };
+        // A remote with nothing fetched backs up nothing
+        repo.remote("origin", "https://example.com/x.git").unwrap();
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).head_on_remote,
+            Some(false)
+        );
+
+        let tip = repo.refname_to_id("refs/heads/main").unwrap();
+        repo.reference("refs/remotes/origin/main", tip, false, "push")
+            .unwrap();
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).head_on_remote,
+            Some(true)
+        );
+
+        // A stale remote behind local leaves the new HEAD unbacked
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_200_000, 0)).unwrap();
+        let parent = repo.find_commit(tip).unwrap();
+        let tree = parent.tree().unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "local", &tree, &[&parent])
+            .unwrap();
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).head_on_remote,
+            Some(false)
+        );
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).head_on_remote,
+            None
+        );
+    }
+
+    #[test]
+    fn test_message_matches() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        let tip = repo.refname_to_id("refs/heads/main").unwrap();
+        let parent = repo.find_commit(tip).unwrap();
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_100_000, 0)).unwrap();
+        let tree = parent.tree().unwrap();
+        let wip = repo
+            .commit(
+                Some("refs/heads/main"),
+                &sig,
+                &sig,
+                "WIP: half done",
+                &tree,
+                &[&parent],
+            )
+            .unwrap();
+        let wip = repo.find_commit(wip).unwrap();
+        repo.commit(
+            Some("refs/heads/main"),
+            &sig,
+            &sig,
+            "wip again",
+            &tree,
+            &[&wip],
+        )
+        .unwrap();
+
+        let matches = |pattern: &str, count: bool| {
+            let opts = ExtractOptions {
+                message_pattern: Some(regex::Regex::new(pattern).unwrap()),
+                count_message_matches: count,
+                ..Default::default()
+            };
+            extract_git_info(tmp.path(), &opts).matching_commits
+        };
+        assert_eq!(matches("(?i)wip", true), Some(2));
+        assert_eq!(matches("(?i)wip", false), Some(1));
+        assert_eq!(matches("TODO", true), Some(0));
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).matching_commits,
+            None
+        );
+    }
+
+    #[test]
+    fn test_commit_graph_keeps_counts() {
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000, 1_700_100_000, 1_700_200_000]);
+        repo.set_head("refs/heads/main").unwrap();
+        let opts = ExtractOptions {
+            commit_graph: true,
+            ..Default::default()
+        };
+        let without = extract_git_info(tmp.path(), &opts);
+        assert_eq!(without.has_commit_graph, Some(false));
+
+        let written = std::process::Command::new("git")
+            .args(["commit-graph", "write", "--reachable"])
+            .current_dir(tmp.path())
+            .status()
+            .expect("this test needs the git CLI to write a commit-graph");
+        assert!(
+            written.success(),
+            "git commit-graph write failed: {written}"
+        );
+        let with = extract_git_info(tmp.path(), &opts);
+        assert_eq!(with.has_commit_graph, Some(true));
+        assert_eq!(with.total_commits, without.total_commits);
+        assert_eq!(with.newest_commit, without.newest_commit);
+    }
+
+    /// libgit2's server timeouts are process-wide and set by the first probe, so every
+    /// test probe uses the same value.
+    const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+    #[test]
+    fn test_origin_reachable_times_out_on_silent_server() {
+        // Accepts connections (via the backlog) but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        repo.remote("origin", &format!("git://127.0.0.1:{port}/silent.git"))
+            .unwrap();
+        let opts = ExtractOptions {
+            validate_origin: Some(PROBE_TIMEOUT),
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).origin_reachable,
+            Some(false)
+        );
+        assert!(start.elapsed() < PROBE_TIMEOUT * 5, "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_origin_reachable_local_bare_remote() {
+        let tmp = TempDir::new().unwrap();
+        let remote_dir = tmp.path().join("remote.git");
+        Repository::init_bare(&remote_dir).unwrap();
+        let work = tmp.path().join("work");
+        let repo = make_repo(&work, &[1_700_000_000]);
+        let opts = ExtractOptions {
+            validate_origin: Some(PROBE_TIMEOUT),
+            ..Default::default()
+        };
+        assert_eq!(extract_git_info(&work, &opts).origin_reachable, None);
+
+        repo.remote("origin", remote_dir.to_str().unwrap()).unwrap();
+        assert_eq!(extract_git_info(&work, &opts).origin_reachable, Some(true));
+
+        repo.remote_set_url("origin", tmp.path().join("gone.git").to_str().unwrap())
+            .unwrap();
+        assert_eq!(extract_git_info(&work, &opts).origin_reachable, Some(false));
+    }
+
+    #[test]
+    fn test_detects_github_actions() {
+        let tmp = TempDir::new().unwrap();
+        make_repo(tmp.path(), &[1_700_000_000]);
+        let opts = ExtractOptions::default();
+        assert_eq!(extract_git_info(tmp.path(), &opts).ci, None);
+
+        let workflows = tmp.path().join(".github").join("workflows");
+        std::fs::create_dir_all(&workflows).unwrap();
+        std::fs::write(workflows.join("ci.yml"), "on: push\n").unwrap();
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).ci.as_deref(),
+            Some("github-actions")
+        );
+    }
+
+    #[test]
+    fn test_classify_original_fork_and_mirror() {
+        let opts = ExtractOptions {
+            classify: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        assert_eq!(extract_git_info(tmp.path(), &opts).classification, None);
+
+        repo.remote("origin", "git@github.com:pete/tool.git")
+            .unwrap();
+        let classification = |o: &ExtractOptions| extract_git_info(tmp.path(), o).classification;
+        assert_eq!(classification(&opts).as_deref(), Some("original"));
+        assert_eq!(classification(&ExtractOptions::default()), None);
+
+        // Same repo over another protocol is not a fork
+        repo.remote("upstream", "https://github.com/pete/tool")
+            .unwrap();
+        assert_eq!(classification(&opts).as_deref(), Some("original"));
+        repo.remote_set_url("upstream", "https://github.com/rust-lang/tool.git")
+            .unwrap();
+        assert_eq!(classification(&opts).as_deref(), Some("fork"));
+
+        repo.config()
+            .unwrap()
+            .set_bool("remote.origin.mirror", true)
+            .unwrap();
+        assert_eq!(classification(&opts).as_deref(), Some("mirror"));
+    }
+
+    #[test]
+    fn test_repo_id_matches_across_clones() {
+        let opts = ExtractOptions {
+            repo_id: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        let (origin, first, second) = (
+            tmp.path().join("origin"),
+            tmp.path().join("first"),
+            tmp.path().join("second"),
+        );
+        let repo = make_repo(&origin, &[1_700_000_000, 1_700_000_100]);
+        repo.set_head("refs/heads/main").unwrap();
+        let root = repo.revparse_single("main~1").unwrap().id().to_string();
+        for clone in [&first, &second] {
+            Repository::clone(origin.to_str().unwrap(), clone).unwrap();
+        }
+        assert_eq!(extract_git_info(&first, &opts).repo_id, Some(root.clone()));
+        assert_eq!(extract_git_info(&second, &opts).repo_id, Some(root.clone()));
+
+        // A later unrelated history merged in doesn't change the identity
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_000_200, 0)).unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let orphan = repo.commit(None, &sig, &sig, "orphan", &tree, &[]).unwrap();
+        let (head, orphan) = (
+            repo.head().unwrap().peel_to_commit().unwrap(),
+            repo.find_commit(orphan).unwrap(),
+        );
+        repo.commit(Some("HEAD"), &sig, &sig, "merge", &tree, &[&head, &orphan])
+            .unwrap();
+        assert_eq!(extract_git_info(&origin, &opts).repo_id, Some(root));
+    }
+
+    #[test]
+    fn test_unsupported_format_version_is_reported() {
+        let tmp = TempDir::new().unwrap();
+        make_repo(tmp.path(), &[1_700_000_000])
+            .config()
+            .unwrap()
+            .set_i32("core.repositoryformatversion", 99)
+            .unwrap();
+        let info = extract_git_info(tmp.path(), &ExtractOptions::default());
+        assert!(info.is_git);
+        assert_eq!(info.healthy, Some(false));
+        let note = info.health_note.unwrap();
+        assert!(
+            note.starts_with("unsupported repository format: "),
+            "{note}"
+        );
+        assert_eq!(info.error, Some(note));
+        assert_eq!(info.total_commits, None);
+    }
+
+    #[test]
+    fn test_unknown_extension_is_reported() {
+        let tmp = TempDir::new().unwrap();
+        let mut config = make_repo(tmp.path(), &[1_700_000_000]).config().unwrap();
+        config.set_i32("core.repositoryformatversion", 1).unwrap();
+        config.set_bool("extensions.fromTheFuture", true).unwrap();
+        let info = extract_git_info(tmp.path(), &ExtractOptions::default());
+        assert!(info.is_git);
+        assert_eq!(info.healthy, Some(false));
+        assert!(info.error.is_some_and(|e| e.contains("fromthefuture")));
+    }
+
+    #[test]
+    fn test_lists_linked_worktrees() {
+        let opts = ExtractOptions {
+            worktrees: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        let main = tmp.path().join("main");
+        let repo = make_repo(&main, &[1_700_000_000]);
+        repo.set_head("refs/heads/main").unwrap();
+        assert_eq!(extract_git_info(&main, &opts).worktrees, Some(vec![]));
+
+        let checkout = tmp.path().join("feature-checkout");
+        repo.worktree("feature", &checkout, None).unwrap();
+        let worktrees = extract_git_info(&main, &opts).worktrees.unwrap();
+        assert_eq!(worktrees.len(), 1);
+        assert!(
+            worktrees[0]
+                .trim_end_matches('/')
+                .ends_with("feature-checkout"),
+            "{worktrees:?}"
+        );
+    }
+
+    #[test]
+    fn test_behind_upstream_after_fetch() {
+        let opts = ExtractOptions {
+            behind: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        let (origin_dir, clone_dir) = (tmp.path().join("origin"), tmp.path().join("clone"));
+        let origin = make_repo(&origin_dir, &[1_700_000_000]);
+        origin.set_head("refs/heads/main").unwrap();
+        let clone = Repository::clone(origin_dir.to_str().unwrap(), &clone_dir).unwrap();
+        assert_eq!(extract_git_info(&clone_dir, &opts).behind, Some(0));
+
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_000_100, 0)).unwrap();
+        let head = origin.head().unwrap().peel_to_commit().unwrap();
+        for message in ["second", "third"] {
+            let parent = origin.head().unwrap().peel_to_commit().unwrap();
+            origin
+                .commit(
+                    Some("HEAD"),
+                    &sig,
+                    &sig,
+                    message,
+                    &head.tree().unwrap(),
+                    &[&parent],
+                )
+                .unwrap();
+        }
+        // Not fetched yet, so the clone doesn't know
+        assert_eq!(extract_git_info(&clone_dir, &opts).behind, Some(0));
+        clone
+            .find_remote("origin")
+            .unwrap()
+            .fetch::<&str>(&[], None, None)
+            .unwrap();
+        assert_eq!(extract_git_info(&clone_dir, &opts).behind, Some(2));
+        assert_eq!(
+            extract_git_info(&clone_dir, &ExtractOptions::default()).behind,
+            None
+        );
+    }
+
+    #[test]
+    fn test_contributors_counts_distinct_authors() {
+        let tmp = TempDir::new().unwrap();
+        let repo = Repository::init(tmp.path()).unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let mut parent: Option<git2::Commit> = None;
+        for (i, email) in ["ann@x.com", "bob@x.com", "Ann@X.com"].iter().enumerate() {
+            let sig = Signature::new("A", email, &Time::new(1_700_000_000 + i as i64, 0)).unwrap();
+            let parents: Vec<&git2::Commit> = parent.iter().collect();
+            let oid = repo
+                .commit(Some("refs/heads/main"), &sig, &sig, "c", &tree, &parents)
+                .unwrap();
+            parent = Some(repo.find_commit(oid).unwrap());
+        }
+        let opts = ExtractOptions {
+            contributors: true,
+            ..Default::default()
+        };
+        let info = extract_git_info(tmp.path(), &opts);
+        assert_eq!(info.contributors, Some(2));
+        assert_eq!(info.total_commits, Some(3));
+
+        // Only the newest commit is examined
+        let bounded = ExtractOptions {
+            max_commits: Some(1),
+            ..opts
+        };
+        assert_eq!(extract_git_info(tmp.path(), &bounded).contributors, Some(1));
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).contributors,
+            None
+        );
+    }
+
+    #[test]
+    fn test_custom_hook_and_mailmap_are_flagged() {
+        let opts = ExtractOptions {
+            hooks: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        make_repo(tmp.path(), &[1_700_000_000]);
+        let hooks = tmp.path().join(".git/hooks");
+        std::fs::create_dir_all(&hooks).unwrap();
+        std::fs::write(hooks.join("pre-commit.sample"), "#!/bin/sh\n").unwrap();
+        let info = extract_git_info(tmp.path(), &opts);
+        assert_eq!((info.hooks, info.mailmap), (Some(false), Some(false)));
+
+        std::fs::write(hooks.join("pre-commit"), "#!/bin/sh\nexit 1\n").unwrap();
+        std::fs::write(tmp.path().join(".mailmap"), "Pete <pete@example.com>\n").unwrap();
+        let info = extract_git_info(tmp.path(), &opts);
+        assert_eq!((info.hooks, info.mailmap), (Some(true), Some(true)));
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).hooks,
+            None
+        );
+    }
+
+    #[test]
+    fn test_hooks_follow_core_hooks_path() {
+        let opts = ExtractOptions {
+            hooks: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        let hooks = tmp.path().join(".githooks");
+        std::fs::create_dir(&hooks).unwrap();
+        repo.config()
+            .unwrap()
+            .set_str("core.hooksPath", ".githooks")
+            .unwrap();
+        assert_eq!(extract_git_info(tmp.path(), &opts).hooks, Some(false));
+
+        std::fs::write(hooks.join("pre-push"), "#!/bin/sh\nexit 1\n").unwrap();
+        assert_eq!(extract_git_info(tmp.path(), &opts).hooks, Some(true));
+    }
+
+    #[test]
+    fn test_stray_index_lock_is_flagged() {
+        let opts = ExtractOptions {
+            locks: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        make_repo(tmp.path(), &[1_700_000_000]);
+        assert_eq!(extract_git_info(tmp.path(), &opts).locked, Some(false));
+        std::fs::write(tmp.path().join(".git/index.lock"), "").unwrap();
+        assert_eq!(extract_git_info(tmp.path(), &opts).locked, Some(true));
+        assert_eq!(
+            extract_git_info(tmp.path(), &ExtractOptions::default()).locked,
+            None
+        );
+    }
+
+    #[test]
+    fn test_identity_reads_repo_local_config_only() {
+        let opts = ExtractOptions {
+            identity: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        let info = extract_git_info(tmp.path(), &opts);
+        assert_eq!((info.user_name, info.user_email), (None, None));
+
+        repo.config()
+            .unwrap()
+            .open_level(git2::ConfigLevel::Local)
+            .unwrap()
+            .set_str("user.email", "pete@work.example")
+            .unwrap();
+        let info = extract_git_info(tmp.path(), &opts);
+        assert_eq!(info.user_email.as_deref(), Some("pete@work.example"));
+        assert_eq!(info.user_name, None);
+    }
+
+    #[test]
+    fn test_unreadable_repo_records_error() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::create_dir(tmp.path().join(".git")).unwrap();
+        std::fs::write(tmp.path().join(".git/HEAD"), "garbage").unwrap();
+        let info = extract_git_info(tmp.path(), &ExtractOptions::default());
+        assert!(info.error.is_some());
+
+        let plain = TempDir::new().unwrap();
+        let info = extract_git_info(plain.path(), &ExtractOptions::default());
+        assert_eq!(info.error, None);
+    }
+
+    #[test]
+    fn test_branch_delta_between_diverged_branches() {
+        let opts = ExtractOptions {
+            branch_delta: Some(("main".to_string(), "feature".to_string())),
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        let base = repo
+            .find_reference("refs/heads/main")
+            .unwrap()
+            .peel_to_commit()
+            .unwrap();
+        repo.branch("feature", &base, false).unwrap();
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_000_100, 0)).unwrap();
+        let tree = base.tree().unwrap();
+        for (branch, commits) in [("refs/heads/feature", 2), ("refs/heads/main", 1)] {
+            for _ in 0..commits {
+                let parent = repo
+                    .find_reference(branch)
+                    .unwrap()
+                    .peel_to_commit()
+                    .unwrap();
+                // Distinct messages: identical commits would share an id across branches
+                repo.commit(Some(branch), &sig, &sig, branch, &tree, &[&parent])
+                    .unwrap();
+            }
+        }
+        let info = extract_git_info(tmp.path(), &opts);
+        assert_eq!((info.branch_ahead, info.branch_behind), (Some(1), Some(2)));
+
+        let missing = ExtractOptions {
+            branch_delta: Some(("main".to_string(), "release".to_string())),
+            ..Default::default()
+        };
+        let info = extract_git_info(tmp.path(), &missing);
+        assert_eq!((info.branch_ahead, info.branch_behind), (None, None));
+    }
+
+    #[test]
+    fn test_head_branch_outranks_main() {
+        let opts = ExtractOptions {
+            unmerged: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        let repo = make_repo(tmp.path(), &[1_700_000_000]);
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_100_000, 0)).unwrap();
+        let main = repo
+            .find_commit(repo.refname_to_id("refs/heads/main").unwrap())
+            .unwrap();
+        repo.commit(Some("refs/heads/topic"), &sig, &sig, "wip", &tree, &[&main])
+            .unwrap();
+        repo.set_head("refs/heads/topic").unwrap();
+
+        // Against topic, main is merged; against main, topic would be unmerged
+        assert_eq!(
+            extract_git_info(tmp.path(), &opts).unmerged_branches,
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_default_branch_follows_head_without_main() {
+        let opts = ExtractOptions {
+            unmerged: true,
+            ..Default::default()
+        };
+        let tmp = TempDir::new().unwrap();
+        let repo = Repository::init(tmp.path()).unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        let sig = Signature::new("T", "t@t.com", &Time::new(1_700_000_000, 0)).unwrap();
+        let first = repo
+            .commit(Some("refs/heads/trunk"), &sig, &sig, "one", &tree, &[])
+            .unwrap();
+        let first = repo.find_commit(first).unwrap();
+        repo.commit(
+            Some("refs/heads/trunk"),
+            &sig,
+            &sig,
+            "two",
+            &tree,
+            &[&first],
+        )
+        .unwrap();
+        repo.set_head("refs/heads/trunk").unwrap();
+
+        let info = extract_git_info(tmp.path(), &opts);
+        assert_eq!(info.total_commits, Some(2));
+        // No main or master, yet there is a default branch to compare against
+        assert_eq!(info.unmerged_branches, Some(0));
+    }
 }
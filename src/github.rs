@@ -0,0 +1,174 @@
+//! GitHub metadata for uploaded repos (`--enrich-github`): description, topics and
+//! visibility from the REST API, looked up by the owner/name in the origin URL.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::git_info::normalize_origin_url;
+
+/// Public GitHub's REST API.
+pub const API_BASE: &str = "https://api.github.com";
+
+/// The fields of `GET /repos/{owner}/{repo}` the report uses.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+pub struct GithubRepo {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    pub visibility: Option<String>,
+}
+
+/// `(owner, repo)` for an origin on github.com, in any URL form git accepts.
+pub fn owner_repo(origin_url: &str) -> Option<(String, String)> {
+    let normalized = normalize_origin_url(origin_url);
+    let path = normalized.strip_prefix("github.com/")?;
+    let (owner, repo) = path.split_once('/')?;
+    if owner.is_empty() || repo.is_empty() || repo.contains('/') {
+        return None;
+    }
+    Some((owner.to_string(), repo.to_string()))
+}
+
+/// Blocking API client. Each request gives up after the agent's timeout, so a slow API
+/// costs a bounded delay per repo.
+#[derive(Debug, Clone)]
+pub struct GithubClient {
+    base: String,
+    token: Option<String>,
+    agent: ureq::Agent,
+    /// Set after the first failed lookup has been reported
+    warned: Arc<AtomicBool>,
+}
+
+impl GithubClient {
+    /// A client for the API at `base`, authenticating with `token` when given (needed
+    /// for private repos and higher rate limits).
+    pub fn new(base: &str, token: Option<String>, timeout: Duration) -> Self {
+        let agent = ureq::Agent::config_builder()
+            .timeout_global(Some(timeout))
+            .build()
+            .into();
+        GithubClient {
+            base: base.trim_end_matches('/').to_string(),
+            token,
+            agent,
+            warned: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Fetch one repo's metadata; `Err` carries a short description of the failure.
+    pub fn fetch(&self, owner: &str, repo: &str) -> Result<GithubRepo, String> {
+        let url = format!("{}/repos/{owner}/{repo}", self.base);
+        let mut request = self
+            .agent
+            .get(&url)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "lsproj");
+        if let Some(token) = &self.token {
+            request = request.header("Authorization", &format!("Bearer {token}"));
+        }
+        let mut response = request.call().map_err(|e| format!("{url}: {e}"))?;
+        response
+            .body_mut()
+            .read_json()
+            .map_err(|e| format!("{url}: {e}"))
+    }
+
+    /// Metadata for the repo `origin_url` points at; `None` when it isn't on GitHub or
+    /// the lookup fails. Only the first failure is reported on stderr, so a bad token or
+    /// a rate limit is noticed without repeating for every repo.
+    pub fn enrich(&self, origin_url: &str) -> Option<GithubRepo> {
+        let (owner, repo) = owner_repo(origin_url)?;
+        self.fetch(&owner, &repo)
+            .inspect_err(|e| {
+                if !self.warned.swap(true, Ordering::Relaxed) {
+                    eprintln!("GitHub lookup failed, leaving its columns blank: {e}");
+                }
+            })
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serves `body` as JSON to a single request, returning the request's header lines.
+    fn serve_once(body: &'static str) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut lines = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                lines.push(line.trim().to_string());
+            }
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            lines
+        });
+        (base, handle)
+    }
+
+    #[test]
+    fn test_enrich_from_canned_response() {
+        let (base, server) = serve_once(
+            r#"{"name":"tool","description":"A handy tool","topics":["cli","rust"],"visibility":"public"}"#,
+        );
+        let client = GithubClient::new(&base, Some("secret".into()), Duration::from_secs(5));
+        let repo = client.enrich("git@github.com:pete/tool.git").unwrap();
+        assert_eq!(
+            repo,
+            GithubRepo {
+                description: Some("A handy tool".to_string()),
+                topics: vec!["cli".to_string(), "rust".to_string()],
+                visibility: Some("public".to_string()),
+            }
+        );
+        let request = server.join().unwrap();
+        assert_eq!(request[0], "GET /repos/pete/tool HTTP/1.1");
+        assert!(
+            request
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case("authorization: Bearer secret"))
+        );
+    }
+
+    #[test]
+    fn test_failed_lookup_is_blank_and_noted() {
+        // Nothing listens on a port freed right after binding it
+        let base = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        let client = GithubClient::new(&base, None, Duration::from_secs(5));
+        assert_eq!(client.enrich("https://github.com/pete/tool.git"), None);
+        assert!(client.warned.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_owner_repo() {
+        assert_eq!(
+            owner_repo("https://github.com/pete/tool.git"),
+            Some(("pete".to_string(), "tool".to_string()))
+        );
+        assert_eq!(owner_repo("git@gitlab.com:pete/tool.git"), None);
+        assert_eq!(owner_repo("https://github.com/pete"), None);
+    }
+}
@@ -0,0 +1,147 @@
+//! Incremental scans (`--since-last-scan`): a state file holding every project's record
+//! from the previous scan, keyed by absolute path. A repo whose git dir is unchanged (see
+//! [`git_dir_mtime`]) has its record copied forward instead of re-reading its history.
+//!
+//! Only history is carried forward: the working-tree metrics of a reused record (lines of
+//! code, file checks, sizes, dirty) are read again. A state file from a scan of another
+//! root, or with different extraction flags (see [`ExtractOptions::fingerprint`]), reuses
+//! nothing.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::fs_meta::git_dir_mtime;
+use crate::metadata::{ExtractOptions, ProjectMetadata};
+
+/// On-disk form: the scan root, the extraction flags, and the records found under it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ScanState {
+    root: PathBuf,
+    #[serde(default)]
+    options: String,
+    projects: BTreeMap<PathBuf, ProjectMetadata>,
+}
+
+/// The previous scan's records, and this scan's as they are produced.
+#[derive(Debug)]
+pub struct ScanCache {
+    path: PathBuf,
+    previous: ScanState,
+    current: Mutex<ScanState>,
+    recomputed: AtomicU64,
+}
+
+impl ScanCache {
+    /// Load the state file at `path` for a scan of `root` with `opts`. A missing file, or
+    /// one from a scan of a different root or with different options, reuses nothing.
+    pub fn open(path: &Path, root: &Path, opts: &ExtractOptions) -> Result<ScanCache> {
+        let options = opts.fingerprint();
+        let previous = if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Cannot read scan state {}", path.display()))?;
+            let state: ScanState = serde_json::from_str(&text)
+                .with_context(|| format!("Invalid scan state {}", path.display()))?;
+            if state.root == root && state.options == options {
+                state
+            } else {
+                ScanState::default()
+            }
+        } else {
+            ScanState::default()
+        };
+        Ok(ScanCache {
+            path: path.to_path_buf(),
+            previous,
+            current: Mutex::new(ScanState {
+                root: root.to_path_buf(),
+                options,
+                ..Default::default()
+            }),
+            recomputed: AtomicU64::new(0),
+        })
+    }
+
+    /// The previous record for `project`, if its `.git` has not been modified since.
+    pub fn reuse(&self, project: &Path) -> Option<ProjectMetadata> {
+        let previous = self.previous.projects.get(project)?;
+        let mtime = previous.git_dir_mtime?;
+        (git_dir_mtime(&project.join(".git")) == Some(mtime)).then(|| previous.clone())
+    }
+
+    /// Remember `meta` for the next scan. `fresh` marks a record extracted in this scan
+    /// rather than reused.
+    pub fn store(&self, project: &Path, meta: &ProjectMetadata, fresh: bool) {
+        if fresh {
+            self.recomputed.fetch_add(1, Ordering::Relaxed);
+        }
+        let mut current = self.current.lock().unwrap();
+        current.projects.insert(project.to_path_buf(), meta.clone());
+    }
+
+    /// Projects whose metadata was extracted rather than copied forward.
+    pub fn recomputed(&self) -> u64 {
+        self.recomputed.load(Ordering::Relaxed)
+    }
+
+    /// Replace the state file with this scan's records. Written to a temp file and renamed,
+    /// so an interruption mid-write leaves the previous state intact.
+    pub fn save(&self) -> Result<()> {
+        let current = self.current.lock().unwrap();
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_vec(&*current)?)
+            .with_context(|| format!("Cannot write scan state {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Cannot write scan state {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_reuses_only_unchanged_git_dirs() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("projects");
+        let project = root.join("app");
+        std::fs::create_dir_all(project.join(".git")).unwrap();
+        let file = dir.path().join("scan.state");
+
+        let opts = ExtractOptions::default();
+        let cache = ScanCache::open(&file, &root, &opts).unwrap();
+        assert!(cache.reuse(&project).is_none());
+        let meta = ProjectMetadata {
+            path: "app".to_string(),
+            total_commits: Some(7),
+            git_dir_mtime: git_dir_mtime(&project.join(".git")),
+            ..Default::default()
+        };
+        cache.store(&project, &meta, true);
+        cache.save().unwrap();
+
+        let cache = ScanCache::open(&file, &root, &opts).unwrap();
+        assert_eq!(cache.reuse(&project).unwrap().total_commits, Some(7));
+        let other_root = ScanCache::open(&file, &dir.path().join("elsewhere"), &opts).unwrap();
+        assert!(other_root.reuse(&project).is_none());
+        let other_flags = ExtractOptions {
+            dirty: true,
+            ..Default::default()
+        };
+        let other_flags = ScanCache::open(&file, &root, &other_flags).unwrap();
+        assert!(other_flags.reuse(&project).is_none());
+
+        // Set explicitly: a write this soon after may land in the same timestamp tick
+        let touched = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1);
+        std::fs::File::open(project.join(".git"))
+            .unwrap()
+            .set_modified(touched)
+            .unwrap();
+        assert!(cache.reuse(&project).is_none());
+    }
+}
@@ -0,0 +1,192 @@
+//! Change tracking against an earlier CSV report (`--diff-against`): which projects are
+//! new, which are gone, and whose commit count moved.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+
+use crate::metadata::ProjectMetadata;
+
+/// The count compared between runs: `commits` when the earlier report has that column
+/// (`--columns`), else the default CSV's unpushed `count`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Measure {
+    Commits,
+    Unpushed,
+}
+
+impl Measure {
+    fn header(self) -> &'static str {
+        match self {
+            Measure::Commits => "commits",
+            Measure::Unpushed => "count",
+        }
+    }
+
+    fn of(self, p: &ProjectMetadata) -> Option<u64> {
+        match self {
+            Measure::Commits => p.total_commits.map(u64::from),
+            Measure::Unpushed => p.unpushed_count.map(u64::from),
+        }
+    }
+}
+
+/// One project whose count differs between the two runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Changed {
+    pub path: String,
+    pub before: u64,
+    pub after: u64,
+}
+
+/// An earlier report, consumed as this scan's records are compared against it.
+#[derive(Debug)]
+pub struct InventoryDiff {
+    measure: Measure,
+    previous: BTreeMap<String, Option<u64>>,
+    pub added: Vec<String>,
+    pub changed: Vec<Changed>,
+}
+
+impl InventoryDiff {
+    /// Load a CSV written by an earlier run. It needs a `repository` (or `path`) column
+    /// and a `commits` or `count` column.
+    pub fn load(path: &Path) -> Result<InventoryDiff> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("Cannot read inventory {}", path.display()))?;
+        InventoryDiff::from_reader(file)
+            .with_context(|| format!("Invalid inventory {}", path.display()))
+    }
+
+    pub fn from_reader(reader: impl std::io::Read) -> Result<InventoryDiff> {
+        let mut csv = csv::Reader::from_reader(reader);
+        let headers = csv.headers()?.clone();
+        let find = |name: &str| headers.iter().position(|h| h == name);
+        let Some(key) = find("repository").or_else(|| find("path")) else {
+            bail!("no repository or path column");
+        };
+        let (measure, column) = match (find("commits"), find("count")) {
+            (Some(i), _) => (Measure::Commits, i),
+            (None, Some(i)) => (Measure::Unpushed, i),
+            (None, None) => bail!("no commits or count column"),
+        };
+        let mut previous = BTreeMap::new();
+        for row in csv.records() {
+            let row = row?;
+            let cell = row.get(column).unwrap_or("");
+            // Shallow clones' counts carry a trailing `+`; --newest-only leaves them blank
+            let count = match cell.strip_suffix('+').unwrap_or(cell) {
+                "" => None,
+                n => Some(n.parse().with_context(|| {
+                    let line = row.position().map_or(0, |p| p.line());
+                    format!("line {line}: {cell:?} is not a {}", measure.header())
+                })?),
+            };
+            previous.insert(row.get(key).unwrap_or("").to_string(), count);
+        }
+        Ok(InventoryDiff {
+            measure,
+            previous,
+            added: Vec::new(),
+            changed: Vec::new(),
+        })
+    }
+
+    /// Name of the column compared: `commits`, or `count` when the earlier report has no
+    /// `commits` column.
+    pub fn measure(&self) -> &'static str {
+        self.measure.header()
+    }
+
+    /// Compare one record of the current scan.
+    pub fn record(&mut self, p: &ProjectMetadata) {
+        let after = self.measure.of(p);
+        match (self.previous.remove(&p.path), after) {
+            (None, _) => self.added.push(p.path.clone()),
+            (Some(Some(before)), Some(after)) if before != after => self.changed.push(Changed {
+                path: p.path.clone(),
+                before,
+                after,
+            }),
+            // A count left blank on either side (`--newest-only`) can't change
+            (Some(_), _) => {}
+        }
+    }
+
+    /// Projects in the earlier report that this scan did not see.
+    pub fn removed(&self) -> impl Iterator<Item = &str> {
+        self.previous.keys().map(String::as_str)
+    }
+}
+
+/// `added`, `removed` and `changed` sections, one indented project per line.
+impl fmt::Display for InventoryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "added ({}):", self.added.len())?;
+        for path in &self.added {
+            writeln!(f, "  {path}")?;
+        }
+        writeln!(f, "removed ({}):", self.previous.len())?;
+        for path in self.removed() {
+            writeln!(f, "  {path}")?;
+        }
+        writeln!(f, "changed ({}):", self.changed.len())?;
+        for c in &self.changed {
+            let delta = c.after as i64 - c.before as i64;
+            writeln!(
+                f,
+                "  {}  {} {} -> {} ({delta:+})",
+                c.path,
+                self.measure.header(),
+                c.before,
+                c.after
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_added_removed_and_changed() {
+        let prior = "repository,commits\nkept,3\ngrown,\"4+\"\ngone,1\n";
+        let mut diff = InventoryDiff::from_reader(prior.as_bytes()).unwrap();
+        for (path, total_commits) in [("grown", 6), ("kept", 3), ("new", 1)] {
+            diff.record(&ProjectMetadata {
+                path: path.to_string(),
+                total_commits: Some(total_commits),
+                ..Default::default()
+            });
+        }
+        assert_eq!(diff.added, ["new"]);
+        assert_eq!(diff.removed().collect::<Vec<_>>(), ["gone"]);
+        assert_eq!(
+            diff.to_string(),
+            "added (1):\n  new\nremoved (1):\n  gone\nchanged (1):\n  grown  commits 4 -> 6 (+2)\n"
+        );
+    }
+
+    #[test]
+    fn test_requires_key_and_count_columns() {
+        let err = InventoryDiff::from_reader("name,commits\n".as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("repository"), "{err}");
+        let err = InventoryDiff::from_reader("repository,oldest\n".as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("count"), "{err}");
+    }
+
+    #[test]
+    fn test_rejects_counts_that_are_not_numbers() {
+        let prior = "repository,count\nkept,3\nodd,5++\n";
+        let err = InventoryDiff::from_reader(prior.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{err}");
+
+        let prior = "repository,count\nfast,\nshallow,4+\n";
+        let diff = InventoryDiff::from_reader(prior.as_bytes()).unwrap();
+        assert_eq!(diff.measure(), "count");
+    }
+}
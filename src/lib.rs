@@ -1,12 +1,36 @@
+// `output::print_schema` builds a large `serde_json::json!` literal
+#![recursion_limit = "256"]
+
 use std::path::Path;
 
+use git2::Repository;
+
+pub mod age;
+pub mod author;
+pub mod checkpoint;
+pub mod columnar;
+pub mod extsort;
+pub mod fdlimit;
 pub mod filter;
+pub mod filtercmd;
 pub mod fs_meta;
 pub mod git_info;
+pub mod github;
+pub mod incremental;
+pub mod inventory;
 pub mod loc;
 pub mod metadata;
 pub mod output;
+pub mod progress;
+pub mod publish;
+pub mod ratelimit;
 pub mod repostatus;
+pub mod sink;
+pub mod stats;
+pub mod timing;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod walk;
 
 /// returns a simplified absolute repo path by:
 /// 1. removing the common base (i.e. the starting dir from the cmd line)
@@ -24,8 +48,8 @@ pub mod repostatus;
 /// ```
 pub fn simplified_repo_path(path: &Path, base: &Path) -> String {
     // If last component is ".git", use parent; else use path directly
-    let path_to_strip = match path.file_name().and_then(|f| f.to_str()) {
-        Some(".git") => path.parent().unwrap(),
+    let path_to_strip = match path.file_name() {
+        Some(name) if name == ".git" => path.parent().unwrap(),
         _ => path,
     };
     if let Ok(display_path) = path_to_strip.strip_prefix(base) {
@@ -40,9 +64,118 @@ pub fn simplified_repo_path(path: &Path, base: &Path) -> String {
     );
 }
 
+/// Which directory a repo's reported path is derived from (`--name-from`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum NameFrom {
+    /// The work tree, as the user sees it (bare repos lose a trailing `.git`)
+    #[default]
+    Workdir,
+    /// The git directory itself (`foo/.git` still reports as `foo`)
+    Gitdir,
+}
+
+/// Display path for a project, relative to `base`.
+///
+/// For git repos this follows `repo.workdir()` rather than guessing from path components,
+/// so unusual layouts keep sensible names; only bare repos fall back to stripping `.git`
+/// (`mirrors/project.git` → `mirrors/project`). Non-repos use [`simplified_repo_path`].
+pub fn repo_display_path(path: &Path, base: &Path, name_from: NameFrom) -> String {
+    let Ok(repo) = Repository::open(path) else {
+        return simplified_repo_path(path, base);
+    };
+    match (name_from, repo.workdir()) {
+        (NameFrom::Workdir, Some(workdir)) if workdir.starts_with(base) => {
+            simplified_repo_path(workdir, base)
+        }
+        (NameFrom::Workdir, _) => strip_git_suffix(&simplified_repo_path(path, base)),
+        (NameFrom::Gitdir, _) if repo.path().starts_with(base) => {
+            simplified_repo_path(repo.path(), base)
+        }
+        (NameFrom::Gitdir, _) => simplified_repo_path(path, base),
+    }
+}
+
+fn strip_git_suffix(display: &str) -> String {
+    match display.strip_suffix(".git") {
+        Some(stripped) if !stripped.is_empty() && !stripped.ends_with('/') => stripped.to_string(),
+        _ => display.to_string(),
+    }
+}
+
+/// Base that reported paths are expressed against (`--relative-to`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RelativeTo {
+    /// The current working directory, using `..` where needed
+    Cwd,
+    /// The scan root
+    #[default]
+    Root,
+    /// Absolute paths
+    Absolute,
+}
+
+/// Re-express `display` (a path relative to `root`, as produced by [`simplified_repo_path`])
+/// against the base chosen by `mode`. `cwd` is only consulted for [`RelativeTo::Cwd`].
+pub fn rebase_display_path(display: &str, root: &Path, cwd: &Path, mode: RelativeTo) -> String {
+    match mode {
+        RelativeTo::Root => display.to_string(),
+        RelativeTo::Absolute => absolute_display_path(display, root).display().to_string(),
+        RelativeTo::Cwd => rebase_onto(display, root, cwd),
+    }
+}
+
+/// Directory that root-relative names are computed from (`--name-base`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum NameBase {
+    /// The scan root: its direct children are named `foo`
+    #[default]
+    Root,
+    /// The scan root's parent: names carry the root's own name, `projects/foo`
+    Parent,
+}
+
+/// Re-express `display` (a path relative to `root`) relative to `base`; `.` when they
+/// are the same directory.
+pub fn rebase_onto(display: &str, root: &Path, base: &Path) -> String {
+    let relative = relative_path(&absolute_display_path(display, root), base);
+    if relative.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        relative.display().to_string()
+    }
+}
+
+fn absolute_display_path(display: &str, root: &Path) -> std::path::PathBuf {
+    if display.is_empty() {
+        root.to_path_buf()
+    } else {
+        root.join(display)
+    }
+}
+
+/// `path` relative to `from`; both must be absolute. Climbs out of `from` with `..`.
+fn relative_path(path: &Path, from: &Path) -> std::path::PathBuf {
+    let path_parts: Vec<_> = path.components().collect();
+    let from_parts: Vec<_> = from.components().collect();
+    let common = path_parts
+        .iter()
+        .zip(&from_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = std::path::PathBuf::new();
+    for _ in common..from_parts.len() {
+        relative.push("..");
+    }
+    for part in &path_parts[common..] {
+        relative.push(part);
+    }
+    relative
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tempfile::TempDir;
 
     #[test]
     fn test_simple_path_basic() {
@@ -61,4 +194,70 @@ mod tests {
         );
         assert_eq!(simple, "foo/lib");
     }
+
+    #[test]
+    fn test_display_path_normal_repo() {
+        let root = TempDir::new().unwrap();
+        let repo_dir = root.path().join("foo");
+        Repository::init(&repo_dir).unwrap();
+        for name_from in [NameFrom::Workdir, NameFrom::Gitdir] {
+            assert_eq!(repo_display_path(&repo_dir, root.path(), name_from), "foo");
+        }
+    }
+
+    #[test]
+    fn test_display_path_bare_repo() {
+        let root = TempDir::new().unwrap();
+        let bare = root.path().join("mirrors").join("project.git");
+        Repository::init_bare(&bare).unwrap();
+        assert_eq!(
+            repo_display_path(&bare, root.path(), NameFrom::Workdir),
+            "mirrors/project"
+        );
+        assert_eq!(
+            repo_display_path(&bare, root.path(), NameFrom::Gitdir),
+            "mirrors/project.git"
+        );
+    }
+
+    #[test]
+    fn test_display_path_project_dot_git_workdir() {
+        // A regular checkout that happens to be named like a bare repo keeps its name
+        let root = TempDir::new().unwrap();
+        let repo_dir = root.path().join("project.git");
+        Repository::init(&repo_dir).unwrap();
+        assert_eq!(
+            repo_display_path(&repo_dir, root.path(), NameFrom::Workdir),
+            "project.git"
+        );
+    }
+
+    #[test]
+    fn test_rebase_display_path_modes() {
+        let root = Path::new("/home/pete/projects");
+        let cwd = Path::new("/home/pete");
+        let rebase = |mode| rebase_display_path("foo/bar", root, cwd, mode);
+        assert_eq!(rebase(RelativeTo::Root), "foo/bar");
+        assert_eq!(rebase(RelativeTo::Cwd), "projects/foo/bar");
+        assert_eq!(rebase(RelativeTo::Absolute), "/home/pete/projects/foo/bar");
+    }
+
+    #[test]
+    fn test_rebase_display_path_outside_cwd() {
+        let root = Path::new("/home/pete/projects");
+        let cwd = Path::new("/home/pete/notes");
+        assert_eq!(
+            rebase_display_path("foo", root, cwd, RelativeTo::Cwd),
+            "../projects/foo"
+        );
+        assert_eq!(rebase_display_path("", root, root, RelativeTo::Cwd), ".");
+    }
+
+    #[test]
+    fn test_rebase_onto_parent_names_root_level_repo() {
+        let root = Path::new("/home/pete/projects");
+        let parent = Path::new("/home/pete");
+        assert_eq!(rebase_onto("foo", root, parent), "projects/foo");
+        assert_eq!(rebase_onto("", root, parent), "projects");
+    }
 }
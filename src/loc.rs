@@ -4,6 +4,7 @@ use tokei::{Config, Languages};
 
 use crate::metadata::LanguageStat;
 
+#[derive(Default)]
 pub struct LocInfo {
     pub languages: Vec<LanguageStat>,
     pub primary_language: Option<String>,
@@ -1,41 +1,597 @@
-use std::collections::HashSet;
-use std::future::Future;
-use std::path::PathBuf;
-use std::pin::Pin;
-use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use tokio::sync::Semaphore;
-use tokio::task::{self, JoinHandle};
 
-use lsproj::filter::{EntryKind, classify_entry};
-use lsproj::metadata::{ProjectMetadata, extract_metadata};
-use lsproj::output;
+use lsproj::author::AuthorPattern;
+use lsproj::fdlimit;
+use lsproj::filter::{DenyList, Filter, FilterChain, HasRemoteNamed};
+use lsproj::filtercmd::FilterCommand;
+use lsproj::fs_meta;
+use lsproj::git_info::normalize_origin_url;
+use lsproj::github::GithubClient;
+use lsproj::incremental::ScanCache;
+use lsproj::inventory::InventoryDiff;
+use lsproj::metadata::{ExtractOptions, ProjectMetadata};
+use lsproj::output::{self, Column, OutputFormat, SplitWriter, TableWriter, group_key};
+use lsproj::progress::Progress;
+use lsproj::publish::{self, CommandRunner, DryRunner, SystemRunner};
+use lsproj::sink::{Compression, Sink};
+use lsproj::stats::SkipStats;
+use lsproj::timing::Timings;
+use lsproj::walk::{self, Engine, WalkOptions};
+use lsproj::{NameBase, NameFrom, RelativeTo};
 
 #[derive(Parser)]
 #[command(name = "lsproj", about = "List local projects with metadata")]
+// Any of the history-walking flags satisfies --max-commits; they combine freely
+#[command(group(clap::ArgGroup::new("history_walk").multiple(true)))]
 struct Args {
     /// Directory to scan
     dir: Option<PathBuf>,
 
+    /// Output format; --json, --jsonl and --csv are shorthands
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["json", "jsonl", "csv"])]
+    format: Option<Format>,
+
+    /// Draw --format tree with plain ASCII instead of box-drawing characters
+    #[arg(long)]
+    ascii: bool,
+
     /// Output as JSON array
     #[arg(long)]
     json: bool,
 
+    /// Output as JSON Lines (one compact object per line)
+    #[arg(long)]
+    jsonl: bool,
+
+    /// Include a `remotes` array of `{name, url}` objects in JSON and JSON Lines output,
+    /// listing every remote rather than just origin
+    #[arg(long)]
+    json_remotes: bool,
+
+    /// Indent JSON array output. This is the default; the flag is accepted for
+    /// explicitness.
+    #[arg(long, requires = "json", conflicts_with_all = ["jsonl", "compact"])]
+    pretty: bool,
+
+    /// Write the JSON array on a single line instead of indented (JSON Lines is always
+    /// compact)
+    #[arg(long, conflicts_with = "jsonl")]
+    compact: bool,
+
     /// Output as CSV (backward-compatible format)
     #[arg(long)]
     csv: bool,
 
+    /// Field separator for CSV output, e.g. ';' or '\t' for TSV
+    #[arg(long, value_name = "CHAR", default_value = ",", value_parser = output::parse_delimiter)]
+    delimiter: char,
+
+    /// Comma-separated table/CSV columns, in output order, replacing the default set
+    /// (e.g. count,repository,newest). See --describe-columns for names.
+    #[arg(long, value_name = "LIST", value_delimiter = ',', value_parser = output::parse_column)]
+    columns: Vec<Column>,
+
+    /// Group table rows by their top-level directory
+    #[arg(long)]
+    group: bool,
+
+    /// Group table rows by the first N path components (implies --group)
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    group_depth: Option<usize>,
+
+    /// Print only the number of matching projects
+    #[arg(long)]
+    count_only: bool,
+
+    /// Output a bash script that creates a GitHub repo under USERNAME for every git repo
+    /// without an origin, adds it as origin, and pushes
+    #[arg(long, value_name = "USERNAME")]
+    emit_script: Option<String>,
+
+    /// Publish every git repo without an origin to GitHub under USERNAME with the `gh` CLI:
+    /// create a private repo, add it as origin, and push. Requires --confirm or --dry-run.
+    /// Adds a `created` column with each outcome.
+    #[arg(long, value_name = "USERNAME", conflicts_with = "emit_script")]
+    create: Option<String>,
+
+    /// Print the commands --create would run instead of running them
+    #[arg(long, requires = "create")]
+    dry_run: bool,
+
+    /// Allow --create to create repos and push
+    #[arg(long, requires = "create")]
+    confirm: bool,
+
+    /// Write results to PATH instead of stdout (gzip-compressed if PATH ends in .gz)
+    #[arg(long, short, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Write one CSV per top-level directory into DIR, named after the directory, instead
+    /// of a single report (projects directly under the root go in `_top.csv`)
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["format", "json", "jsonl", "emit_script", "count_only", "output"]
+    )]
+    split_by_top: Option<PathBuf>,
+
+    /// Compare against CSV, a report from an earlier run, and print which projects were
+    /// added, removed or changed their commit count (the `commits` column if CSV has one,
+    /// else the unpushed `count`) instead of a report
+    #[arg(
+        long,
+        value_name = "CSV",
+        conflicts_with_all = ["format", "json", "jsonl", "emit_script", "count_only", "split_by_top"]
+    )]
+    diff_against: Option<PathBuf>,
+
+    /// Compress the output
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    compress: Option<Compression>,
+
     /// Print JSON Schema for ProjectMetadata
     #[arg(long)]
     schema: bool,
 
+    /// Print a JSON description of every table/CSV column and the flag that enables it
+    #[arg(long)]
+    describe_columns: bool,
+
     /// Filter by repostatus state. Valid values: unreviewed, pending, skip, ready, posted, no-git.
     /// Can be specified multiple times.
     #[arg(long, value_name = "STATE")]
     filter: Vec<String>,
 
+    /// Only report git repos whose HEAD is detached (e.g. left mid-bisect or mid-rebase)
+    #[arg(long)]
+    only_detached: bool,
+
+    /// Only report git repos stuck mid-merge, mid-rebase, mid-cherry-pick, etc.
+    /// Adds a `state` column naming the operation.
+    #[arg(long)]
+    only_interrupted: bool,
+
+    /// Only report git repos whose newest commit is older than AGE (e.g. 90d, 6w, 2y)
+    #[arg(long, value_name = "AGE", value_parser = lsproj::age::parse_age)]
+    min_age: Option<chrono::Duration>,
+
+    /// Only report git repos whose pack files (history, not working tree) exceed BYTES
+    #[arg(long, value_name = "BYTES")]
+    min_pack_size: Option<u64>,
+
+    /// Add a `pack_size` column with the total bytes of `.git/objects/pack/*.pack`
+    #[arg(long)]
+    show_pack_size: bool,
+
+    /// Add a `dirty` column: whether the work tree has uncommitted or untracked changes
+    #[arg(long)]
+    show_dirty: bool,
+
+    /// Run a quick integrity check on each repo and add `healthy` and `health_note` columns
+    #[arg(long)]
+    verify: bool,
+
+    /// Only report git repos whose newest commit was committed by EMAIL. Wrap in slashes
+    /// for a regex, e.g. '/@oldco\.com$/'
+    #[arg(long, value_name = "EMAIL", value_parser = lsproj::author::parse_author_pattern)]
+    last_author: Option<AuthorPattern>,
+
+    /// Add a `remote_branches` column counting remote-tracking branches (0 without remotes)
+    #[arg(long)]
+    show_remote_branches: bool,
+
+    /// Only report git repos whose `.git` is over --gc-ratio times the size of the working
+    /// tree (candidates for `git gc`). Adds `git_size` and `worktree_size` columns.
+    #[arg(long)]
+    gc_candidates: bool,
+
+    /// `.git` to working-tree size ratio that makes a --gc-candidates match
+    #[arg(
+        long,
+        value_name = "RATIO",
+        default_value_t = 10.0,
+        requires = "gc_candidates"
+    )]
+    gc_ratio: f64,
+
+    /// Only report git repos with local branches not merged into the default branch
+    /// (origin's default, else main or master). Adds an `unmerged_branches` column.
+    #[arg(long)]
+    unmerged_branches: bool,
+
+    /// Add `branch_ahead` and `branch_behind` columns: commits on local branch A that B
+    /// lacks and the reverse, e.g. `--branch-delta main release`. Empty when either branch
+    /// is missing.
+    #[arg(long, num_args = 2, value_names = ["A", "B"])]
+    branch_delta: Option<Vec<String>>,
+
+    /// Only report git repos whose default branch is behind its upstream, as of the last
+    /// fetch (no network). Adds a `behind` column.
+    #[arg(long)]
+    only_behind: bool,
+
+    /// Connect to each repo's origin (like `git ls-remote`) and add an `origin_reachable`
+    /// column. Uses the network; checks run within --repo-concurrency.
+    #[arg(long)]
+    validate_origin: bool,
+
+    /// Seconds to wait for each --validate-origin connection
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 10,
+        requires = "validate_origin"
+    )]
+    origin_timeout: u64,
+
+    /// Rely on each repo's commit-graph (`git commit-graph write --reachable`) to speed up
+    /// history walks, which can make counting many times faster on large repos. libgit2
+    /// reads the graph whenever one exists; with -v, repos lacking one are listed.
+    #[arg(long)]
+    use_commit_graph: bool,
+
+    /// Only report git repos with a commit whose message matches REGEX (e.g. '(?i)wip|todo')
+    #[arg(long, value_name = "REGEX", value_parser = regex::Regex::new, group = "history_walk")]
+    message_matches: Option<regex::Regex>,
+
+    /// Add a `matching_commits` column counting commits that match --message-matches
+    #[arg(long, requires = "message_matches")]
+    only_matching_commits: bool,
+
+    /// Test at most N commits per repo against --message-matches, or count the authors
+    /// of at most N for --show-contributors
+    #[arg(long, value_name = "N", requires = "history_walk")]
+    max_commits: Option<usize>,
+
+    /// Add a `contributors` column counting distinct author emails across the history
+    #[arg(long, group = "history_walk")]
+    show_contributors: bool,
+
+    /// Only report git repos whose HEAD commit is on no remote-tracking branch: stronger
+    /// than having no origin, it also catches remotes that are behind local history
+    #[arg(long)]
+    truly_unbacked: bool,
+
+    /// Only report git repos with no tags but more than --unreleased-min-commits commits:
+    /// mature projects that were never released
+    #[arg(long)]
+    unreleased: bool,
+
+    /// Commit count a tagless repo must exceed to match --unreleased
+    #[arg(long, value_name = "N", default_value_t = 50, requires = "unreleased")]
+    unreleased_min_commits: u32,
+
+    /// Add a `head` column with the abbreviated HEAD commit hash
+    #[arg(long)]
+    show_head: bool,
+
+    /// Show the full 40-character hash in the `head` column
+    #[arg(long, requires = "show_head")]
+    full_hash: bool,
+
+    /// Add an `lfs` column: whether the repo uses Git LFS
+    #[arg(long)]
+    show_lfs: bool,
+
+    /// Only report git repos that use Git LFS
+    #[arg(long)]
+    only_lfs: bool,
+
+    /// Only report stub repos: initialized but never committed to, with no branches
+    /// beyond the unborn default (typically an accidental `git init`)
+    #[arg(long)]
+    only_stub: bool,
+
+    /// Add `description` and `visibility` columns from the GitHub API for repos whose
+    /// origin is on GitHub, authenticating with $GITHUB_TOKEN if set. Uses the network;
+    /// lookups run within --repo-concurrency.
+    #[arg(long)]
+    enrich_github: bool,
+
+    /// GitHub API endpoint for --enrich-github, e.g. for GitHub Enterprise
+    #[arg(long, value_name = "URL", default_value = lsproj::github::API_BASE, requires = "enrich_github")]
+    github_api_url: String,
+
+    /// Add a `classification` column for repos with an origin: original, fork (has an
+    /// `upstream` remote elsewhere) or mirror
+    #[arg(long)]
+    classify: bool,
+
+    /// Add `user_name` and `user_email` columns from each repo's own config, empty when
+    /// only set globally
+    #[arg(long)]
+    show_identity: bool,
+
+    /// Add a `repo_id` column: the SHA of the root commit, which identifies the same repo
+    /// across clones and hosts better than its origin URL
+    #[arg(long)]
+    repo_id: bool,
+
+    /// Add a `worktrees` column listing each repo's linked worktrees (`git worktree add`)
+    #[arg(long)]
+    show_worktrees: bool,
+
+    /// Only report repos with at least one linked worktree
+    #[arg(long)]
+    only_with_worktrees: bool,
+
+    /// Add a `signed` column: whether the HEAD commit is GPG/SSH-signed
+    #[arg(long)]
+    show_signed: bool,
+
+    /// Add a `locked` column: whether `.git` holds a stray lock file (`index.lock`,
+    /// `HEAD.lock`, ...) from an interrupted or crashed git operation
+    #[arg(long)]
+    show_locks: bool,
+
+    /// Only report git repos holding a stray lock file
+    #[arg(long)]
+    only_locked: bool,
+
+    /// Add `hooks` and `mailmap` columns: whether the hooks directory (`core.hooksPath`,
+    /// else `.git/hooks`) holds hooks other than the stock samples, and whether the repo
+    /// has a `.mailmap`
+    #[arg(long)]
+    show_hooks: bool,
+
+    /// Only report git repos with a remote named NAME, e.g. `upstream` or `backup`
+    #[arg(long, value_name = "NAME")]
+    has_remote: Option<String>,
+
+    /// Add a `ci` column naming the CI system configured in the working tree
+    #[arg(long)]
+    show_ci: bool,
+
+    /// Only report git repos with CI configured
+    #[arg(long, conflicts_with = "no_ci")]
+    only_ci: bool,
+
+    /// Only report git repos without CI configured
+    #[arg(long)]
+    no_ci: bool,
+
+    /// Treat repos whose origin was removed as uploaded when origin's remote-tracking
+    /// reflog shows past fetches or pushes; --emit-script then skips them
+    #[arg(long)]
+    check_reflog: bool,
+
+    /// Report only the first repo (in path order) for each origin URL, leaving out other
+    /// clones of the same upstream. With -v the skipped clones are listed on stderr.
+    #[arg(long)]
+    dedup_by_origin: bool,
+
+    /// Report only the repo with the newest commit in each top-level group (or each
+    /// --group-depth group): a summary of what was last worked on in each area
+    #[arg(long)]
+    latest_per_group: bool,
+
+    /// Run PROGRAM with each project's absolute path as its argument, keeping the project
+    /// only if it exits 0. Runs after the other filters.
+    #[arg(long, value_name = "PROGRAM")]
+    filter_cmd: Option<PathBuf>,
+
+    /// Most --filter-cmd processes running at once
+    #[arg(long, value_name = "N", default_value_t = 8, requires = "filter_cmd")]
+    filter_cmd_jobs: usize,
+
+    /// Seconds before a --filter-cmd process is killed and its project left out
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 10,
+        requires = "filter_cmd"
+    )]
+    filter_cmd_timeout: u64,
+
+    /// Report submodule checkouts (a `.git` file pointing into another repo's
+    /// `.git/modules/`), which are skipped by default as dependencies
+    #[arg(long)]
+    include_dependency_checkouts: bool,
+
+    /// Only report projects on a different filesystem than / (e.g. an external drive).
+    /// Unix only.
+    #[arg(long)]
+    external_only: bool,
+
+    /// Add a column with origin's default branch, read from the cached origin/HEAD
+    #[arg(long)]
+    show_upstream_default: bool,
+
+    /// Query origin over the network for its default branch instead of using origin/HEAD
+    #[arg(long, requires = "show_upstream_default")]
+    remote_connect: bool,
+
+    /// Hold at most N results in memory while scanning; beyond that, sorted runs are
+    /// spilled to temp files and merged for output
+    #[arg(long, value_name = "N")]
+    sort_buffer: Option<usize>,
+
+    /// Only descend into and report directories under $HOME (useful when scanning from /)
+    #[arg(long)]
+    home_only: bool,
+
+    /// Limit directory reads to N per second, smoothing I/O bursts on slow disks
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    rate: Option<u32>,
+
+    /// Record completed top-level subtrees (and their results) in PATH as the scan runs
+    #[arg(long, value_name = "PATH")]
+    checkpoint: Option<PathBuf>,
+
+    /// Skip subtrees already completed in the --checkpoint file, reusing their results
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+
+    /// Incremental scan: reuse history metrics from the previous scan saved in PATH for repos
+    /// whose git dir is unchanged since, then save this scan's records there. Working-tree
+    /// metrics are always re-read; a scan with different flags recomputes everything.
+    #[arg(long, value_name = "PATH", conflicts_with = "bare_mirrors")]
+    since_last_scan: Option<PathBuf>,
+
+    /// Treat DIR as a directory of bare mirror repos: inventory each immediate `*.git`
+    /// subdirectory instead of walking for working trees
+    #[arg(long)]
+    bare_mirrors: bool,
+
+    /// Only look for projects among the starting directory's immediate subdirectories
+    #[arg(long)]
+    no_recurse: bool,
+
+    /// Fast path for a directory of repos: report the immediate subdirectories holding a
+    /// `.git` directory, without reading the others. Implies --no-recurse; projects found
+    /// only by their files or --repo-marker are not reported.
+    #[arg(long, conflicts_with_all = ["bare_mirrors", "checkpoint"])]
+    flat: bool,
+
+    /// Skip directories not modified since FILE was. A directory's mtime changes when
+    /// entries are added or removed, so this finds new repos quickly but misses edits
+    /// inside existing ones.
+    #[arg(long, value_name = "FILE")]
+    newer_than: Option<PathBuf>,
+
+    /// Skip full history walks: report only the newest unpushed date, leaving `oldest`
+    /// and the counts blank. Much faster on repos with enormous histories. Implied when
+    /// --columns selects none of count, unpushed, commits or oldest.
+    #[arg(long)]
+    newest_only: bool,
+
+    /// Show oldest/newest dates in each commit's recorded timezone, as `git log` does,
+    /// instead of this machine's
+    #[arg(long)]
+    author_local: bool,
+
+    /// Retry directory reads that fail transiently (e.g. on network mounts) up to N times
+    #[arg(long, value_name = "N", default_value_t = 2)]
+    retries: u32,
+
+    /// Add a `language` column with the most common file extension (sampled, heuristic)
+    #[arg(long)]
+    show_language: bool,
+
+    /// Directory traversal engine: `async` (tokio tasks) or `threads` (rayon pool)
+    #[arg(long, value_enum, default_value_t = Engine::Async)]
+    engine: Engine,
+
+    /// Treat directories containing NAME (e.g. .hg, .svn, .project) as project roots.
+    /// Can be specified multiple times.
+    #[arg(long, value_name = "NAME")]
+    repo_marker: Vec<String>,
+
+    /// Skip directories containing FILE, in addition to those with a valid `CACHEDIR.TAG`.
+    /// Can be specified multiple times.
+    #[arg(long, value_name = "FILE")]
+    exclude_dir_with: Vec<String>,
+
+    /// Skip directories whose name matches an entry in FILE: one name or glob per line,
+    /// `#` for comments. Extends the built-in list of build and dependency dirs.
+    #[arg(long, value_name = "FILE")]
+    deny_from: Option<PathBuf>,
+
+    /// Descend into /proc, /sys, /dev, /run and other pseudo-filesystems, which are
+    /// skipped by default
+    #[arg(long)]
+    scan_special: bool,
+
+    /// Use the starting directory as given (made absolute) instead of resolving symlinks,
+    /// so reported paths keep the prefix you typed
+    #[arg(long)]
+    no_canonicalize: bool,
+
+    /// Derive reported paths from the work tree (default) or the git directory
+    #[arg(long, value_enum, value_name = "SOURCE", default_value_t = NameFrom::Workdir)]
+    name_from: NameFrom,
+
+    /// Express paths relative to the current directory, the scan root (default), or as
+    /// absolute paths
+    #[arg(long, value_enum, value_name = "BASE", default_value_t = RelativeTo::Root)]
+    relative_to: RelativeTo,
+
+    /// Name projects relative to the scan root (default) or its parent, which prefixes
+    /// every name with the root's own name (`projects/foo`)
+    #[arg(
+        long,
+        value_enum,
+        value_name = "BASE",
+        default_value_t = NameBase::Root,
+        conflicts_with = "relative_to"
+    )]
+    name_base: NameBase,
+
+    /// Treat repos with any remote URL containing PATTERN as backed up and leave them out
+    /// (e.g. a private mirror host). SSH and HTTPS forms of a URL match alike. Can be
+    /// specified multiple times.
+    #[arg(long, value_name = "PATTERN")]
+    trusted_remote: Vec<String>,
+
+    /// Report origin URLs as `host/owner/repo`, the form used to compare them, so SSH and
+    /// HTTPS clones of the same remote show the same origin
+    #[arg(long)]
+    normalize_origin: bool,
+
+    /// Directory reads in flight at once (async engine; default 100)
+    #[arg(long, value_name = "N")]
+    dir_concurrency: Option<usize>,
+
+    /// When directory reads fail with "Too many open files", lower the number in flight
+    /// and retry, then raise it again gradually. Only directory reads adapt; extractions
+    /// stay at --repo-concurrency. Requires the async engine.
+    #[arg(long)]
+    adaptive_concurrency: bool,
+
+    /// Repos whose history is walked at once (async engine; default: number of CPUs).
+    /// Kept separate from --dir-concurrency so huge repos don't stall discovery.
+    #[arg(long, value_name = "N")]
+    repo_concurrency: Option<usize>,
+
+    /// Show a live view of projects as they are found; `q` stops the scan early and
+    /// reports what was found so far
+    #[cfg(feature = "tui")]
+    #[arg(long, conflicts_with = "checkpoint")]
+    tui: bool,
+
+    /// Exit with an error, rather than warn, when the open-file limit looks too low for
+    /// the configured concurrency
+    #[arg(long)]
+    strict: bool,
+
+    /// Stop the scan after SECS seconds and report what was found so far. Projects being
+    /// read when time runs out are still finished. With --checkpoint, subtrees left
+    /// unfinished are walked again on --resume.
+    #[arg(long, value_name = "SECS", conflicts_with = "bare_mirrors")]
+    deadline: Option<u64>,
+
+    /// Exit with an error after writing the report if any directory could not be read or
+    /// any repo could not be processed. Unreadable repos carry an `error` field in JSON.
+    #[arg(long)]
+    require_clean_exit: bool,
+
+    /// Count directories in a quick first pass, then show percent complete and an ETA on
+    /// stderr during the scan
+    #[arg(long)]
+    estimate: bool,
+
+    /// Print counts of skipped directories by reason to stderr after the scan
+    #[arg(long)]
+    stats: bool,
+
+    /// Print warnings about results that may be misleading (e.g. shallow clones). Repeat
+    /// (-vv) to also list the slowest projects to process.
+    #[arg(long, short, action = clap::ArgAction::Count)]
+    verbose: u8,
+
     #[command(subcommand)]
     command: Option<SubCommand>,
 }
@@ -61,198 +617,787 @@ async fn main() -> Result<()> {
         output::print_schema();
         return Ok(());
     }
+    if args.describe_columns {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&output::describe_columns())?
+        );
+        return Ok(());
+    }
 
     if let Some(SubCommand::Mark {
         path,
         state,
         reason,
-    }) = args.command
+    }) = &args.command
     {
         let canonical = tokio::fs::canonicalize(&path)
             .await
             .with_context(|| format!("Path not found: {}", path.display()))?;
-        lsproj::repostatus::write_repostatus(&canonical, &state, reason.as_deref())?;
+        lsproj::repostatus::write_repostatus(&canonical, state, reason.as_deref())?;
         println!("Marked {} as {state}", canonical.display());
         return Ok(());
     }
 
-    let scan_dir = args.dir.unwrap_or_else(|| PathBuf::from("."));
-    let root_dir = tokio::fs::canonicalize(&scan_dir).await?;
+    // Columns picked with --columns need their data extracted as if their flag were given
+    let selected = |columns: &[Column]| columns.iter().any(|c| args.columns.contains(c));
+    if args.create.is_some() && !args.confirm && !args.dry_run {
+        anyhow::bail!(
+            "--create pushes to GitHub; pass --confirm to proceed or --dry-run to preview"
+        );
+    }
 
-    let root_for_discover = root_dir.clone();
-    let discover_result =
-        task::spawn_blocking(move || git2::Repository::discover(&root_for_discover)).await?;
-    if let Ok(repo) = discover_result
-        && let Some(workdir) = repo.workdir()
+    if args.adaptive_concurrency && args.engine == Engine::Threads {
+        anyhow::bail!("--adaptive-concurrency only applies to --engine async");
+    }
+
+    if args.json_remotes && !writes_json(&args) {
+        anyhow::bail!("--json-remotes only applies to --json and --jsonl output");
+    }
+
+    let opts = ExtractOptions {
+        upstream_default: args.show_upstream_default || selected(&[Column::UpstreamDefault]),
+        remote_connect: args.remote_connect,
+        trusted_remotes: args.trusted_remote.clone(),
+        sample_extensions: args.show_language || selected(&[Column::Language]),
+        skip_loc: args.count_only,
+        name_from: args.name_from,
+        pack_size: args.show_pack_size
+            || args.min_pack_size.is_some()
+            || selected(&[Column::PackSize]),
+        dirty: args.show_dirty || selected(&[Column::Dirty]),
+        verify: args.verify || selected(&[Column::Healthy, Column::HealthNote]),
+        sizes: args.gc_candidates || selected(&[Column::GitSize, Column::WorktreeSize]),
+        newest_only: args.newest_only || !needs_full_history(&args),
+        unmerged: args.unmerged_branches || selected(&[Column::UnmergedBranches]),
+        behind: args.only_behind || selected(&[Column::Behind]),
+        branch_delta: args
+            .branch_delta
+            .as_deref()
+            .map(|pair| (pair[0].clone(), pair[1].clone())),
+        author_local: args.author_local,
+        device_id: args.external_only,
+        check_reflog: args.check_reflog,
+        tags: args.unreleased,
+        head_on_remote: args.truly_unbacked,
+        message_pattern: args.message_matches.clone(),
+        count_message_matches: args.only_matching_commits || selected(&[Column::MatchingCommits]),
+        max_commits: args.max_commits,
+        contributors: args.show_contributors || selected(&[Column::Contributors]),
+        commit_graph: args.use_commit_graph,
+        signed: args.show_signed || selected(&[Column::Signed]),
+        locks: args.show_locks || args.only_locked || selected(&[Column::Locked]),
+        hooks: args.show_hooks || selected(&[Column::Hooks, Column::Mailmap]),
+        classify: args.classify || selected(&[Column::Classification]),
+        identity: args.show_identity || selected(&[Column::UserName, Column::UserEmail]),
+        repo_id: args.repo_id || selected(&[Column::RepoId]),
+        remotes: args.json_remotes,
+        worktrees: args.show_worktrees
+            || args.only_with_worktrees
+            || selected(&[Column::Worktrees]),
+        github: (args.enrich_github || selected(&[Column::Description, Column::Visibility])).then(
+            || {
+                Arc::new(GithubClient::new(
+                    &args.github_api_url,
+                    std::env::var("GITHUB_TOKEN").ok(),
+                    GITHUB_TIMEOUT,
+                ))
+            },
+        ),
+        validate_origin: (args.validate_origin || selected(&[Column::OriginReachable]))
+            .then(|| std::time::Duration::from_secs(args.origin_timeout)),
+    };
+
+    let scan_dir = args.dir.clone().unwrap_or_else(|| PathBuf::from("."));
+    let canonicalize = !args.no_canonicalize;
+    let root_dir = resolve_dir(&scan_dir, canonicalize).await?;
+
+    let home = if args.home_only {
+        let home = std::env::var_os("HOME").context("--home-only requires $HOME to be set")?;
+        Some(resolve_dir(Path::new(&home), canonicalize).await?)
+    } else {
+        None
+    };
+    let timings = (args.verbose >= 2).then(|| Arc::new(Timings::new()));
+    let stats = args.stats.then(|| Arc::new(SkipStats::new()));
+    let cache = match &args.since_last_scan {
+        Some(path) => Some(Arc::new(ScanCache::open(path, &root_dir, &opts)?)),
+        None => None,
+    };
+    let newer_than = match &args.newer_than {
+        Some(marker) => Some(
+            std::fs::metadata(marker)
+                .and_then(|m| m.modified())
+                .with_context(|| format!("Cannot read mtime of {}", marker.display()))?,
+        ),
+        None => None,
+    };
+    let deny = match &args.deny_from {
+        Some(path) => DenyList::load(path)?,
+        None => DenyList::default(),
+    };
+    // Set when --deadline passes or --tui is quit
+    let cancel = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let mut walk_opts = WalkOptions {
+        extract: opts,
+        engine: args.engine,
+        sort_buffer: args.sort_buffer,
+        home,
+        retries: args.retries,
+        markers: args.repo_marker.clone(),
+        exclude_sentinels: args.exclude_dir_with.clone(),
+        deny,
+        scan_special: args.scan_special,
+        rate: args.rate,
+        checkpoint: args.checkpoint.clone(),
+        resume: args.resume,
+        max_depth: (args.no_recurse || args.flat).then_some(1),
+        flat: args.flat,
+        newer_than,
+        include_dependency_checkouts: args.include_dependency_checkouts,
+        stats: stats.clone(),
+        timings: timings.clone(),
+        progress: None,
+        dir_concurrency: args.dir_concurrency,
+        adaptive_concurrency: args.adaptive_concurrency,
+        repo_concurrency: args.repo_concurrency,
+        since_last_scan: cache.clone(),
+        live: None,
+        cancel: Some(cancel.clone()),
+    };
+    // The threads engine is bounded by its pool size instead
+    if walk_opts.engine == Engine::Async
+        && let Some(limit) = fdlimit::soft_limit()
+        && let Some(warning) =
+            fdlimit::check(walk_opts.dir_permits(), walk_opts.repo_permits(), limit)
     {
-        let workdir = workdir.to_path_buf();
-        let parent = workdir.parent().unwrap_or(&workdir).to_path_buf();
-        let meta =
-            task::spawn_blocking(move || extract_metadata(&workdir, &parent)).await??;
-        let results = apply_filters(vec![meta], &args.filter);
-        match (args.json, args.csv) {
-            (true, _) => output::print_json(&results),
-            (_, true) => output::print_csv(&results),
-            _ => output::print_table(&results),
+        if args.strict {
+            anyhow::bail!(warning);
         }
-        return Ok(());
+        eprintln!("warning: {warning}");
     }
-
-    let tasks: Arc<Mutex<Vec<JoinHandle<()>>>> = Arc::new(Mutex::new(Vec::new()));
-    let semaphore = Arc::new(Semaphore::new(100));
-    let seen_paths: Arc<Mutex<HashSet<PathBuf>>> = Arc::new(Mutex::new(HashSet::new()));
-    let results: Arc<Mutex<Vec<ProjectMetadata>>> = Arc::new(Mutex::new(Vec::new()));
-
-    let tasks_clone = tasks.clone();
-    let root_clone = root_dir.clone();
-    let seen_clone = seen_paths.clone();
-    let results_clone = results.clone();
-
-    let initial_task = task::spawn(async move {
-        if let Err(e) = walk_dir(
-            root_clone.clone(),
-            root_clone,
-            tasks_clone,
-            semaphore,
-            seen_clone,
-            results_clone,
-        )
-        .await
+    let progress = if args.estimate && !args.bare_mirrors {
+        let (root, opts) = (root_dir.clone(), walk_opts.clone());
+        let total = tokio::task::spawn_blocking(move || walk::count_dirs(&root, &opts)).await?;
+        eprintln!("estimate: {total} directories to examine");
+        let progress = Arc::new(Progress::new(total));
+        walk_opts.progress = Some(progress.clone());
+        Some(progress)
+    } else {
+        None
+    };
+    // The live view shows progress itself
+    let reporter = match &progress {
+        Some(progress) if !tui_requested(&args) => Some(tokio::spawn({
+            let progress = progress.clone();
+            async move {
+                let mut interval = tokio::time::interval(PROGRESS_INTERVAL);
+                loop {
+                    interval.tick().await;
+                    eprint!("\r{}", progress.status());
+                }
+            }
+        })),
+        _ => None,
+    };
+    #[cfg(feature = "tui")]
+    let viewer = if args.tui {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let cancel = cancel.clone();
+        walk_opts.live = Some(tx);
+        let progress = progress.clone();
+        Some(tokio::task::spawn_blocking(move || {
+            lsproj::tui::run(rx, progress.as_deref(), &cancel)
+        }))
+    } else {
+        None
+    };
+    let deadline = args.deadline.and_then(|secs| {
+        if secs == 0 {
+            // Already passed: stop before the first directory is read
+            cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+            return None;
+        }
+        let cancel = cancel.clone();
+        Some(tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(secs)).await;
+            cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+        }))
+    });
+    let scan = if args.bare_mirrors {
+        walk::scan_bare_mirrors(root_dir.clone(), walk_opts).await?
+    } else {
+        walk::scan(root_dir.clone(), walk_opts).await?
+    };
+    // The timer may have fired after the walk was already done; only a walk that saw
+    // the cancel and skipped work is partial
+    let deadline_passed = deadline.is_none_or(|timer| {
+        let finished = timer.is_finished();
+        timer.abort();
+        finished
+    });
+    if args.deadline.is_some() && deadline_passed && scan.cancelled {
+        eprintln!("scan stopped at --deadline; results are partial");
+    }
+    #[cfg(feature = "tui")]
+    if let Some(viewer) = viewer
+        && viewer.await?? == lsproj::tui::Exit::Quit
+    {
+        eprintln!("scan stopped early; results are partial");
+    }
+    if let (Some(task), Some(progress)) = (reporter, &progress) {
+        task.abort();
+        eprintln!("\r{}", progress.status());
+    }
+    if let Some(cache) = cache {
+        cache.save()?;
+        if args.verbose >= 1 {
+            eprintln!(
+                "note: re-read {} changed projects since the last scan",
+                cache.recomputed()
+            );
+        }
+    }
+    let cwd = resolve_dir(&std::env::current_dir()?, canonicalize).await?;
+    let name_root = match args.name_base {
+        NameBase::Root => scan.base.clone(),
+        NameBase::Parent => root_dir.parent().unwrap_or(&root_dir).to_path_buf(),
+    };
+    let report_base = match args.relative_to {
+        RelativeTo::Cwd => cwd.clone(),
+        RelativeTo::Root => name_root.clone(),
+        RelativeTo::Absolute => scan.base.clone(),
+    };
+    let mut writer = match (&args.split_by_top, &args.diff_against) {
+        (Some(dir), _) => Report::Split(
+            SplitWriter::new(dir, |out| output_writer(&args, &report_base, out))
+                .context("Cannot create --split-by-top directory")?,
+        ),
+        (None, Some(prior)) => {
+            let diff = InventoryDiff::load(prior)?;
+            if diff.measure() != "commits" {
+                eprintln!(
+                    "{} has no commits column; comparing the unpushed {} instead",
+                    prior.display(),
+                    diff.measure()
+                );
+            }
+            Report::Diff(
+                Box::new(diff),
+                Sink::open(args.output.as_deref(), args.compress)
+                    .context("Cannot open --output file")?,
+            )
+        }
+        (None, None) => Report::Single(Box::new(output_writer(
+            &args,
+            &report_base,
+            Sink::open(args.output.as_deref(), args.compress)
+                .context("Cannot open --output file")?,
+        ))),
+    };
+    let runner: Box<dyn CommandRunner> = if args.dry_run {
+        Box::new(DryRunner)
+    } else {
+        Box::new(SystemRunner)
+    };
+    let mut emit = |relative: String, mut meta: ProjectMetadata| -> Result<()> {
+        if args.verbose >= 1 && meta.is_shallow {
+            eprintln!(
+                "warning: {} is a shallow clone; commit counts cover fetched history only",
+                meta.path
+            );
+        }
+        if args.verbose >= 1 && meta.has_commit_graph == Some(false) {
+            eprintln!(
+                "note: {} has no commit-graph; `git commit-graph write --reachable` speeds up scans",
+                meta.path
+            );
+        }
+        if let Some(user) = &args.create
+            && publish::needs_publishing(&meta)
         {
-            eprintln!("Error in root: {e:?}");
+            let result = publish::publish(&meta, &report_base, user, runner.as_ref());
+            meta.publish_result = Some(if args.dry_run {
+                "would create".to_string()
+            } else {
+                result
+            });
         }
+        writer.write(&relative, meta)?;
+        Ok(())
+    };
+    // Filter while merging; streaming formats never hold the full result set.
+    // --latest-per-group holds one record per group until the end.
+    let filters = build_filters(&args);
+    // Opens each repo again, so it runs after the cheaper filters
+    let has_remote = args.has_remote.clone().map(|name| HasRemoteNamed { name });
+    let mut latest: BTreeMap<String, (String, ProjectMetadata)> = BTreeMap::new();
+    let mut admit = |relative: String, meta: ProjectMetadata| -> Result<()> {
+        if args.latest_per_group {
+            // Commit times are UTC RFC 3339, so they order as strings; repos without
+            // commits lose to any with. Ties keep the first in path order.
+            let group = group_key(&meta.path, args.group_depth.unwrap_or(1));
+            match latest.get(&group) {
+                Some((_, current)) if current.newest_commit >= meta.newest_commit => {}
+                _ => {
+                    latest.insert(group, (relative, meta));
+                }
+            }
+            Ok(())
+        } else {
+            emit(relative, meta)
+        }
+    };
+    // --filter-cmd runs ahead of the output by up to --filter-cmd-jobs projects, which
+    // are admitted in order as their commands finish
+    let filter_cmd = args.filter_cmd.as_ref().map(|program| {
+        Arc::new(FilterCommand::new(
+            program,
+            std::time::Duration::from_secs(args.filter_cmd_timeout),
+        ))
     });
-    tasks.lock().unwrap().push(initial_task);
-
-    loop {
-        let current_tasks = {
-            let mut locked = tasks.lock().unwrap();
-            if locked.is_empty() {
-                break;
+    let mut pending: VecDeque<(String, ProjectMetadata, tokio::task::JoinHandle<bool>)> =
+        VecDeque::new();
+    let mut unreadable = 0;
+    for meta in scan.results.into_sorted()? {
+        let mut meta = meta?;
+        if let Some(error) = &meta.error {
+            unreadable += 1;
+            eprintln!("Error reading {}: {error}", meta.path);
+        }
+        let absolute = scan.base.join(&meta.path);
+        // --split-by-top groups by the path under the root, not the displayed one
+        let relative = meta.path.clone();
+        meta.path = match args.name_base {
+            NameBase::Parent => lsproj::rebase_onto(&meta.path, &scan.base, &name_root),
+            NameBase::Root => {
+                lsproj::rebase_display_path(&meta.path, &scan.base, &cwd, args.relative_to)
             }
-            std::mem::take(&mut *locked)
         };
-        for handle in current_tasks {
-            let _ = handle.await;
+        if args.normalize_origin {
+            meta.origin_url = meta.origin_url.map(|url| normalize_origin_url(&url));
+        }
+        if !filters.filter(&meta) || has_remote.as_ref().is_some_and(|f| !f.filter(&absolute)) {
+            continue;
+        }
+        let Some(cmd) = &filter_cmd else {
+            admit(relative, meta)?;
+            continue;
+        };
+        let cmd = cmd.clone();
+        pending.push_back((
+            relative,
+            meta,
+            tokio::spawn(async move { cmd.accepts(&absolute).await }),
+        ));
+        if pending.len() >= args.filter_cmd_jobs.max(1)
+            && let Some((relative, meta, accepted)) = pending.pop_front()
+            && accepted.await?
+        {
+            admit(relative, meta)?;
         }
     }
+    for (relative, meta, accepted) in pending {
+        if accepted.await? {
+            admit(relative, meta)?;
+        }
+    }
+    for (relative, meta) in latest.into_values() {
+        emit(relative, meta)?;
+    }
+    writer.finish()?;
 
-    let mut all = Arc::try_unwrap(results)
-        .expect("results arc still held")
-        .into_inner()
-        .unwrap();
-    all.sort_by(|a, b| a.path.cmp(&b.path));
-    let all = apply_filters(all, &args.filter);
-
-    match (args.json, args.csv) {
-        (true, _) => output::print_json(&all),
-        (_, true) => output::print_csv(&all),
-        _ => output::print_table(&all),
+    if let Some(stats) = stats {
+        eprint!("{stats}");
+    }
+    if let Some(timings) = timings {
+        eprintln!("slowest projects:");
+        for (path, elapsed) in timings.slowest(SLOWEST_REPORTED) {
+            eprintln!("  {:>8.1?}  {}", elapsed, path.display());
+        }
+    }
+    let errors = scan.errors + unreadable;
+    if args.require_clean_exit && errors > 0 {
+        anyhow::bail!("{errors} directories or projects could not be read");
     }
 
     Ok(())
 }
 
-fn apply_filters(projects: Vec<ProjectMetadata>, filters: &[String]) -> Vec<ProjectMetadata> {
-    if filters.is_empty() {
-        return projects;
-    }
-    projects
-        .into_iter()
-        .filter(|p| {
-            filters.iter().any(|f| match f.as_str() {
-                "no-git" => !p.is_git,
-                state => p.repostatus_state == state,
-            })
-        })
-        .collect()
+/// Where the report goes: one output, a file per top-level group (`--split-by-top`), or
+/// a comparison with an earlier report (`--diff-against`).
+enum Report<F> {
+    Single(Box<TableWriter<Sink>>),
+    Split(SplitWriter<F>),
+    Diff(Box<InventoryDiff>, Sink),
 }
 
-fn walk_dir(
-    dir: PathBuf,
-    root: PathBuf,
-    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
-    semaphore: Arc<Semaphore>,
-    seen_paths: Arc<Mutex<HashSet<PathBuf>>>,
-    results: Arc<Mutex<Vec<ProjectMetadata>>>,
-) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> {
-    Box::pin(async move {
-        let _permit = semaphore.acquire().await?;
-
-        let mut read_dir = tokio::fs::read_dir(&dir)
-            .await
-            .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
-
-        while let Some(entry) = read_dir
-            .next_entry()
-            .await
-            .with_context(|| format!("Failed to read entry in {}", dir.display()))?
-        {
-            let path = entry.path();
-            let ft = entry
-                .file_type()
-                .await
-                .with_context(|| format!("Failed to get file type for {}", path.display()))?;
-
-            if !ft.is_dir() {
-                continue;
+impl<F: FnMut(BufWriter<File>) -> TableWriter<BufWriter<File>>> Report<F> {
+    fn write(&mut self, relative: &str, record: ProjectMetadata) -> std::io::Result<()> {
+        match self {
+            Report::Single(writer) => writer.write(record),
+            Report::Split(writer) => writer.write(relative, record),
+            Report::Diff(diff, _) => {
+                diff.record(&record);
+                Ok(())
             }
+        }
+    }
 
-            // Check canonical path for cycle detection
-            if let Ok(canonical) = std::fs::canonicalize(&path) {
-                let mut seen = seen_paths.lock().unwrap();
-                if !seen.insert(canonical) {
-                    continue; // already visited via a symlink — skip
-                }
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Report::Single(writer) => writer.finish()?.close(),
+            Report::Split(writer) => writer.finish(),
+            Report::Diff(diff, mut out) => {
+                write!(out, "{diff}")?;
+                out.close()
             }
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+fn tui_requested(args: &Args) -> bool {
+    args.tui
+}
 
-            match classify_entry(&path) {
-                EntryKind::Skip => {}
-                EntryKind::Project => {
-                    let root_clone = root.clone();
-                    let path_clone = path.clone();
-                    let results_clone = results.clone();
-                    let path_display = path.display().to_string();
-                    let new_task = task::spawn(async move {
-                        let result = task::spawn_blocking(move || {
-                            extract_metadata(&path_clone, &root_clone)
-                        })
-                        .await;
-                        match result {
-                            Ok(Ok(meta)) => results_clone.lock().unwrap().push(meta),
-                            Ok(Err(e)) => eprintln!("Error extracting {path_display}: {e:?}"),
-                            Err(e) => eprintln!("Task panic for {path_display}: {e:?}"),
-                        }
-                    });
-                    tasks.lock().unwrap().push(new_task);
+#[cfg(not(feature = "tui"))]
+fn tui_requested(_args: &Args) -> bool {
+    false
+}
+
+/// Longest wait for one `--enrich-github` API request.
+const GITHUB_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How often `--estimate` refreshes its progress line.
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Projects listed by `-vv` after the scan.
+const SLOWEST_REPORTED: usize = 10;
+
+/// Output formats selectable with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Jsonl,
+    Csv,
+    /// Indented directory tree with commit counts
+    Tree,
+    /// Parquet file (binary; use with --output)
+    Parquet,
+    /// GitHub-flavored Markdown table
+    Markdown,
+    /// Self-contained HTML page with a sortable table
+    Html,
+    /// Shell variable assignments (`REPO_0_PATH=...`) for `eval`
+    Env,
+    /// TOML array of `[[repo]]` tables
+    Toml,
+}
+
+/// Make `dir` absolute, resolving symlinks unless `--no-canonicalize` was given.
+async fn resolve_dir(dir: &Path, canonicalize: bool) -> Result<PathBuf> {
+    let resolved = if canonicalize {
+        tokio::fs::canonicalize(dir).await
+    } else {
+        std::path::absolute(dir)
+    };
+    let resolved = resolved.with_context(|| format!("Path not found: {}", dir.display()))?;
+    if !resolved.is_dir() {
+        anyhow::bail!("Not a directory: {}", dir.display());
+    }
+    Ok(resolved)
+}
+
+/// True when records are written as JSON or JSON Lines, the formats that can nest.
+fn writes_json(args: &Args) -> bool {
+    args.emit_script.is_none()
+        && !args.count_only
+        && match args.format {
+            Some(format) => matches!(format, Format::Json | Format::Jsonl),
+            None => args.json || args.jsonl,
+        }
+}
+
+/// Whether anything in this run reads full commit counts or oldest unpushed dates. Only
+/// the column-driven formats with an explicit --columns can do without them.
+fn needs_full_history(args: &Args) -> bool {
+    const HISTORY_COLUMNS: [Column; 4] = [
+        Column::Count,
+        Column::Unpushed,
+        Column::Commits,
+        Column::Oldest,
+    ];
+    let column_driven = args.emit_script.is_none()
+        && !(args.json || args.jsonl)
+        && !matches!(
+            args.format,
+            Some(Format::Json | Format::Jsonl | Format::Tree | Format::Parquet)
+        );
+    !column_driven
+        || args.columns.is_empty()
+        || args.columns.iter().any(|c| HISTORY_COLUMNS.contains(c))
+        || tui_requested(args)
+        || args.unreleased
+        || args.diff_against.is_some()
+        || args.since_last_scan.is_some()
+}
+
+fn output_writer<W: Write>(args: &Args, root: &Path, sink: W) -> TableWriter<W> {
+    let format = if args.count_only {
+        OutputFormat::Count
+    } else if let Some(user) = &args.emit_script {
+        OutputFormat::Script {
+            user: user.clone(),
+            root: root.to_path_buf(),
+        }
+    } else {
+        let format = args.format.unwrap_or(if args.json {
+            Format::Json
+        } else if args.jsonl {
+            Format::Jsonl
+        } else if args.csv || args.split_by_top.is_some() {
+            Format::Csv
+        } else {
+            Format::Table
+        });
+        match format {
+            Format::Table => OutputFormat::Table,
+            Format::Json => OutputFormat::Json {
+                pretty: !args.compact,
+            },
+            Format::Jsonl => OutputFormat::Jsonl,
+            Format::Csv => OutputFormat::Csv {
+                delimiter: args.delimiter,
+            },
+            Format::Tree => OutputFormat::Tree { ascii: args.ascii },
+            Format::Parquet => OutputFormat::Parquet,
+            Format::Markdown => OutputFormat::Markdown,
+            Format::Html => OutputFormat::Html,
+            Format::Env => OutputFormat::Env,
+            Format::Toml => OutputFormat::Toml,
+        }
+    };
+    let mut extra = Vec::new();
+    if args.show_upstream_default {
+        extra.push(Column::UpstreamDefault);
+    }
+    if args.only_interrupted {
+        extra.push(Column::InterruptedState);
+    }
+    if args.show_language {
+        extra.push(Column::Language);
+    }
+    if args.show_pack_size {
+        extra.push(Column::PackSize);
+    }
+    if args.show_dirty {
+        extra.push(Column::Dirty);
+    }
+    if args.show_remote_branches {
+        extra.push(Column::RemoteBranches);
+    }
+    if args.gc_candidates {
+        extra.extend([Column::GitSize, Column::WorktreeSize]);
+    }
+    if args.unmerged_branches {
+        extra.push(Column::UnmergedBranches);
+    }
+    if args.only_behind {
+        extra.push(Column::Behind);
+    }
+    if args.branch_delta.is_some() {
+        extra.extend([Column::BranchAhead, Column::BranchBehind]);
+    }
+    if args.only_matching_commits {
+        extra.push(Column::MatchingCommits);
+    }
+    if args.show_contributors {
+        extra.push(Column::Contributors);
+    }
+    if args.validate_origin {
+        extra.push(Column::OriginReachable);
+    }
+    if args.create.is_some() {
+        extra.push(Column::Created);
+    }
+    if args.show_head {
+        extra.push(if args.full_hash {
+            Column::HeadFull
+        } else {
+            Column::Head
+        });
+    }
+    if args.show_lfs {
+        extra.push(Column::Lfs);
+    }
+    if args.show_ci {
+        extra.push(Column::Ci);
+    }
+    if args.show_signed {
+        extra.push(Column::Signed);
+    }
+    if args.show_locks {
+        extra.push(Column::Locked);
+    }
+    if args.show_hooks {
+        extra.extend([Column::Hooks, Column::Mailmap]);
+    }
+    if args.classify {
+        extra.push(Column::Classification);
+    }
+    if args.show_identity {
+        extra.extend([Column::UserName, Column::UserEmail]);
+    }
+    if args.repo_id {
+        extra.push(Column::RepoId);
+    }
+    if args.show_worktrees {
+        extra.push(Column::Worktrees);
+    }
+    if args.enrich_github {
+        extra.extend([Column::Description, Column::Visibility]);
+    }
+    if args.verify {
+        extra.extend([Column::Healthy, Column::HealthNote]);
+    }
+    let mut writer = TableWriter::new(sink, format, extra);
+    if !args.columns.is_empty() {
+        writer = writer.with_columns(args.columns.clone());
+    }
+    if args.group || args.group_depth.is_some() {
+        writer.group_by(args.group_depth.unwrap_or(1))
+    } else {
+        writer
+    }
+}
+
+/// The report filters selected by flags, ANDed together.
+fn build_filters(args: &Args) -> FilterChain<ProjectMetadata> {
+    let mut chain = FilterChain::new();
+    // Repos on a trusted mirror are backed up; they never need triage
+    chain.push(|p: &ProjectMetadata| !p.trusted_remote);
+    if !args.filter.is_empty() {
+        let states = args.filter.clone();
+        chain.push(move |p: &ProjectMetadata| matches_state(p, &states));
+    }
+    if args.only_detached {
+        chain.push(|p: &ProjectMetadata| p.head_detached);
+    }
+    if args.only_interrupted {
+        chain.push(|p: &ProjectMetadata| p.interrupted_state.is_some());
+    }
+    if let Some(age) = args.min_age {
+        chain.push(move |p: &ProjectMetadata| older_than(p.newest_commit.as_deref(), age));
+    }
+    if let Some(min) = args.min_pack_size {
+        chain.push(move |p: &ProjectMetadata| p.pack_size.is_some_and(|size| size > min));
+    }
+    if args.gc_candidates {
+        let ratio = args.gc_ratio;
+        chain.push(
+            move |p: &ProjectMetadata| match (p.git_size, p.worktree_size) {
+                (Some(git), Some(tree)) => git as f64 > ratio * tree.max(1) as f64,
+                _ => false,
+            },
+        );
+    }
+    if args.external_only {
+        let system_device = fs_meta::device_id(Path::new("/"));
+        chain.push(move |p: &ProjectMetadata| {
+            fs_meta::on_external_device(p.device_id, system_device)
+        });
+    }
+    if args.only_stub {
+        chain.push(is_stub);
+    }
+    if args.only_lfs {
+        chain.push(|p: &ProjectMetadata| p.uses_lfs);
+    }
+    if args.only_with_worktrees {
+        chain.push(|p: &ProjectMetadata| p.worktrees.as_ref().is_some_and(|w| !w.is_empty()));
+    }
+    if args.only_locked {
+        chain.push(|p: &ProjectMetadata| p.locked == Some(true));
+    }
+    if args.only_ci {
+        chain.push(|p: &ProjectMetadata| p.ci.is_some());
+    }
+    if args.no_ci {
+        chain.push(|p: &ProjectMetadata| p.is_git && p.ci.is_none());
+    }
+    if args.unmerged_branches {
+        chain.push(|p: &ProjectMetadata| p.unmerged_branches.is_some_and(|n| n > 0));
+    }
+    if args.only_behind {
+        chain.push(|p: &ProjectMetadata| p.behind.is_some_and(|n| n > 0));
+    }
+    if args.message_matches.is_some() {
+        chain.push(|p: &ProjectMetadata| p.matching_commits.is_some_and(|n| n > 0));
+    }
+    if args.truly_unbacked {
+        chain.push(|p: &ProjectMetadata| p.head_on_remote == Some(false));
+    }
+    if args.unreleased {
+        let min_commits = args.unreleased_min_commits;
+        chain.push(move |p: &ProjectMetadata| {
+            p.tag_count == Some(0) && p.total_commits.is_some_and(|n| n > min_commits)
+        });
+    }
+    if let Some(pattern) = args.last_author.clone() {
+        chain.push(move |p: &ProjectMetadata| {
+            p.newest_committer
+                .as_deref()
+                .is_some_and(|email| pattern.matches(email))
+        });
+    }
+    // Last, so only repos that pass every other filter claim their origin
+    if args.dedup_by_origin {
+        let verbose = args.verbose >= 1;
+        let first_seen: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+        chain.push(move |p: &ProjectMetadata| {
+            let Some(url) = p.origin_url.as_deref() else {
+                return true;
+            };
+            let mut first_seen = first_seen.borrow_mut();
+            match first_seen.entry(normalize_origin_url(url)) {
+                Entry::Vacant(slot) => {
+                    slot.insert(p.path.clone());
+                    true
                 }
-                EntryKind::Collection => {
-                    let root_clone = root.clone();
-                    let tasks_clone = tasks.clone();
-                    let semaphore_clone = semaphore.clone();
-                    let seen_clone = seen_paths.clone();
-                    let results_clone = results.clone();
-                    let path_clone = path.clone();
-                    let path_display = path.display().to_string();
-                    let new_task = task::spawn(async move {
-                        if let Err(e) = walk_dir(
-                            path_clone,
-                            root_clone,
-                            tasks_clone,
-                            semaphore_clone,
-                            seen_clone,
-                            results_clone,
-                        )
-                        .await
-                        {
-                            eprintln!("Error in {path_display}: {e:?}");
-                        }
-                    });
-                    tasks.lock().unwrap().push(new_task);
+                Entry::Occupied(first) => {
+                    if verbose {
+                        eprintln!("note: {} is another clone of {}", p.path, first.get());
+                    }
+                    false
                 }
             }
-        }
+        });
+    }
+    chain
+}
 
-        Ok(())
+/// True when `iso` (an RFC 3339 commit time) is more than `age` in the past.
+/// Projects without commits have no age and never match.
+fn older_than(iso: Option<&str>, age: chrono::Duration) -> bool {
+    iso.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .is_some_and(|t| t < chrono::Utc::now() - age)
+}
+
+/// A readable git repo with no commits anywhere: HEAD is unborn, no local branch has a
+/// tip, and nothing was fetched.
+fn is_stub(p: &ProjectMetadata) -> bool {
+    p.is_git
+        && p.healthy != Some(false)
+        && p.head_commit.is_none()
+        && p.newest_commit.is_none()
+        && p.remote_branches == Some(0)
+}
+
+fn matches_state(p: &ProjectMetadata, filters: &[String]) -> bool {
+    filters.iter().any(|f| match f.as_str() {
+        "no-git" => !p.is_git,
+        state => p.repostatus_state == state,
     })
 }
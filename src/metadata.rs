@@ -3,9 +3,13 @@ use std::path::Path;
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 
-use crate::fs_meta::extract_fs_info;
-use crate::git_info::extract_git_info;
-use crate::loc::extract_loc;
+use crate::NameFrom;
+use crate::fs_meta::{
+    EXTENSION_SAMPLE_LIMIT, device_id, dir_size, dominant_extension, extract_fs_info, git_dir_mtime,
+};
+use crate::git_info::{extract_git_info, is_dirty};
+use crate::github::GithubClient;
+use crate::loc::{LocInfo, extract_loc};
 use crate::repostatus::read_repostatus;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
@@ -16,6 +20,103 @@ pub struct LanguageStat {
     pub blanks: u64,
 }
 
+/// A configured remote, as listed by `git remote -v`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+}
+
+/// Optional extraction steps, enabled by CLI flags because they cost extra I/O.
+#[derive(Debug, Default, Clone)]
+pub struct ExtractOptions {
+    /// Resolve `origin`'s default branch (`--show-upstream-default`)
+    pub upstream_default: bool,
+    /// Query the remote over the network instead of trusting `origin/HEAD` (`--remote-connect`)
+    pub remote_connect: bool,
+    /// URL substrings of hosts that count as backed up (`--trusted-remote`)
+    pub trusted_remotes: Vec<String>,
+    /// Sample file extensions for a cheap language guess (`--show-language`)
+    pub sample_extensions: bool,
+    /// Skip tokei line counting when nothing will display it (`--count-only`)
+    pub skip_loc: bool,
+    /// Derive reported paths from the work tree or the git dir (`--name-from`)
+    pub name_from: NameFrom,
+    /// Sum `.git/objects/pack/*.pack` sizes (`--min-pack-size`, `--show-pack-size`)
+    pub pack_size: bool,
+    /// Check the work tree for uncommitted changes (`--show-dirty`)
+    pub dirty: bool,
+    /// Run a bounded integrity check on each repo (`--verify`)
+    pub verify: bool,
+    /// Measure `.git` and working-tree sizes (`--gc-candidates`)
+    pub sizes: bool,
+    /// Stop each history walk at its newest commit, skipping counts and oldest dates
+    /// (`--newest-only`)
+    pub newest_only: bool,
+    /// Count local branches not merged into the default branch (`--unmerged-branches`)
+    pub unmerged: bool,
+    /// Count commits the default branch is behind its upstream (`--only-behind`)
+    pub behind: bool,
+    /// Count commits each of two local branches has that the other lacks (`--branch-delta`)
+    pub branch_delta: Option<(String, String)>,
+    /// Keep unpushed commit dates in each commit's recorded timezone (`--author-local`)
+    pub author_local: bool,
+    /// Record the filesystem device of each project (`--external-only`)
+    pub device_id: bool,
+    /// Count a repo whose origin was removed as uploaded if origin's remote-tracking
+    /// reflog survives (`--check-reflog`)
+    pub check_reflog: bool,
+    /// Count tags (`--unreleased`)
+    pub tags: bool,
+    /// Check whether HEAD is contained in some remote-tracking branch (`--truly-unbacked`)
+    pub head_on_remote: bool,
+    /// Test commit messages against this pattern (`--message-matches`)
+    pub message_pattern: Option<regex::Regex>,
+    /// Count every matching commit rather than stopping at the first
+    /// (`--only-matching-commits`)
+    pub count_message_matches: bool,
+    /// Count distinct author emails (`--show-contributors`)
+    pub contributors: bool,
+    /// Commits per repo whose messages are tested or authors counted (`--max-commits`)
+    pub max_commits: Option<usize>,
+    /// Note whether each repo has a commit-graph (`--use-commit-graph`)
+    pub commit_graph: bool,
+    /// Check whether the HEAD commit is signed (`--show-signed`)
+    pub signed: bool,
+    /// Look for lock files left by interrupted git operations (`--show-locks`)
+    pub locks: bool,
+    /// Look for custom git hooks and a `.mailmap` (`--show-hooks`)
+    pub hooks: bool,
+    /// Classify uploaded repos as original, fork or mirror (`--classify`)
+    pub classify: bool,
+    /// Read the per-repo `user.name` and `user.email` (`--show-identity`)
+    pub identity: bool,
+    /// Identify repos by their root commit (`--repo-id`)
+    pub repo_id: bool,
+    /// List linked worktrees (`--show-worktrees`)
+    pub worktrees: bool,
+    /// List every remote with its URL (`--json-remotes`)
+    pub remotes: bool,
+    /// Look up repos with a GitHub origin through the API (`--enrich-github`)
+    pub github: Option<std::sync::Arc<GithubClient>>,
+    /// Connect to each origin, giving up after this long (`--validate-origin`)
+    pub validate_origin: Option<std::time::Duration>,
+}
+
+impl ExtractOptions {
+    /// Identifies the settings a record was extracted under, so `--since-last-scan` only
+    /// reuses records from a scan with the same ones. The GitHub client counts only as
+    /// present or not, keeping its token out of the state file.
+    pub fn fingerprint(&self) -> String {
+        let github = self.github.is_some();
+        let rest = ExtractOptions {
+            github: None,
+            ..self.clone()
+        };
+        format!("{rest:?} github={github}")
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectMetadata {
     pub path: String,
@@ -23,13 +124,20 @@ pub struct ProjectMetadata {
     pub is_git: bool,
     pub is_worktree: bool,
     pub has_remote: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_branches: Option<u32>,
     pub origin_url: Option<String>,
     pub is_on_github: bool,
-    pub unpushed_count: u32,
+    pub trusted_remote: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unpushed_count: Option<u32>,
     pub oldest_unpushed: Option<String>,
     pub newest_unpushed: Option<String>,
     pub branches_with_unpushed: Vec<String>,
-    pub total_commits: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_commits: Option<u32>,
+    pub newest_commit: Option<String>,
+    pub newest_committer: Option<String>,
     pub primary_language: Option<String>,
     pub languages: Vec<LanguageStat>,
     pub has_readme: bool,
@@ -39,6 +147,91 @@ pub struct ProjectMetadata {
     pub last_modified: Option<String>,
     pub repostatus_state: String,
     pub repostatus_age_days: Option<u32>,
+    pub head_detached: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_default: Option<String>,
+    pub interrupted_state: Option<String>,
+    pub is_shallow: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dominant_extension: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_dirty: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub healthy: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub worktree_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unmerged_branches: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub behind: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch_ahead: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch_behind: Option<u32>,
+    #[serde(default)]
+    pub uses_lfs: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ci: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previously_uploaded: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head_commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head_signed: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mailmap: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub classification: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub worktrees: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remotes: Option<Vec<Remote>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github_description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github_topics: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub github_visibility: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub head_on_remote: Option<bool>,
+    /// Commits whose message matched `--message-matches`; at most 1 unless counting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matching_commits: Option<u32>,
+    /// Distinct author emails in the history, or in its newest `--max-commits` commits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contributors: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_commit_graph: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin_reachable: Option<bool>,
+    /// Newest change to the git dir, for `--since-last-scan` (see [`git_dir_mtime`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_dir_mtime: Option<u64>,
+    /// Set by `--create` after the scan, never during extraction
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish_result: Option<String>,
 }
 
 impl Default for ProjectMetadata {
@@ -49,13 +242,17 @@ impl Default for ProjectMetadata {
             is_git: Default::default(),
             is_worktree: Default::default(),
             has_remote: Default::default(),
+            remote_branches: Default::default(),
             origin_url: Default::default(),
             is_on_github: Default::default(),
-            unpushed_count: Default::default(),
+            trusted_remote: Default::default(),
+            unpushed_count: Some(0),
             oldest_unpushed: Default::default(),
             newest_unpushed: Default::default(),
             branches_with_unpushed: Default::default(),
-            total_commits: Default::default(),
+            total_commits: Some(0),
+            newest_commit: Default::default(),
+            newest_committer: Default::default(),
             primary_language: Default::default(),
             languages: Default::default(),
             has_readme: Default::default(),
@@ -65,24 +262,189 @@ impl Default for ProjectMetadata {
             last_modified: Default::default(),
             repostatus_state: "unreviewed".to_string(),
             repostatus_age_days: Default::default(),
+            head_detached: Default::default(),
+            upstream_default: Default::default(),
+            interrupted_state: Default::default(),
+            is_shallow: Default::default(),
+            dominant_extension: Default::default(),
+            pack_size: Default::default(),
+            is_dirty: Default::default(),
+            healthy: Default::default(),
+            health_note: Default::default(),
+            error: Default::default(),
+            git_size: Default::default(),
+            worktree_size: Default::default(),
+            unmerged_branches: Default::default(),
+            behind: Default::default(),
+            branch_ahead: Default::default(),
+            branch_behind: Default::default(),
+            uses_lfs: Default::default(),
+            ci: Default::default(),
+            device_id: Default::default(),
+            previously_uploaded: Default::default(),
+            head_commit: Default::default(),
+            head_signed: Default::default(),
+            locked: Default::default(),
+            hooks: Default::default(),
+            mailmap: Default::default(),
+            classification: Default::default(),
+            user_name: Default::default(),
+            user_email: Default::default(),
+            repo_id: Default::default(),
+            worktrees: Default::default(),
+            remotes: Default::default(),
+            github_description: Default::default(),
+            github_topics: Default::default(),
+            github_visibility: Default::default(),
+            tag_count: Default::default(),
+            head_on_remote: Default::default(),
+            matching_commits: Default::default(),
+            contributors: Default::default(),
+            has_commit_graph: Default::default(),
+            origin_reachable: Default::default(),
+            git_dir_mtime: Default::default(),
+            publish_result: Default::default(),
         }
     }
 }
 
-pub fn extract_metadata(path: &Path, root: &Path) -> anyhow::Result<ProjectMetadata> {
-    let git = extract_git_info(path);
-    let loc = extract_loc(path);
-    let fs = extract_fs_info(path);
-    let status = read_repostatus(path);
+pub fn extract_metadata(
+    path: &Path,
+    root: &Path,
+    opts: &ExtractOptions,
+) -> anyhow::Result<ProjectMetadata> {
+    // Taken before anything is read, so a commit landing mid-extraction shows up as a
+    // change to the next --since-last-scan instead of being cached as already seen
+    let git_dir_mtime = git_dir_mtime(&path.join(".git"));
+    let git = extract_git_info(path, opts);
+    let github = opts
+        .github
+        .as_ref()
+        .zip(git.origin_url.as_deref())
+        .and_then(|(client, url)| client.enrich(url));
+    let (github_description, github_topics, github_visibility) = match github {
+        Some(repo) => (repo.description, Some(repo.topics), repo.visibility),
+        None => (None, None, None),
+    };
 
-    let display_path = crate::simplified_repo_path(path, root);
-    let name = path
+    // Paths and names that aren't valid UTF-8 are reported lossily, with U+FFFD standing
+    // in for each invalid sequence, rather than dropped
+    let display_path = crate::repo_display_path(path, root, opts.name_from);
+    let name = Path::new(&display_path)
         .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("")
-        .to_string();
+        .or_else(|| path.file_name())
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut meta = ProjectMetadata {
+        path: display_path,
+        name,
+        is_git: git.is_git,
+        is_worktree: git.is_worktree,
+        has_remote: git.has_remote,
+        remote_branches: git.remote_branches,
+        origin_url: git.origin_url,
+        is_on_github: git.is_on_github,
+        trusted_remote: git.trusted_remote,
+        unpushed_count: git.unpushed_count,
+        oldest_unpushed: git.oldest_unpushed,
+        newest_unpushed: git.newest_unpushed,
+        branches_with_unpushed: git.branches_with_unpushed,
+        total_commits: git.total_commits,
+        newest_commit: git.newest_commit,
+        newest_committer: git.newest_committer,
+        last_modified: git.last_modified,
+        head_detached: git.head_detached,
+        upstream_default: git.upstream_default,
+        interrupted_state: git.interrupted_state,
+        is_shallow: git.is_shallow,
+        pack_size: git.pack_size,
+        is_dirty: git.is_dirty,
+        healthy: git.healthy,
+        health_note: git.health_note,
+        error: git.error,
+        unmerged_branches: git.unmerged_branches,
+        behind: git.behind,
+        branch_ahead: git.branch_ahead,
+        branch_behind: git.branch_behind,
+        uses_lfs: git.uses_lfs,
+        ci: git.ci,
+        device_id: if opts.device_id {
+            device_id(path)
+        } else {
+            None
+        },
+        previously_uploaded: git.previously_uploaded,
+        head_commit: git.head_commit,
+        head_signed: git.head_signed,
+        locked: git.locked,
+        hooks: git.hooks,
+        mailmap: git.mailmap,
+        classification: git.classification,
+        user_name: git.user_name,
+        user_email: git.user_email,
+        repo_id: git.repo_id,
+        worktrees: git.worktrees,
+        remotes: git.remotes,
+        github_description,
+        github_topics,
+        github_visibility,
+        tag_count: git.tag_count,
+        head_on_remote: git.head_on_remote,
+        matching_commits: git.matching_commits,
+        contributors: git.contributors,
+        has_commit_graph: git.has_commit_graph,
+        origin_reachable: git.origin_reachable,
+        git_dir_mtime,
+        publish_result: None,
+        ..Default::default()
+    };
+    worktree_metrics(path, opts, &mut meta);
+    Ok(meta)
+}
+
+/// Re-read what can change without touching `.git` into `meta`, a record otherwise copied
+/// from an earlier scan (`--since-last-scan`): the work tree's contents, sizes and status.
+pub fn refresh_worktree(path: &Path, opts: &ExtractOptions, meta: &mut ProjectMetadata) {
+    worktree_metrics(path, opts, meta);
+    if opts.dirty {
+        meta.is_dirty = is_dirty(path);
+    }
+}
+
+/// Fill in the metrics read from the work tree rather than the repo.
+fn worktree_metrics(path: &Path, opts: &ExtractOptions, meta: &mut ProjectMetadata) {
+    let loc = if opts.skip_loc {
+        LocInfo::default()
+    } else {
+        extract_loc(path)
+    };
+    meta.primary_language = loc.primary_language;
+    meta.languages = loc.languages;
+
+    let fs = extract_fs_info(path);
+    meta.has_readme = fs.has_readme;
+    meta.has_tests = fs.has_tests;
+    meta.has_ci = fs.has_ci;
+    meta.has_license = fs.has_license;
+
+    meta.dominant_extension = if opts.sample_extensions {
+        dominant_extension(path, EXTENSION_SAMPLE_LIMIT)
+    } else {
+        None
+    };
 
-    let (repostatus_state, repostatus_age_days) = match status {
+    let git_dir = path.join(".git");
+    (meta.git_size, meta.worktree_size) = if opts.sizes && git_dir.is_dir() {
+        (
+            Some(dir_size(&git_dir, None)),
+            Some(dir_size(path, Some(".git"))),
+        )
+    } else {
+        (None, None)
+    };
+
+    (meta.repostatus_state, meta.repostatus_age_days) = match read_repostatus(path) {
         Some(rs) => {
             let state = rs.state.unwrap_or_else(|| "unreviewed".to_string());
             let age = rs.reviewed.as_deref().and_then(|d| {
@@ -97,30 +459,6 @@ pub fn extract_metadata(path: &Path, root: &Path) -> anyhow::Result<ProjectMetad
         }
         None => ("unreviewed".to_string(), None),
     };
-
-    Ok(ProjectMetadata {
-        path: display_path,
-        name,
-        is_git: git.is_git,
-        is_worktree: git.is_worktree,
-        has_remote: git.has_remote,
-        origin_url: git.origin_url,
-        is_on_github: git.is_on_github,
-        unpushed_count: git.unpushed_count,
-        oldest_unpushed: git.oldest_unpushed,
-        newest_unpushed: git.newest_unpushed,
-        branches_with_unpushed: git.branches_with_unpushed,
-        total_commits: git.total_commits,
-        primary_language: loc.primary_language,
-        languages: loc.languages,
-        has_readme: fs.has_readme,
-        has_tests: fs.has_tests,
-        has_ci: fs.has_ci,
-        has_license: fs.has_license,
-        last_modified: git.last_modified,
-        repostatus_state,
-        repostatus_age_days,
-    })
 }
 
 #[cfg(test)]
@@ -134,7 +472,7 @@ mod tests {
             name: "bar".to_string(),
             is_git: true,
             repostatus_state: "unreviewed".to_string(),
-            total_commits: 5,
+            total_commits: Some(5),
             ..Default::default()
         };
         let json = serde_json::to_string(&m).unwrap();
@@ -143,6 +481,20 @@ mod tests {
         assert!(json.contains("\"repostatus_state\":\"unreviewed\""));
     }
 
+    #[test]
+    fn test_records_without_uses_lfs_still_load() {
+        let mut json = serde_json::to_value(ProjectMetadata::default()).unwrap();
+        json.as_object_mut().unwrap().remove("uses_lfs").unwrap();
+        let m: ProjectMetadata = serde_json::from_value(json).unwrap();
+        assert!(!m.uses_lfs);
+    }
+
+    #[test]
+    fn test_head_commit_is_omitted_when_unknown() {
+        let json = serde_json::to_value(ProjectMetadata::default()).unwrap();
+        assert!(json.get("head_commit").is_none(), "{json}");
+    }
+
     #[test]
     fn test_default_repostatus_state_is_unreviewed() {
         let m = ProjectMetadata::default();
@@ -156,10 +508,10 @@ mod tests {
         std::fs::create_dir(&project).unwrap();
         std::fs::write(project.join("main.py"), "print('hello')\n").unwrap();
 
-        let meta = extract_metadata(&project, root.path()).unwrap();
+        let meta = extract_metadata(&project, root.path(), &ExtractOptions::default()).unwrap();
         assert_eq!(meta.name, "myproj");
         assert!(!meta.is_git);
-        assert_eq!(meta.total_commits, 0);
+        assert_eq!(meta.total_commits, Some(0));
         assert_eq!(meta.repostatus_state, "unreviewed");
         assert!(
             meta.primary_language.as_deref() == Some("Python"),
@@ -1,57 +1,1013 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
 use comfy_table::{Table, presets::NOTHING};
 
 use crate::metadata::ProjectMetadata;
 
-pub fn print_table(projects: &[ProjectMetadata]) {
-    let mut table = Table::new();
-    table.load_preset(NOTHING);
-    table.set_header(vec![
-        "PATH", "ORIGIN", "LANG", "LOC", "COMMITS", "UNPUSHED", "STATUS",
-    ]);
-    for p in projects {
-        let total_loc: u64 = p.languages.iter().map(|l| l.code).sum();
-        table.add_row(vec![
-            p.path.clone(),
-            p.origin_url.clone().unwrap_or_default(),
-            p.primary_language.clone().unwrap_or_default(),
-            total_loc.to_string(),
-            p.total_commits.to_string(),
-            p.unpushed_count.to_string(),
-            p.repostatus_state.clone(),
-        ]);
-    }
-    println!("{table}");
-}
-
-pub fn print_json(projects: &[ProjectMetadata]) {
-    match serde_json::to_string_pretty(projects) {
-        Ok(json) => println!("{json}"),
-        Err(e) => eprintln!("JSON serialization error: {e}"),
-    }
-}
-
-pub fn print_csv(projects: &[ProjectMetadata]) {
-    println!("repository,oldest,newest,count");
-    for p in projects {
-        let fmt = |iso: &Option<String>| {
-            iso.as_deref()
-                .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
-                .map(|dt| {
-                    let local: chrono::DateTime<chrono::Local> = dt.into();
-                    local.format("%y-%m-%d").to_string()
-                })
-                .unwrap_or_default()
+/// One column of table or CSV output. Each format has a fixed base set; flags append
+/// optional columns after it, and the header is always derived from the same list as the
+/// rows. JSON output always carries every populated field, so it ignores columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    // CSV base columns (gitfinder compatible)
+    Repository,
+    Oldest,
+    Newest,
+    Count,
+    // Table base columns
+    Path,
+    Origin,
+    Lang,
+    Loc,
+    Commits,
+    Unpushed,
+    Status,
+    // Optional columns
+    UpstreamDefault,
+    InterruptedState,
+    Language,
+    PackSize,
+    Dirty,
+    Healthy,
+    HealthNote,
+    RemoteBranches,
+    GitSize,
+    WorktreeSize,
+    UnmergedBranches,
+    Behind,
+    BranchAhead,
+    BranchBehind,
+    Lfs,
+    Head,
+    HeadFull,
+    Created,
+    MatchingCommits,
+    Contributors,
+    OriginReachable,
+    Ci,
+    Signed,
+    Locked,
+    Hooks,
+    Mailmap,
+    Classification,
+    UserName,
+    UserEmail,
+    Description,
+    Visibility,
+    RepoId,
+    Worktrees,
+}
+
+impl Column {
+    /// Every column, in declaration order: the registry behind `--describe-columns`.
+    pub const ALL: &[Column] = &[
+        Column::Repository,
+        Column::Oldest,
+        Column::Newest,
+        Column::Count,
+        Column::Path,
+        Column::Origin,
+        Column::Lang,
+        Column::Loc,
+        Column::Commits,
+        Column::Unpushed,
+        Column::Status,
+        Column::UpstreamDefault,
+        Column::InterruptedState,
+        Column::Language,
+        Column::PackSize,
+        Column::Dirty,
+        Column::Healthy,
+        Column::HealthNote,
+        Column::RemoteBranches,
+        Column::GitSize,
+        Column::WorktreeSize,
+        Column::UnmergedBranches,
+        Column::Behind,
+        Column::BranchAhead,
+        Column::BranchBehind,
+        Column::Lfs,
+        Column::Head,
+        Column::HeadFull,
+        Column::Created,
+        Column::MatchingCommits,
+        Column::Contributors,
+        Column::OriginReachable,
+        Column::Ci,
+        Column::Signed,
+        Column::Locked,
+        Column::Hooks,
+        Column::Mailmap,
+        Column::Classification,
+        Column::UserName,
+        Column::UserEmail,
+        Column::Description,
+        Column::Visibility,
+        Column::RepoId,
+        Column::Worktrees,
+    ];
+
+    fn header(self) -> &'static str {
+        match self {
+            Column::Repository => "repository",
+            Column::Oldest => "oldest",
+            Column::Newest => "newest",
+            Column::Count => "count",
+            Column::Path => "path",
+            Column::Origin => "origin",
+            Column::Lang => "lang",
+            Column::Loc => "loc",
+            Column::Commits => "commits",
+            Column::Unpushed => "unpushed",
+            Column::Status => "status",
+            Column::UpstreamDefault => "upstream_default",
+            Column::InterruptedState => "state",
+            Column::Language => "language",
+            Column::PackSize => "pack_size",
+            Column::Dirty => "dirty",
+            Column::Healthy => "healthy",
+            Column::HealthNote => "health_note",
+            Column::RemoteBranches => "remote_branches",
+            Column::GitSize => "git_size",
+            Column::WorktreeSize => "worktree_size",
+            Column::UnmergedBranches => "unmerged_branches",
+            Column::Behind => "behind",
+            Column::BranchAhead => "branch_ahead",
+            Column::BranchBehind => "branch_behind",
+            Column::Lfs => "lfs",
+            Column::Head | Column::HeadFull => "head",
+            Column::Created => "created",
+            Column::MatchingCommits => "matching_commits",
+            Column::Contributors => "contributors",
+            Column::OriginReachable => "origin_reachable",
+            Column::Ci => "ci",
+            Column::Signed => "signed",
+            Column::Locked => "locked",
+            Column::Hooks => "hooks",
+            Column::Mailmap => "mailmap",
+            Column::Classification => "classification",
+            Column::UserName => "user_name",
+            Column::UserEmail => "user_email",
+            Column::Description => "description",
+            Column::Visibility => "visibility",
+            Column::RepoId => "repo_id",
+            Column::Worktrees => "worktrees",
+        }
+    }
+
+    /// Value type, as a JSON Schema type name; dates are `YY-MM-DD` strings.
+    fn value_type(self) -> &'static str {
+        match self {
+            Column::Count
+            | Column::Loc
+            | Column::Commits
+            | Column::Unpushed
+            | Column::PackSize
+            | Column::RemoteBranches
+            | Column::GitSize
+            | Column::WorktreeSize
+            | Column::UnmergedBranches
+            | Column::Behind
+            | Column::BranchAhead
+            | Column::BranchBehind
+            | Column::MatchingCommits
+            | Column::Contributors => "integer",
+            Column::Dirty
+            | Column::Healthy
+            | Column::Lfs
+            | Column::OriginReachable
+            | Column::Signed
+            | Column::Locked
+            | Column::Hooks
+            | Column::Mailmap => "boolean",
+            Column::Oldest | Column::Newest => "date",
+            _ => "string",
+        }
+    }
+
+    /// The flag that adds this column, or `None` for base columns.
+    fn flag(self) -> Option<&'static str> {
+        match self {
+            Column::UpstreamDefault => Some("--show-upstream-default"),
+            Column::InterruptedState => Some("--only-interrupted"),
+            Column::Language => Some("--show-language"),
+            Column::PackSize => Some("--show-pack-size"),
+            Column::Dirty => Some("--show-dirty"),
+            Column::Healthy | Column::HealthNote => Some("--verify"),
+            Column::RemoteBranches => Some("--show-remote-branches"),
+            Column::GitSize | Column::WorktreeSize => Some("--gc-candidates"),
+            Column::UnmergedBranches => Some("--unmerged-branches"),
+            Column::Behind => Some("--only-behind"),
+            Column::BranchAhead | Column::BranchBehind => Some("--branch-delta"),
+            Column::Lfs => Some("--show-lfs"),
+            Column::Head => Some("--show-head"),
+            Column::HeadFull => Some("--show-head --full-hash"),
+            Column::Created => Some("--create"),
+            Column::MatchingCommits => Some("--only-matching-commits"),
+            Column::Contributors => Some("--show-contributors"),
+            Column::OriginReachable => Some("--validate-origin"),
+            Column::Ci => Some("--show-ci"),
+            Column::Signed => Some("--show-signed"),
+            Column::Locked => Some("--show-locks"),
+            Column::Hooks | Column::Mailmap => Some("--show-hooks"),
+            Column::Classification => Some("--classify"),
+            Column::UserName | Column::UserEmail => Some("--show-identity"),
+            Column::Description | Column::Visibility => Some("--enrich-github"),
+            Column::RepoId => Some("--repo-id"),
+            Column::Worktrees => Some("--show-worktrees"),
+            _ => None,
+        }
+    }
+
+    /// Output formats that show this column.
+    fn formats(self) -> &'static [&'static str] {
+        if CSV_COLUMNS.contains(&self) {
+            &["csv"]
+        } else if TABLE_COLUMNS.contains(&self) {
+            &["table"]
+        } else {
+            &["table", "csv"]
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Column::Repository | Column::Path => "Project path relative to the scan root",
+            Column::Oldest => "Date of the oldest unpushed commit",
+            Column::Newest => "Date of the newest unpushed commit",
+            Column::Count | Column::Unpushed => {
+                "Commits not on any remote; suffixed with + for shallow clones"
+            }
+            Column::Origin => "URL of the origin remote",
+            Column::Lang => "Primary language by lines of code",
+            Column::Loc => "Lines of code, excluding comments and blanks",
+            Column::Commits => "Commits reachable from local branches; + for shallow clones",
+            Column::Status => "repostatus triage state",
+            Column::UpstreamDefault => "Default branch of origin",
+            Column::InterruptedState => "Operation the repo is stuck in, e.g. merge or rebase",
+            Column::Language => "Most common file extension (sampled)",
+            Column::PackSize => "Bytes in .git/objects/pack/*.pack",
+            Column::Dirty => "yes if the work tree has uncommitted or untracked changes",
+            Column::Healthy => "yes if the integrity check passed",
+            Column::HealthNote => "First problem found by the integrity check",
+            Column::RemoteBranches => "Number of remote-tracking branches",
+            Column::GitSize => "Bytes under .git",
+            Column::WorktreeSize => "Bytes in the working tree, excluding .git",
+            Column::UnmergedBranches => "Local branches not merged into the default branch",
+            Column::Behind => "Commits the default branch lacks from its fetched upstream",
+            Column::BranchAhead => "Commits on the first --branch-delta branch the second lacks",
+            Column::BranchBehind => "Commits on the second --branch-delta branch the first lacks",
+            Column::Lfs => "yes if the repo uses Git LFS",
+            Column::Head => "Abbreviated HEAD commit hash; empty for unborn repos",
+            Column::HeadFull => "Full HEAD commit hash; empty for unborn repos",
+            Column::MatchingCommits => "Commits whose message matches --message-matches",
+            Column::Contributors => "Distinct author emails in the history",
+            Column::OriginReachable => "yes if origin answered a ref listing in time",
+            Column::Ci => "CI system configured in the working tree (github-actions, ...)",
+            Column::Signed => "yes if the HEAD commit carries a GPG or SSH signature",
+            Column::Locked => "yes if .git holds a lock file left by an interrupted operation",
+            Column::Hooks => "yes if the hooks directory holds hooks other than the stock samples",
+            Column::Mailmap => "yes if the work tree has a .mailmap",
+            Column::Classification => "original, fork (upstream differs from origin) or mirror",
+            Column::UserName => "user.name set in the repo's own config",
+            Column::UserEmail => "user.email set in the repo's own config",
+            Column::Description => "Repo description on GitHub",
+            Column::Visibility => "public, private or internal, per GitHub",
+            Column::RepoId => "SHA of the root commit; the same in every clone on every host",
+            Column::Worktrees => "paths of linked worktrees, separated by `;`",
+            Column::Created => {
+                "Outcome of publishing to GitHub: created, failed: <reason>, or would create"
+            }
+        }
+    }
+
+    fn value(self, p: &ProjectMetadata) -> String {
+        match self {
+            Column::Repository | Column::Path => p.path.clone(),
+            Column::Oldest => csv_date(&p.oldest_unpushed),
+            Column::Newest => csv_date(&p.newest_unpushed),
+            Column::Count | Column::Unpushed => commit_count(p.unpushed_count, p),
+            Column::Origin => p.origin_url.clone().unwrap_or_default(),
+            Column::Lang => p.primary_language.clone().unwrap_or_default(),
+            Column::Loc => p.languages.iter().map(|l| l.code).sum::<u64>().to_string(),
+            Column::Commits => commit_count(p.total_commits, p),
+            Column::Status => p.repostatus_state.clone(),
+            Column::UpstreamDefault => p.upstream_default.clone().unwrap_or_default(),
+            Column::InterruptedState => p.interrupted_state.clone().unwrap_or_default(),
+            Column::Language => p.dominant_extension.clone().unwrap_or_default(),
+            Column::PackSize => p.pack_size.map(|n| n.to_string()).unwrap_or_default(),
+            Column::Dirty => yes_no(p.is_dirty),
+            Column::Healthy => yes_no(p.healthy),
+            Column::HealthNote => p.health_note.clone().unwrap_or_default(),
+            Column::Lfs => yes_no(p.is_git.then_some(p.uses_lfs)),
+            Column::RemoteBranches => p.remote_branches.map(|n| n.to_string()).unwrap_or_default(),
+            Column::GitSize => p.git_size.map(|n| n.to_string()).unwrap_or_default(),
+            Column::WorktreeSize => p.worktree_size.map(|n| n.to_string()).unwrap_or_default(),
+            Column::UnmergedBranches => p
+                .unmerged_branches
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+            Column::Behind => p.behind.map(|n| n.to_string()).unwrap_or_default(),
+            Column::BranchAhead => p.branch_ahead.map(|n| n.to_string()).unwrap_or_default(),
+            Column::BranchBehind => p.branch_behind.map(|n| n.to_string()).unwrap_or_default(),
+            Column::Head => p
+                .head_commit
+                .as_deref()
+                .map(|h| h[..h.len().min(SHORT_HASH_LEN)].to_string())
+                .unwrap_or_default(),
+            Column::HeadFull => p.head_commit.clone().unwrap_or_default(),
+            Column::Created => p.publish_result.clone().unwrap_or_default(),
+            Column::OriginReachable => yes_no(p.origin_reachable),
+            Column::Ci => p.ci.clone().unwrap_or_default(),
+            Column::Signed => yes_no(p.head_signed),
+            Column::Locked => yes_no(p.locked),
+            Column::Hooks => yes_no(p.hooks),
+            Column::Mailmap => yes_no(p.mailmap),
+            Column::Classification => p.classification.clone().unwrap_or_default(),
+            Column::UserName => p.user_name.clone().unwrap_or_default(),
+            Column::UserEmail => p.user_email.clone().unwrap_or_default(),
+            Column::Description => p.github_description.clone().unwrap_or_default(),
+            Column::Visibility => p.github_visibility.clone().unwrap_or_default(),
+            Column::RepoId => p.repo_id.clone().unwrap_or_default(),
+            Column::Worktrees => p
+                .worktrees
+                .as_ref()
+                .map(|w| w.join(";"))
+                .unwrap_or_default(),
+            Column::MatchingCommits => p
+                .matching_commits
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+            Column::Contributors => p.contributors.map(|n| n.to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+/// Rendering of scan results.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned, human-readable table (the default)
+    Table,
+    /// A single JSON array, optionally indented
+    Json { pretty: bool },
+    /// One compact JSON object per line
+    Jsonl,
+    /// Backward-compatible gitfinder CSV; `delimiter` other than `,` gives TSV and friends
+    Csv { delimiter: char },
+    /// Bash script that publishes every git repo without an `origin` to GitHub under `user`,
+    /// skipping repos known to have been uploaded before (`--check-reflog`).
+    /// `root` is the scan root that record paths are relative to.
+    Script { user: String, root: PathBuf },
+    /// Just the number of matching projects
+    Count,
+    /// Indented tree mirroring the directory hierarchy, commit counts at the leaves.
+    /// `ascii` swaps box-drawing characters for plain `|`, `` ` `` and `-`.
+    Tree { ascii: bool },
+    /// Columnar Parquet file for analytics tools such as DuckDB
+    Parquet,
+    /// GitHub-flavored Markdown table with the table columns
+    Markdown,
+    /// Self-contained HTML page with the table columns; clicking a header sorts by it
+    Html,
+    /// Shell variable assignments for `eval`: one line per record setting
+    /// `REPO_<n>_<COLUMN>` for the table columns, then `REPO_COUNT`
+    Env,
+    /// TOML document with a `[[repo]]` table per record, holding the CSV columns
+    Toml,
+}
+
+/// Hex digits shown by the `head` column without `--full-hash`, as in `git log --oneline`.
+const SHORT_HASH_LEN: usize = 7;
+
+const TABLE_COLUMNS: &[Column] = &[
+    Column::Path,
+    Column::Origin,
+    Column::Lang,
+    Column::Loc,
+    Column::Commits,
+    Column::Unpushed,
+    Column::Status,
+];
+const CSV_COLUMNS: &[Column] = &[
+    Column::Repository,
+    Column::Oldest,
+    Column::Newest,
+    Column::Count,
+];
+
+/// Writes scan results in one [`OutputFormat`].
+///
+/// CSV, JSON Lines and env are streamed as records arrive; the table, tree, Markdown, HTML,
+/// TOML, JSON array and Parquet need every record before they can be rendered, so those are buffered until
+/// [`TableWriter::finish`].
+pub struct TableWriter<W: Write> {
+    out: W,
+    format: OutputFormat,
+    extra: Vec<Column>,
+    buffered: Vec<ProjectMetadata>,
+    header_written: bool,
+    count: usize,
+    group_depth: Option<usize>,
+    selected: Option<Vec<Column>>,
+}
+
+impl<W: Write> TableWriter<W> {
+    /// `extra` columns are appended after the format's base columns.
+    pub fn new(out: W, format: OutputFormat, extra: Vec<Column>) -> Self {
+        TableWriter {
+            out,
+            format,
+            extra,
+            buffered: Vec::new(),
+            header_written: false,
+            count: 0,
+            group_depth: None,
+            selected: None,
+        }
+    }
+
+    /// Group table rows under headings made of the first `depth` path components
+    /// (`--group`, `--group-depth`). Other formats are unaffected.
+    pub fn group_by(mut self, depth: usize) -> Self {
+        self.group_depth = Some(depth);
+        self
+    }
+
+    /// Show exactly `columns`, in this order, instead of the format's base set plus extras
+    /// (`--columns`).
+    pub fn with_columns(mut self, columns: Vec<Column>) -> Self {
+        self.selected = Some(columns);
+        self
+    }
+
+    pub fn write(&mut self, record: ProjectMetadata) -> io::Result<()> {
+        self.count += 1;
+        match self.format {
+            OutputFormat::Csv { .. } => {
+                self.write_csv_header()?;
+                let row = self.csv_row(&record);
+                writeln!(self.out, "{row}")
+            }
+            OutputFormat::Jsonl => {
+                serde_json::to_writer(&mut self.out, &record)?;
+                writeln!(self.out)
+            }
+            OutputFormat::Script { .. } => self.write_script_entry(&record),
+            OutputFormat::Env => {
+                let line = self.env_line(self.count - 1, &record);
+                writeln!(self.out, "{line}")
+            }
+            OutputFormat::Count => Ok(()),
+            OutputFormat::Table
+            | OutputFormat::Json { .. }
+            | OutputFormat::Tree { .. }
+            | OutputFormat::Parquet
+            | OutputFormat::Markdown
+            | OutputFormat::Html
+            | OutputFormat::Toml => {
+                self.buffered.push(record);
+                Ok(())
+            }
+        }
+    }
+
+    /// Render anything buffered, flush, and hand back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        match self.format {
+            OutputFormat::Csv { .. } => self.write_csv_header()?,
+            OutputFormat::Jsonl => {}
+            OutputFormat::Script { .. } => self.write_script_header()?,
+            OutputFormat::Count => writeln!(self.out, "{}", self.count)?,
+            OutputFormat::Env => writeln!(self.out, "REPO_COUNT={}", self.count)?,
+            OutputFormat::Json { pretty } => {
+                if pretty {
+                    serde_json::to_writer_pretty(&mut self.out, &self.buffered)?;
+                } else {
+                    serde_json::to_writer(&mut self.out, &self.buffered)?;
+                }
+                writeln!(self.out)?;
+            }
+            OutputFormat::Table => {
+                let table = self.table();
+                writeln!(self.out, "{table}")?;
+            }
+            OutputFormat::Tree { ascii } => {
+                let tree = render_tree(&self.buffered, ascii);
+                write!(self.out, "{tree}")?;
+            }
+            OutputFormat::Parquet => crate::columnar::write_parquet(&self.buffered, &mut self.out)?,
+            OutputFormat::Markdown => {
+                let markdown = self.markdown();
+                write!(self.out, "{markdown}")?;
+            }
+            OutputFormat::Html => {
+                let html = self.html();
+                write!(self.out, "{html}")?;
+            }
+            OutputFormat::Toml => {
+                let toml = self.toml().map_err(io::Error::other)?;
+                write!(self.out, "{toml}")?;
+            }
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_csv_header(&mut self) -> io::Result<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        self.header_written = true;
+        let header: Vec<String> = self
+            .columns()
+            .iter()
+            .map(|c| c.header().to_string())
+            .collect();
+        let line = self.delimited(&header);
+        writeln!(self.out, "{line}")
+    }
+
+    /// Join CSV fields with the format's delimiter, quoting as needed.
+    fn delimited(&self, fields: &[String]) -> String {
+        let delimiter = match self.format {
+            OutputFormat::Csv { delimiter } => delimiter,
+            _ => ',',
         };
-        println!(
-            "{},{},{},{}",
-            p.path,
-            fmt(&p.oldest_unpushed),
-            fmt(&p.newest_unpushed),
-            p.unpushed_count,
-        );
+        fields
+            .iter()
+            .map(|f| csv_field(f, delimiter))
+            .collect::<Vec<_>>()
+            .join(&delimiter.to_string())
+    }
+
+    /// Active columns for table and CSV output: the format's base set, then the extras,
+    /// unless an explicit selection replaces them.
+    fn columns(&self) -> Vec<Column> {
+        if let Some(selected) = &self.selected {
+            return selected.clone();
+        }
+        let base = match self.format {
+            OutputFormat::Csv { .. } | OutputFormat::Toml => CSV_COLUMNS,
+            _ => TABLE_COLUMNS,
+        };
+        base.iter().chain(&self.extra).copied().collect()
+    }
+
+    /// `REPO_<index>_<COLUMN>='value'; ...` for one record, every value shell-quoted.
+    fn env_line(&self, index: usize, p: &ProjectMetadata) -> String {
+        self.columns()
+            .iter()
+            .map(|c| {
+                let name = c.header().to_ascii_uppercase();
+                format!("REPO_{index}_{name}={}", shell_quote(&c.value(p)))
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    fn write_script_header(&mut self) -> io::Result<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        self.header_written = true;
+        writeln!(self.out, "#!/usr/bin/env bash")?;
+        writeln!(
+            self.out,
+            "# Generated by lsproj: publish local-only repos to GitHub"
+        )?;
+        writeln!(self.out, "set -euo pipefail")
+    }
+
+    /// Create, wire up and push one repo. Repos that already have an origin are skipped.
+    fn write_script_entry(&mut self, p: &ProjectMetadata) -> io::Result<()> {
+        self.write_script_header()?;
+        let OutputFormat::Script { user, root } = &self.format else {
+            return Ok(());
+        };
+        if !crate::publish::needs_publishing(p) {
+            return Ok(());
+        }
+        let dir = root.join(&p.path).display().to_string();
+        let slug = format!("{user}/{}", github_repo_name(&p.name));
+        writeln!(self.out)?;
+        writeln!(self.out, "# {}", p.path.replace('\n', " "))?;
+        writeln!(self.out, "gh repo create {} --private", shell_quote(&slug))?;
+        writeln!(
+            self.out,
+            "git -C {} remote add origin {}",
+            shell_quote(&dir),
+            shell_quote(&format!("git@github.com:{slug}.git"))
+        )?;
+        writeln!(
+            self.out,
+            "git -C {} push -u origin --all",
+            shell_quote(&dir)
+        )
+    }
+
+    fn csv_row(&self, p: &ProjectMetadata) -> String {
+        let row: Vec<String> = self.columns().iter().map(|c| c.value(p)).collect();
+        self.delimited(&row)
+    }
+
+    /// The `--format toml` document, keyed by column header. Integer and boolean columns
+    /// keep their types; empty values are left out, as TOML has no null.
+    fn toml(&self) -> Result<String, toml::ser::Error> {
+        let columns = self.columns();
+        let repos = self
+            .buffered
+            .iter()
+            .map(|p| {
+                let table: toml::Table = columns
+                    .iter()
+                    .map(|c| (c.header().to_string(), toml_value(*c, c.value(p))))
+                    .filter(|(_, value)| value.as_str() != Some(""))
+                    .collect();
+                toml::Value::Table(table)
+            })
+            .collect();
+        let mut document = toml::Table::new();
+        document.insert("repo".to_string(), toml::Value::Array(repos));
+        toml::to_string(&document)
+    }
+
+    fn markdown(&self) -> String {
+        let columns = self.columns();
+        let row = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+        let mut out = row(columns.iter().map(|c| c.header().to_string()).collect());
+        out.push_str(&row(columns.iter().map(|c| markdown_align(*c)).collect()));
+        for p in &self.buffered {
+            out.push_str(&row(columns
+                .iter()
+                .map(|c| markdown_cell(&c.value(p)))
+                .collect()));
+        }
+        out
+    }
+
+    fn html(&self) -> String {
+        let columns = self.columns();
+        let mut out = HTML_HEAD.to_string();
+        out.push_str("<table>\n<thead>\n<tr>");
+        for c in &columns {
+            out.push_str(&format!(
+                "<th data-type=\"{}\">{}</th>",
+                c.value_type(),
+                html_escape(c.header())
+            ));
+        }
+        out.push_str("</tr>\n</thead>\n<tbody>\n");
+        for p in &self.buffered {
+            out.push_str("<tr>");
+            for c in &columns {
+                out.push_str(&format!("<td>{}</td>", html_escape(&c.value(p))));
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</tbody>\n</table>\n");
+        out.push_str(HTML_TAIL);
+        out
+    }
+
+    fn table(&self) -> Table {
+        let mut table = Table::new();
+        table.load_preset(NOTHING);
+        let columns = self.columns();
+        table.set_header(columns.iter().map(|c| c.header().to_uppercase()));
+        let Some(depth) = self.group_depth else {
+            for p in &self.buffered {
+                table.add_row(columns.iter().map(|c| c.value(p)));
+            }
+            return table;
+        };
+
+        let mut groups: BTreeMap<String, Vec<&ProjectMetadata>> = BTreeMap::new();
+        for p in &self.buffered {
+            groups.entry(group_key(&p.path, depth)).or_default().push(p);
+        }
+        for (key, members) in groups {
+            // Projects above the grouping depth are listed first, without a heading
+            if !key.is_empty() {
+                table.add_row(vec![format!("{key}/")]);
+            }
+            for p in members {
+                let mut row: Vec<String> = columns.iter().map(|c| c.value(p)).collect();
+                if matches!(columns.first(), Some(Column::Path)) && !key.is_empty() {
+                    let rest = p.path.strip_prefix(&key).unwrap_or(&p.path);
+                    row[0] = format!("  {}", rest.trim_start_matches('/'));
+                }
+                table.add_row(row);
+            }
+        }
+        table
+    }
+}
+
+/// File for projects directly under the scan root in [`SplitWriter`] output.
+pub const SPLIT_TOP_FILE: &str = "_top";
+
+/// Writes one file per top-level group into a directory (`--split-by-top`), each through
+/// its own [`TableWriter`] so every file gets its own header.
+pub struct SplitWriter<F> {
+    dir: PathBuf,
+    make: F,
+    writers: BTreeMap<String, TableWriter<BufWriter<File>>>,
+}
+
+impl<F: FnMut(BufWriter<File>) -> TableWriter<BufWriter<File>>> SplitWriter<F> {
+    /// Files go in `dir`, created if missing; `make` configures the writer for each one.
+    pub fn new(dir: &Path, make: F) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(SplitWriter {
+            dir: dir.to_path_buf(),
+            make,
+            writers: BTreeMap::new(),
+        })
+    }
+
+    /// Append `record` to its group's file, creating the file on the group's first record.
+    /// The group comes from `relative`, the project's path under the scan root, since
+    /// `record.path` may already be rebased for display.
+    pub fn write(&mut self, relative: &str, record: ProjectMetadata) -> io::Result<()> {
+        let key = group_key(relative, 1);
+        let writer = match self.writers.entry(key) {
+            std::collections::btree_map::Entry::Occupied(e) => e.into_mut(),
+            std::collections::btree_map::Entry::Vacant(e) => {
+                let stem = if e.key().is_empty() {
+                    SPLIT_TOP_FILE
+                } else {
+                    e.key()
+                };
+                let file = File::create(self.dir.join(format!("{stem}.csv")))?;
+                e.insert((self.make)(BufWriter::new(file)))
+            }
+        };
+        writer.write(record)
+    }
+
+    /// Finish and flush every group's file.
+    pub fn finish(self) -> io::Result<()> {
+        for writer in self.writers.into_values() {
+            writer.finish()?.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// One directory in `--format tree`; `label` is set when the directory is itself a project.
+#[derive(Default)]
+struct TreeNode {
+    label: Option<String>,
+    children: BTreeMap<String, TreeNode>,
+}
+
+/// Rebuild the directory hierarchy from record paths and draw it, rooted at `.`.
+fn render_tree(records: &[ProjectMetadata], ascii: bool) -> String {
+    let mut root = TreeNode::default();
+    for p in records {
+        let node = p
+            .path
+            .split('/')
+            .filter(|c| !c.is_empty())
+            .fold(&mut root, |node, part| {
+                node.children.entry(part.to_string()).or_default()
+            });
+        node.label = Some(tree_label(p));
+    }
+    let mut out = String::from(".");
+    if let Some(label) = &root.label {
+        out.push_str(label);
+    }
+    out.push('\n');
+    draw_children(&root, "", ascii, &mut out);
+    out
+}
+
+fn draw_children(node: &TreeNode, prefix: &str, ascii: bool, out: &mut String) {
+    let (branch, last, pipe) = if ascii {
+        ("|-- ", "`-- ", "|   ")
+    } else {
+        ("├── ", "└── ", "│   ")
+    };
+    let count = node.children.len();
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let is_last = i + 1 == count;
+        out.push_str(prefix);
+        out.push_str(if is_last { last } else { branch });
+        out.push_str(name);
+        if let Some(label) = &child.label {
+            out.push_str(label);
+        }
+        out.push('\n');
+        let indent = if is_last { "    " } else { pipe };
+        draw_children(child, &format!("{prefix}{indent}"), ascii, out);
+    }
+}
+
+/// Annotation after a project's name: its commit count, for git repos.
+fn tree_label(p: &ProjectMetadata) -> String {
+    let (true, Some(total)) = (p.is_git, p.total_commits) else {
+        return String::new();
+    };
+    let n = commit_count(Some(total), p);
+    let noun = if total == 1 { "commit" } else { "commits" };
+    format!(" ({n} {noun})")
+}
+
+/// Group for a project path: its first `depth` components, never including the project's
+/// own name. Projects shallower than that group under their parent (`""` at the top).
+pub fn group_key(path: &str, depth: usize) -> String {
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    let take = depth.min(components.len().saturating_sub(1));
+    components[..take].join("/")
+}
+
+/// Separator cell for a Markdown column: numbers are right-aligned.
+fn markdown_align(column: Column) -> String {
+    match column.value_type() {
+        "integer" => "---:".to_string(),
+        _ => "---".to_string(),
     }
 }
 
+/// A cell as a typed TOML value. Counts from shallow clones (`5+`) stay strings.
+fn toml_value(column: Column, value: String) -> toml::Value {
+    match column.value_type() {
+        "integer" => value
+            .parse()
+            .map_or(toml::Value::String(value), toml::Value::Integer),
+        "boolean" if value == "yes" || value == "no" => toml::Value::Boolean(value == "yes"),
+        _ => toml::Value::String(value),
+    }
+}
+
+/// Escape a value for a Markdown table cell, where `|` ends the cell and newlines the row.
+fn markdown_cell(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('\n', " ")
+}
+
+/// Start of the `--format html` page, up to the table.
+const HTML_HEAD: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>lsproj</title>
+<style>
+body { font-family: sans-serif; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 2px 8px; text-align: left; }
+th { cursor: pointer; background: #f4f4f4; }
+</style>
+</head>
+<body>
+"#;
+
+/// End of the `--format html` page: clicking a header sorts the rows by that column,
+/// numerically for integer columns; clicking again reverses the order.
+const HTML_TAIL: &str = r#"<script>
+document.querySelectorAll("th").forEach((th, col) => th.addEventListener("click", () => {
+  const body = th.closest("table").tBodies[0];
+  const numeric = th.dataset.type === "integer";
+  const dir = th.dataset.dir === "asc" ? -1 : 1;
+  th.dataset.dir = dir === 1 ? "asc" : "desc";
+  const key = row => row.cells[col].textContent;
+  const rows = Array.from(body.rows).sort((a, b) => dir * (numeric
+    ? (parseFloat(key(a)) || 0) - (parseFloat(key(b)) || 0)
+    : key(a).localeCompare(key(b))));
+  rows.forEach(row => body.appendChild(row));
+}));
+</script>
+</body>
+</html>
+"#;
+
+/// Escape text for HTML element content and attribute values.
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Quote a CSV field (RFC 4180) if it contains the delimiter, a quote or a line break.
+fn csv_field(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Parse a column by its header name (`--columns`). Where two columns share a header
+/// (`head`), the first registered wins.
+pub fn parse_column(s: &str) -> Result<Column, String> {
+    Column::ALL
+        .iter()
+        .copied()
+        .find(|c| c.header() == s)
+        .ok_or_else(|| {
+            let mut names: Vec<&str> = Column::ALL.iter().map(|c| c.header()).collect();
+            names.dedup();
+            format!(
+                "unknown column {s:?}; expected one of: {}",
+                names.join(", ")
+            )
+        })
+}
+
+/// Parse a `--delimiter`: a single character, or `\t`/`tab` for TSV.
+/// Used as a clap value parser, hence the `String` error.
+pub fn parse_delimiter(s: &str) -> Result<char, String> {
+    match s {
+        "\\t" | "tab" => return Ok('\t'),
+        "\"" | "\n" | "\r" => return Err(format!("{s:?} cannot be a delimiter")),
+        _ => {}
+    }
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("delimiter must be a single character, got {s:?}")),
+    }
+}
+
+/// Flag columns read `yes`/`no`, or blank when the check didn't run.
+fn yes_no(flag: Option<bool>) -> String {
+    match flag {
+        Some(true) => "yes".to_string(),
+        Some(false) => "no".to_string(),
+        None => String::new(),
+    }
+}
+
+/// Commit counts from a shallow clone are lower bounds; mark them `N+`. Counts skipped by
+/// `--newest-only` are blank.
+fn commit_count(n: Option<u32>, p: &ProjectMetadata) -> String {
+    match n {
+        Some(n) if p.is_shallow => format!("{n}+"),
+        Some(n) => n.to_string(),
+        None => String::new(),
+    }
+}
+
+/// Quote `s` for a POSIX shell: single quotes, with embedded `'` spliced in as `'\''`.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// GitHub repo names allow only ASCII letters, digits, `-`, `_` and `.`; map the rest to `-`.
+pub(crate) fn github_repo_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// CSV dates are local `YY-MM-DD`, as gitfinder printed them. UTC (`Z`) times are shown in
+/// the host's timezone; times carrying an explicit offset (`--author-local`) keep it.
+fn csv_date(iso: &Option<String>) -> String {
+    let Some(iso) = iso.as_deref() else {
+        return String::new();
+    };
+    chrono::DateTime::parse_from_rfc3339(iso)
+        .map(|dt| {
+            if iso.ends_with('Z') {
+                let local: chrono::DateTime<chrono::Local> = dt.into();
+                local.format("%y-%m-%d").to_string()
+            } else {
+                dt.format("%y-%m-%d").to_string()
+            }
+        })
+        .unwrap_or_default()
+}
+
+/// JSON description of every table and CSV column: name, type, flag and formats.
+pub fn describe_columns() -> serde_json::Value {
+    Column::ALL
+        .iter()
+        .map(|c| {
+            serde_json::json!({
+                "name": c.header(),
+                "type": c.value_type(),
+                "flag": c.flag(),
+                "formats": c.formats(),
+                "description": c.description(),
+            })
+        })
+        .collect()
+}
+
 pub fn print_schema() {
     let schema = serde_json::json!({
         "$schema": "http://json-schema.org/draft-07/schema#",
@@ -63,13 +1019,17 @@ pub fn print_schema() {
             "is_git":                  { "type": "boolean" },
             "is_worktree":             { "type": "boolean" },
             "has_remote":              { "type": "boolean" },
+            "remote_branches":         { "type": ["integer", "null"] },
             "origin_url":              { "type": ["string", "null"] },
             "is_on_github":            { "type": "boolean" },
-            "unpushed_count":          { "type": "integer" },
+            "trusted_remote":          { "type": "boolean" },
+            "unpushed_count":          { "type": ["integer", "null"] },
             "oldest_unpushed":         { "type": ["string", "null"] },
             "newest_unpushed":         { "type": ["string", "null"] },
             "branches_with_unpushed":  { "type": "array", "items": { "type": "string" } },
-            "total_commits":           { "type": "integer" },
+            "total_commits":           { "type": ["integer", "null"] },
+            "newest_commit":           { "type": ["string", "null"] },
+            "newest_committer":        { "type": ["string", "null"] },
             "primary_language":        { "type": ["string", "null"] },
             "languages": {
                 "type": "array",
@@ -89,8 +1049,459 @@ pub fn print_schema() {
             "has_license":             { "type": "boolean" },
             "last_modified":           { "type": ["string", "null"] },
             "repostatus_state":        { "type": "string" },
-            "repostatus_age_days":     { "type": ["integer", "null"] }
+            "repostatus_age_days":     { "type": ["integer", "null"] },
+            "head_detached":           { "type": "boolean" },
+            "upstream_default":        { "type": ["string", "null"] },
+            "interrupted_state":       { "type": ["string", "null"] },
+            "is_shallow":              { "type": "boolean" },
+            "dominant_extension":      { "type": ["string", "null"] },
+            "pack_size":               { "type": ["integer", "null"] },
+            "is_dirty":                { "type": ["boolean", "null"] },
+            "healthy":                 { "type": ["boolean", "null"] },
+            "health_note":             { "type": ["string", "null"] },
+            "git_size":                { "type": ["integer", "null"] },
+            "worktree_size":           { "type": ["integer", "null"] },
+            "unmerged_branches":       { "type": ["integer", "null"] },
+            "behind":                  { "type": ["integer", "null"] },
+            "branch_ahead":            { "type": ["integer", "null"] },
+            "branch_behind":           { "type": ["integer", "null"] },
+            "uses_lfs":                { "type": "boolean" },
+            "ci":                      { "type": ["string", "null"] },
+            "device_id":               { "type": ["integer", "null"] },
+            "previously_uploaded":     { "type": ["boolean", "null"] },
+            "head_commit":             { "type": ["string", "null"] },
+            "head_signed":             { "type": ["boolean", "null"] },
+            "locked":                  { "type": ["boolean", "null"] },
+            "hooks":                   { "type": ["boolean", "null"] },
+            "mailmap":                 { "type": ["boolean", "null"] },
+            "classification":          { "type": ["string", "null"] },
+            "user_name":               { "type": ["string", "null"] },
+            "user_email":              { "type": ["string", "null"] },
+            "repo_id":                 { "type": ["string", "null"] },
+            "worktrees":               { "type": ["array", "null"], "items": { "type": "string" } },
+            "github_description":      { "type": ["string", "null"] },
+            "github_topics":           { "type": ["array", "null"], "items": { "type": "string" } },
+            "github_visibility":       { "type": ["string", "null"] },
+            "tag_count":               { "type": ["integer", "null"] },
+            "head_on_remote":          { "type": ["boolean", "null"] },
+            "matching_commits":        { "type": ["integer", "null"] },
+            "contributors":            { "type": ["integer", "null"] },
+            "has_commit_graph":        { "type": ["boolean", "null"] },
+            "origin_reachable":        { "type": ["boolean", "null"] },
+            "git_dir_mtime":           { "type": ["integer", "null"] },
+            "publish_result":          { "type": ["string", "null"] }
         }
     });
     println!("{}", serde_json::to_string_pretty(&schema).unwrap());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<ProjectMetadata> {
+        vec![
+            ProjectMetadata {
+                path: "alpha".to_string(),
+                name: "alpha".to_string(),
+                is_git: true,
+                total_commits: Some(3),
+                unpushed_count: Some(2),
+                oldest_unpushed: Some("2023-11-14T22:13:20Z".to_string()),
+                newest_unpushed: Some("2023-11-16T02:00:00Z".to_string()),
+                upstream_default: Some("main".to_string()),
+                ..Default::default()
+            },
+            ProjectMetadata {
+                path: "tools/beta".to_string(),
+                name: "beta".to_string(),
+                ..Default::default()
+            },
+        ]
+    }
+
+    fn render(format: OutputFormat, extra: Vec<Column>) -> String {
+        let mut writer = TableWriter::new(Vec::new(), format, extra);
+        for r in records() {
+            writer.write(r).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let out = render(
+            OutputFormat::Csv { delimiter: ',' },
+            vec![Column::UpstreamDefault],
+        );
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "repository,oldest,newest,count,upstream_default");
+        assert!(lines[1].starts_with("alpha,"));
+        assert!(lines[1].ends_with(",2,main"));
+        assert_eq!(lines[2], "tools/beta,,,0,");
+    }
+
+    #[test]
+    fn test_render_toml_parses_back() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Repo {
+            repository: String,
+            oldest: Option<String>,
+            newest: Option<String>,
+            count: u32,
+        }
+        #[derive(serde::Deserialize)]
+        struct Document {
+            repo: Vec<Repo>,
+        }
+        let out = render(OutputFormat::Toml, Vec::new());
+        assert!(out.starts_with("[[repo]]\n"), "{out}");
+        let document: Document = toml::from_str(&out).unwrap();
+        assert_eq!(document.repo.len(), 2);
+        assert_eq!(document.repo[0].repository, "alpha");
+        assert_eq!(document.repo[0].count, 2);
+        assert!(document.repo[0].oldest.is_some());
+        assert_eq!(
+            document.repo[1],
+            Repo {
+                repository: "tools/beta".to_string(),
+                oldest: None,
+                newest: None,
+                count: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_render_csv_header_without_records() {
+        let writer = TableWriter::new(Vec::new(), OutputFormat::Csv { delimiter: ',' }, Vec::new());
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(out, "repository,oldest,newest,count\n");
+    }
+
+    #[test]
+    fn test_render_json() {
+        let out = render(OutputFormat::Json { pretty: false }, Vec::new());
+        assert_eq!(out.lines().count(), 1);
+        let json: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[1]["path"], "tools/beta");
+
+        let pretty = render(OutputFormat::Json { pretty: true }, Vec::new());
+        assert!(pretty.contains("\n  {"));
+    }
+
+    #[test]
+    fn test_render_jsonl() {
+        let out = render(OutputFormat::Jsonl, Vec::new());
+        let paths: Vec<String> = out
+            .lines()
+            .map(|l| serde_json::from_str::<ProjectMetadata>(l).unwrap().path)
+            .collect();
+        assert_eq!(paths, vec!["alpha", "tools/beta"]);
+    }
+
+    #[test]
+    fn test_render_table() {
+        let out = render(OutputFormat::Table, vec![Column::UpstreamDefault]);
+        let header = out.lines().next().unwrap();
+        assert!(header.contains("PATH") && header.contains("UPSTREAM_DEFAULT"));
+        assert!(out.contains("alpha") && out.contains("tools/beta"));
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("it's here"), r"'it'\''s here'");
+    }
+
+    #[test]
+    fn test_render_script_skips_repos_with_origin() {
+        let mut records = records();
+        records[1].is_git = true;
+        records[1].origin_url = Some("git@github.com:pete/beta.git".to_string());
+        let format = OutputFormat::Script {
+            user: "pete".to_string(),
+            root: PathBuf::from("/home/pete/projects"),
+        };
+        let mut writer = TableWriter::new(Vec::new(), format, Vec::new());
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(out.starts_with("#!/usr/bin/env bash\n"));
+        assert!(out.contains("set -euo pipefail"));
+        assert!(out.contains("gh repo create 'pete/alpha' --private"));
+        assert!(!out.contains("beta"));
+    }
+
+    #[test]
+    fn test_render_script_skips_previously_uploaded() {
+        let mut records = records();
+        records[0].previously_uploaded = Some(true);
+        let format = OutputFormat::Script {
+            user: "pete".to_string(),
+            root: PathBuf::from("/home/pete/projects"),
+        };
+        let mut writer = TableWriter::new(Vec::new(), format, Vec::new());
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(!out.contains("alpha"));
+    }
+
+    #[test]
+    fn test_render_count() {
+        assert_eq!(render(OutputFormat::Count, Vec::new()), "2\n");
+    }
+
+    #[test]
+    fn test_shallow_counts_marked() {
+        let mut records = records();
+        records[0].is_shallow = true;
+        let mut writer =
+            TableWriter::new(Vec::new(), OutputFormat::Csv { delimiter: ',' }, Vec::new());
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let alpha = out.lines().find(|l| l.starts_with("alpha,")).unwrap();
+        assert!(alpha.ends_with('+'), "got {alpha}");
+        assert!(!out.lines().last().unwrap().ends_with('+'));
+    }
+
+    #[test]
+    fn test_render_tsv_round_trips() {
+        let mut records = records();
+        records[1].path = "odd\tname, \"quoted\"".to_string();
+        let mut writer = TableWriter::new(
+            Vec::new(),
+            OutputFormat::Csv { delimiter: '\t' },
+            vec![Column::UpstreamDefault],
+        );
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        let out = writer.finish().unwrap();
+        assert!(out.starts_with(b"repository\toldest\tnewest\tcount\tupstream_default\n"));
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(b'\t')
+            .from_reader(out.as_slice());
+        let rows: Vec<csv::StringRecord> = reader.records().map(|r| r.unwrap()).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(&rows[0][0], "alpha");
+        assert_eq!(&rows[0][4], "main");
+        assert_eq!(&rows[1][0], "odd\tname, \"quoted\"");
+    }
+
+    #[test]
+    fn test_parse_delimiter() {
+        assert_eq!(parse_delimiter("\\t"), Ok('\t'));
+        assert_eq!(parse_delimiter("tab"), Ok('\t'));
+        assert_eq!(parse_delimiter(";"), Ok(';'));
+        assert!(parse_delimiter(";;").is_err());
+        assert!(parse_delimiter("\"").is_err());
+    }
+
+    #[test]
+    fn test_describe_columns() {
+        let described = describe_columns();
+        let columns = described.as_array().unwrap();
+        assert_eq!(columns.len(), Column::ALL.len());
+        let count = columns.iter().find(|c| c["name"] == "count").unwrap();
+        assert_eq!(count["type"], "integer");
+        assert_eq!(count["formats"], serde_json::json!(["csv"]));
+        assert!(count["flag"].is_null());
+        let dirty = columns.iter().find(|c| c["name"] == "dirty").unwrap();
+        assert_eq!(dirty["flag"], "--show-dirty");
+    }
+
+    #[test]
+    fn test_csv_date_keeps_explicit_offset() {
+        let iso = Some("2023-11-15T00:30:00+14:00".to_string());
+        assert_eq!(csv_date(&iso), "23-11-15");
+        assert_eq!(csv_date(&None), "");
+    }
+
+    #[test]
+    fn test_group_key_depth() {
+        assert_eq!(group_key("a/b/x", 2), "a/b");
+        assert_eq!(group_key("a/b/y", 2), "a/b");
+        assert_eq!(group_key("a/c/z", 2), "a/c");
+        assert_eq!(group_key("a/b/x", 1), "a");
+        assert_eq!(group_key("a/c/z", 1), "a");
+        // Never the project itself
+        assert_eq!(group_key("a/b", 2), "a");
+        assert_eq!(group_key("top", 1), "");
+    }
+
+    #[test]
+    fn test_render_grouped_table() {
+        let mut records = records();
+        records.push(ProjectMetadata {
+            path: "tools/gamma".to_string(),
+            ..Default::default()
+        });
+        let mut writer = TableWriter::new(Vec::new(), OutputFormat::Table, Vec::new()).group_by(1);
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let paths: Vec<&str> = out
+            .lines()
+            .skip(1)
+            .map(|l| l.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(paths, ["alpha", "tools/", "beta", "gamma"]);
+    }
+
+    #[test]
+    fn test_split_writer_one_csv_per_group() {
+        let out = tempfile::TempDir::new().unwrap();
+        let mut writer = SplitWriter::new(out.path(), |file| {
+            TableWriter::new(file, OutputFormat::Csv { delimiter: ',' }, Vec::new())
+        })
+        .unwrap();
+        for path in ["team-a/x", "team-b/y", "team-a/z"] {
+            writer
+                .write(
+                    path,
+                    ProjectMetadata {
+                        path: path.to_string(),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut files: Vec<String> = std::fs::read_dir(out.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["team-a.csv", "team-b.csv"]);
+        let team_a = std::fs::read_to_string(out.path().join("team-a.csv")).unwrap();
+        let lines: Vec<&str> = team_a.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("repository,"));
+        assert!(lines[1].starts_with("team-a/x,"));
+        assert!(lines[2].starts_with("team-a/z,"));
+        let team_b = std::fs::read_to_string(out.path().join("team-b.csv")).unwrap();
+        assert_eq!(team_b.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_render_tree_nests_paths() {
+        let mut writer =
+            TableWriter::new(Vec::new(), OutputFormat::Tree { ascii: false }, Vec::new());
+        for (path, commits) in [("a/b", 4), ("a", 1), ("c", 2)] {
+            writer
+                .write(ProjectMetadata {
+                    path: path.to_string(),
+                    is_git: true,
+                    total_commits: Some(commits),
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(
+            out,
+            ".\n├── a (1 commit)\n│   └── b (4 commits)\n└── c (2 commits)\n"
+        );
+    }
+
+    #[test]
+    fn test_render_tree_ascii() {
+        let out = render(OutputFormat::Tree { ascii: true }, Vec::new());
+        assert_eq!(out, ".\n|-- alpha (3 commits)\n`-- tools\n    `-- beta\n");
+    }
+
+    #[test]
+    fn test_selected_columns_set_order() {
+        let columns = ["count", "repository", "newest"]
+            .iter()
+            .map(|s| parse_column(s).unwrap())
+            .collect();
+        let mut writer =
+            TableWriter::new(Vec::new(), OutputFormat::Csv { delimiter: ',' }, Vec::new())
+                .with_columns(columns);
+        for r in records() {
+            writer.write(r).unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "count,repository,newest");
+        assert_eq!(lines[2], "0,tools/beta,");
+        assert!(parse_column("bogus").unwrap_err().contains("repository"));
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let mut records = records();
+        records[1].path = "tools/be|ta".to_string();
+        let mut writer = TableWriter::new(Vec::new(), OutputFormat::Markdown, Vec::new());
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[0],
+            "| path | origin | lang | loc | commits | unpushed | status |"
+        );
+        assert_eq!(lines[1], "| --- | --- | --- | ---: | ---: | ---: | --- |");
+        assert!(lines[2].starts_with("| alpha |"));
+        assert!(lines[3].starts_with("| tools/be\\|ta |"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_render_env_evals_in_shell() {
+        let mut records = records();
+        records[1].path = "tools/it's $HOME".to_string();
+        let mut writer = TableWriter::new(Vec::new(), OutputFormat::Env, Vec::new());
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert!(
+            out.starts_with("REPO_0_PATH='alpha'; REPO_0_ORIGIN="),
+            "{out}"
+        );
+        assert!(out.ends_with("REPO_COUNT=2\n"), "{out}");
+        let shell = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(r#"eval "$1" && printf '%s|%s|%s' "$REPO_COUNT" "$REPO_0_PATH" "$REPO_1_PATH""#)
+            .arg("sh")
+            .arg(&out)
+            .output()
+            .unwrap();
+        assert!(shell.status.success(), "{shell:?}");
+        assert_eq!(
+            String::from_utf8(shell.stdout).unwrap(),
+            "2|alpha|tools/it's $HOME"
+        );
+    }
+
+    #[test]
+    fn test_render_html_escapes() {
+        let mut records = records();
+        records[1].path = "tools/<beta> & co".to_string();
+        let mut writer = TableWriter::new(Vec::new(), OutputFormat::Html, Vec::new());
+        for r in records {
+            writer.write(r).unwrap();
+        }
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(out.matches("<table>").count(), 1);
+        // One header row plus one per project
+        assert_eq!(out.matches("<tr>").count(), 3);
+        assert!(out.contains("<td>tools/&lt;beta&gt; &amp; co</td>"));
+        assert!(!out.contains("<beta>"));
+        assert!(out.contains("<th data-type=\"integer\">loc</th>"));
+    }
+}
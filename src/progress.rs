@@ -0,0 +1,77 @@
+//! Percent-complete and ETA for a scan measured against a pre-counted total (`--estimate`).
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Directories examined so far out of an estimated total.
+#[derive(Debug)]
+pub struct Progress {
+    total: u64,
+    done: AtomicU64,
+    started: Instant,
+}
+
+impl Progress {
+    pub fn new(total: u64) -> Self {
+        Progress {
+            total,
+            done: AtomicU64::new(0),
+            started: Instant::now(),
+        }
+    }
+
+    pub fn tick(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Fraction complete, capped at 1: the tree may have grown since it was counted.
+    pub fn fraction(&self) -> f64 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        (self.done.load(Ordering::Relaxed) as f64 / self.total as f64).min(1.0)
+    }
+
+    /// Remaining time, extrapolated from the rate so far; `None` before any progress.
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        if fraction == 0.0 {
+            return None;
+        }
+        let elapsed = self.started.elapsed().as_secs_f64();
+        Some(Duration::from_secs_f64(elapsed / fraction - elapsed))
+    }
+
+    /// One status line, e.g. `42% (420/1000 dirs), ETA 12s`.
+    pub fn status(&self) -> String {
+        let done = self.done.load(Ordering::Relaxed).min(self.total);
+        let eta = match self.eta() {
+            Some(eta) => format!("{}s", eta.as_secs()),
+            None => "?".to_string(),
+        };
+        format!(
+            "{:.0}% ({done}/{} dirs), ETA {eta}",
+            self.fraction() * 100.0,
+            self.total
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fraction_and_status() {
+        let progress = Progress::new(4);
+        assert_eq!(progress.eta(), None);
+        progress.tick();
+        assert_eq!(progress.fraction(), 0.25);
+        assert!(progress.status().starts_with("25% (1/4 dirs), ETA "));
+        for _ in 0..5 {
+            progress.tick();
+        }
+        assert_eq!(progress.fraction(), 1.0);
+        assert!(progress.status().starts_with("100% (4/4 dirs)"));
+    }
+}
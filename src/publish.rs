@@ -0,0 +1,167 @@
+//! Publishing local-only repos to GitHub with the `gh` CLI (`--create`).
+//!
+//! This does what `--emit-script` writes out, but runs it directly and records how each
+//! repo fared. Commands go through [`CommandRunner`] so they can be printed (`--dry-run`)
+//! or mocked in tests.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::metadata::ProjectMetadata;
+use crate::output::github_repo_name;
+
+/// Runs one external command; `Err` carries a short description of the failure.
+pub trait CommandRunner {
+    fn run(&self, program: &str, args: &[String]) -> Result<(), String>;
+}
+
+/// Runs commands for real, waiting for each to finish.
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[String]) -> Result<(), String> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| format!("{program}: {e}"))?;
+        if output.status.success() {
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().next().unwrap_or("").trim();
+        Err(format!("{program} failed ({}): {reason}", output.status))
+    }
+}
+
+/// Prints each command, shell-quoted, to stderr instead of running it (`--dry-run`).
+pub struct DryRunner;
+
+impl CommandRunner for DryRunner {
+    fn run(&self, program: &str, args: &[String]) -> Result<(), String> {
+        let quoted: Vec<String> = args.iter().map(|a| crate::output::shell_quote(a)).collect();
+        eprintln!("{program} {}", quoted.join(" "));
+        Ok(())
+    }
+}
+
+/// True for repos `--create` acts on: git repos with no origin that aren't known to have
+/// been uploaded before.
+pub fn needs_publishing(p: &ProjectMetadata) -> bool {
+    p.is_git && p.origin_url.is_none() && p.previously_uploaded != Some(true)
+}
+
+/// The commands that publish `p` (at `root`/`p.path`) as a private repo under `user`:
+/// create it on GitHub, add it as `origin`, and push every branch.
+pub fn publish_commands(
+    p: &ProjectMetadata,
+    root: &Path,
+    user: &str,
+) -> Vec<(String, Vec<String>)> {
+    let dir = root.join(&p.path).display().to_string();
+    let slug = format!("{user}/{}", github_repo_name(&p.name));
+    let git = |args: &[&str]| {
+        let mut full = vec!["-C".to_string(), dir.clone()];
+        full.extend(args.iter().map(|a| a.to_string()));
+        ("git".to_string(), full)
+    };
+    vec![
+        (
+            "gh".to_string(),
+            vec![
+                "repo".to_string(),
+                "create".to_string(),
+                slug.clone(),
+                "--private".to_string(),
+            ],
+        ),
+        git(&[
+            "remote",
+            "add",
+            "origin",
+            &format!("git@github.com:{slug}.git"),
+        ]),
+        git(&["push", "-u", "origin", "--all"]),
+    ]
+}
+
+/// Publish `p`, stopping at the first failing command. Returns the result column value:
+/// `created`, or `failed: <reason>`.
+pub fn publish(p: &ProjectMetadata, root: &Path, user: &str, runner: &dyn CommandRunner) -> String {
+    for (program, args) in publish_commands(p, root, user) {
+        if let Err(reason) = runner.run(&program, &args) {
+            return format!("failed: {reason}");
+        }
+    }
+    "created".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    /// Records invocations; fails any whose program is `fail_on`.
+    #[derive(Default)]
+    struct MockRunner {
+        calls: RefCell<Vec<String>>,
+        fail_on: Option<&'static str>,
+    }
+
+    impl CommandRunner for MockRunner {
+        fn run(&self, program: &str, args: &[String]) -> Result<(), String> {
+            self.calls
+                .borrow_mut()
+                .push(format!("{program} {}", args.join(" ")));
+            match self.fail_on {
+                Some(p) if p == program => Err("boom".to_string()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    fn sample() -> ProjectMetadata {
+        ProjectMetadata {
+            path: "tools/my tool".to_string(),
+            name: "my tool".to_string(),
+            is_git: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_publish_invokes_gh_then_git() {
+        let runner = MockRunner::default();
+        let result = publish(&sample(), Path::new("/home/pete"), "pete", &runner);
+        assert_eq!(result, "created");
+        assert_eq!(
+            *runner.calls.borrow(),
+            [
+                "gh repo create pete/my-tool --private",
+                "git -C /home/pete/tools/my tool remote add origin git@github.com:pete/my-tool.git",
+                "git -C /home/pete/tools/my tool push -u origin --all",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_publish_stops_at_first_failure() {
+        let runner = MockRunner {
+            fail_on: Some("gh"),
+            ..Default::default()
+        };
+        let result = publish(&sample(), Path::new("/home/pete"), "pete", &runner);
+        assert_eq!(result, "failed: boom");
+        assert_eq!(runner.calls.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_needs_publishing() {
+        assert!(needs_publishing(&sample()));
+        let mut uploaded = sample();
+        uploaded.origin_url = Some("git@github.com:pete/my-tool.git".to_string());
+        assert!(!needs_publishing(&uploaded));
+        uploaded.origin_url = None;
+        uploaded.previously_uploaded = Some(true);
+        assert!(!needs_publishing(&uploaded));
+    }
+}
@@ -0,0 +1,78 @@
+//! Token-bucket limiter for directory reads (`--rate`), smoothing bursty I/O on slow media.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+/// Allows at most `rate` operations per second on average, with bursts of up to `rate`.
+///
+/// Tokens live in a semaphore; a background task refills one every `1/rate` seconds until
+/// the bucket is full. Each operation consumes a token for good.
+pub struct RateLimiter {
+    tokens: Arc<Semaphore>,
+    refill: JoinHandle<()>,
+    handle: Handle,
+}
+
+impl RateLimiter {
+    /// Start a full bucket and its refill task. Must be called inside a tokio runtime.
+    pub fn new(rate: u32) -> Self {
+        let capacity = rate.max(1) as usize;
+        let tokens = Arc::new(Semaphore::new(capacity));
+        let bucket = tokens.clone();
+        let period = Duration::from_secs(1) / rate.max(1);
+        let refill = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(period);
+            ticks.tick().await; // the first tick fires immediately
+            loop {
+                ticks.tick().await;
+                if bucket.available_permits() < capacity {
+                    bucket.add_permits(1);
+                }
+            }
+        });
+        RateLimiter {
+            tokens,
+            refill,
+            handle: Handle::current(),
+        }
+    }
+
+    /// Wait for a token and consume it.
+    pub async fn acquire(&self) {
+        if let Ok(permit) = self.tokens.acquire().await {
+            permit.forget();
+        }
+    }
+
+    /// [`RateLimiter::acquire`] for threads outside the runtime (the thread-pool engine).
+    pub fn acquire_blocking(&self) {
+        self.handle.block_on(self.acquire());
+    }
+}
+
+impl Drop for RateLimiter {
+    fn drop(&mut self) {
+        self.refill.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_burst_then_steady_rate() {
+        let limiter = RateLimiter::new(20);
+        let start = Instant::now();
+        // 20 tokens up front, then 10 more at one per 50ms
+        for _ in 0..30 {
+            limiter.acquire().await;
+        }
+        assert!(start.elapsed() >= Duration::from_millis(450));
+    }
+}
@@ -0,0 +1,108 @@
+//! Where results are written: stdout or a file (`--output`), optionally gzip-compressed.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use flate2::Compression as GzLevel;
+use flate2::write::GzEncoder;
+
+/// Output compression (`--compress`). Also implied by an `--output` path ending in `.gz`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Compression {
+    Gzip,
+}
+
+/// A buffered output destination. Call [`Sink::close`] when done: a gzip stream is only
+/// complete once its trailer is written.
+pub enum Sink {
+    Plain(Box<dyn Write>),
+    Gzip(GzEncoder<Box<dyn Write>>),
+}
+
+impl Sink {
+    /// Open `path` (or stdout when `None`), compressing when asked to or when the path
+    /// ends in `.gz`.
+    pub fn open(path: Option<&Path>, compression: Option<Compression>) -> io::Result<Sink> {
+        let out: Box<dyn Write> = match path {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(BufWriter::new(io::stdout())),
+        };
+        let gzip_suffix = path.is_some_and(|p| p.extension().is_some_and(|ext| ext == "gz"));
+        if compression == Some(Compression::Gzip) || gzip_suffix {
+            Ok(Sink::Gzip(GzEncoder::new(out, GzLevel::default())))
+        } else {
+            Ok(Sink::Plain(out))
+        }
+    }
+
+    /// Finish any compressed stream and flush everything to the destination.
+    pub fn close(self) -> io::Result<()> {
+        let mut out = match self {
+            Sink::Plain(out) => out,
+            Sink::Gzip(encoder) => encoder.finish()?,
+        };
+        out.flush()
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Plain(out) => out.write(buf),
+            Sink::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Plain(out) => out.flush(),
+            Sink::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    fn write_and_close(path: &Path, compression: Option<Compression>) {
+        let mut sink = Sink::open(Some(path), compression).unwrap();
+        writeln!(sink, "repository,oldest,newest,count").unwrap();
+        sink.close().unwrap();
+    }
+
+    fn gunzip(path: &Path) -> String {
+        let mut text = String::new();
+        GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn test_gz_suffix_compresses() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("out.csv.gz");
+        write_and_close(&path, None);
+        assert_eq!(gunzip(&path), "repository,oldest,newest,count\n");
+    }
+
+    #[test]
+    fn test_compress_flag_and_plain() {
+        let dir = TempDir::new().unwrap();
+        let gz = dir.path().join("out.csv");
+        write_and_close(&gz, Some(Compression::Gzip));
+        assert_eq!(gunzip(&gz), "repository,oldest,newest,count\n");
+
+        let plain = dir.path().join("plain.csv");
+        write_and_close(&plain, None);
+        assert_eq!(
+            std::fs::read_to_string(&plain).unwrap(),
+            "repository,oldest,newest,count\n"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Counts of directories the walk did not descend into, by reason (`--stats`).
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Why the walker stopped at a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Build output, dependency or tooling directory (`target`, `node_modules`, ...)
+    BadComponent,
+    /// A linked worktree or submodule checkout (`.git` is a file)
+    GitFile,
+    /// `CACHEDIR.TAG` or an `--exclude-dir-with` sentinel
+    Excluded,
+    /// Already visited through a symlink
+    Symlink,
+    /// Directory could not be read for lack of permission
+    PermissionDenied,
+    /// A project root: reported, not descended into
+    ProjectBoundary,
+    /// Outside `--home-only`
+    OutsideHome,
+    /// Deeper than `--no-recurse` allows
+    DepthLimit,
+    /// Not modified since `--newer-than`
+    Unmodified,
+    /// Matched an entry in the `--deny-from` file
+    Denied,
+    /// A pseudo-filesystem such as `/proc` (see `--scan-special`)
+    Special,
+}
+
+impl SkipReason {
+    pub const ALL: &[SkipReason] = &[
+        SkipReason::BadComponent,
+        SkipReason::GitFile,
+        SkipReason::Excluded,
+        SkipReason::Symlink,
+        SkipReason::PermissionDenied,
+        SkipReason::ProjectBoundary,
+        SkipReason::OutsideHome,
+        SkipReason::DepthLimit,
+        SkipReason::Unmodified,
+        SkipReason::Denied,
+        SkipReason::Special,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            SkipReason::BadComponent => "build/dependency dir",
+            SkipReason::GitFile => "worktree or submodule",
+            SkipReason::Excluded => "excluded by sentinel",
+            SkipReason::Symlink => "symlink already visited",
+            SkipReason::PermissionDenied => "permission denied",
+            SkipReason::ProjectBoundary => "project boundary",
+            SkipReason::OutsideHome => "outside home",
+            SkipReason::DepthLimit => "depth limit",
+            SkipReason::Unmodified => "older than --newer-than",
+            SkipReason::Denied => "in deny list",
+            SkipReason::Special => "pseudo-filesystem",
+        }
+    }
+}
+
+/// Lock-free skip counters shared by every task of a scan.
+#[derive(Debug, Default)]
+pub struct SkipStats {
+    counts: [AtomicU64; SkipReason::ALL.len()],
+}
+
+impl SkipStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, reason: SkipReason) {
+        self.counts[reason as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self, reason: SkipReason) -> u64 {
+        self.counts[reason as usize].load(Ordering::Relaxed)
+    }
+}
+
+/// One line per reason that occurred, most frequent first.
+impl fmt::Display for SkipStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut counts: Vec<(SkipReason, u64)> = SkipReason::ALL
+            .iter()
+            .map(|&r| (r, self.count(r)))
+            .filter(|&(_, n)| n > 0)
+            .collect();
+        counts.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+        writeln!(f, "skipped directories:")?;
+        for (reason, n) in counts {
+            writeln!(f, "  {n:>8}  {}", reason.label())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_lists_occurring_reasons() {
+        let stats = SkipStats::new();
+        stats.record(SkipReason::Symlink);
+        stats.record(SkipReason::BadComponent);
+        stats.record(SkipReason::BadComponent);
+        assert_eq!(stats.count(SkipReason::BadComponent), 2);
+        let report = stats.to_string();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].ends_with("build/dependency dir"));
+        assert!(!report.contains("permission denied"));
+    }
+}
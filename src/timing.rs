@@ -0,0 +1,63 @@
+//! Per-project extraction times, for finding what makes a scan slow (`-vv`).
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Collects how long metadata extraction took for each project. Shared by every task of a
+/// scan; recording is a short lock per project.
+#[derive(Debug, Default)]
+pub struct Timings {
+    entries: Mutex<Vec<(PathBuf, Duration)>>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, path: &Path, elapsed: Duration) {
+        self.entries
+            .lock()
+            .unwrap()
+            .push((path.to_path_buf(), elapsed));
+    }
+
+    /// Run `f`, recording its duration against `path`.
+    pub fn time<T>(&self, path: &Path, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record(path, start.elapsed());
+        result
+    }
+
+    /// The `n` slowest projects, slowest first.
+    pub fn slowest(&self, n: usize) -> Vec<(PathBuf, Duration)> {
+        let mut entries = self.entries.lock().unwrap().clone();
+        entries.sort_by_key(|(_, elapsed)| std::cmp::Reverse(*elapsed));
+        entries.truncate(n);
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slowest_orders_by_duration() {
+        let timings = Timings::new();
+        timings.record(Path::new("fast"), Duration::from_millis(1));
+        timings.record(Path::new("slow"), Duration::from_millis(50));
+        timings.record(Path::new("medium"), Duration::from_millis(10));
+        let slowest: Vec<PathBuf> = timings.slowest(2).into_iter().map(|(p, _)| p).collect();
+        assert_eq!(slowest, [PathBuf::from("slow"), PathBuf::from("medium")]);
+    }
+
+    #[test]
+    fn test_time_records_and_returns() {
+        let timings = Timings::new();
+        assert_eq!(timings.time(Path::new("proj"), || 42), 42);
+        assert_eq!(timings.slowest(5).len(), 1);
+    }
+}
@@ -0,0 +1,204 @@
+//! Live view of a scan (`--tui`, built with the `tui` feature): a scrolling list of
+//! projects as they are found, under a header of running counts and progress.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
+
+use ratatui::Frame;
+use ratatui::Terminal;
+use ratatui::backend::Backend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+
+use crate::metadata::ProjectMetadata;
+use crate::progress::Progress;
+
+/// How long to wait for a key press between redraws.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Why the view closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// The scan ended and every record was shown
+    Finished,
+    /// `q` or Esc was pressed; the scan was asked to stop
+    Quit,
+}
+
+/// Projects seen so far and counts over them.
+#[derive(Default)]
+struct LiveView {
+    rows: Vec<String>,
+    git: usize,
+    no_origin: usize,
+    unpushed: usize,
+}
+
+impl LiveView {
+    fn push(&mut self, p: &ProjectMetadata) {
+        if p.is_git {
+            self.git += 1;
+            if p.origin_url.is_none() {
+                self.no_origin += 1;
+            }
+        }
+        if p.unpushed_count.is_some_and(|n| n > 0) {
+            self.unpushed += 1;
+        }
+        let detail = if p.is_git {
+            let count = |n: Option<u32>| n.map_or("?".to_string(), |n| n.to_string());
+            format!(
+                "{} commits, {} unpushed",
+                count(p.total_commits),
+                count(p.unpushed_count)
+            )
+        } else {
+            "not git".to_string()
+        };
+        self.rows.push(format!("{}  ({detail})", p.path));
+    }
+
+    fn header(&self, progress: Option<&Progress>) -> String {
+        let mut header = format!(
+            "{} found, {} git, {} without origin, {} with unpushed commits",
+            self.rows.len(),
+            self.git,
+            self.no_origin,
+            self.unpushed
+        );
+        if let Some(progress) = progress {
+            header.push_str(&format!("  |  {}", progress.status()));
+        }
+        header
+    }
+
+    fn draw(&self, frame: &mut Frame, progress: Option<&Progress>) {
+        let [top, list] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(frame.area());
+        let header = Paragraph::new(self.header(progress)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("lsproj (q to stop)"),
+        );
+        frame.render_widget(header, top);
+        // Keep the newest projects in view
+        let visible = list.height.saturating_sub(2) as usize;
+        let start = self.rows.len().saturating_sub(visible);
+        let items: Vec<ListItem> = self.rows[start..]
+            .iter()
+            .map(|r| ListItem::new(r.as_str()))
+            .collect();
+        frame.render_widget(
+            List::new(items).block(Block::default().borders(Borders::ALL)),
+            list,
+        );
+    }
+}
+
+/// Show the view on the terminal until the scan ends (every sender of `records` is
+/// dropped) or `q` is pressed, which sets `cancel`. The terminal is restored either way.
+pub fn run(
+    records: Receiver<ProjectMetadata>,
+    progress: Option<&Progress>,
+    cancel: &AtomicBool,
+) -> io::Result<Exit> {
+    let mut terminal = ratatui::init();
+    let result = run_with(&mut terminal, &records, progress, cancel, || {
+        if event::poll(TICK)?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            return Ok(Some(key.code));
+        }
+        Ok(None)
+    });
+    ratatui::restore();
+    result
+}
+
+/// [`run`] against any backend, with key presses from `next_key`.
+fn run_with<B>(
+    terminal: &mut Terminal<B>,
+    records: &Receiver<ProjectMetadata>,
+    progress: Option<&Progress>,
+    cancel: &AtomicBool,
+    mut next_key: impl FnMut() -> io::Result<Option<KeyCode>>,
+) -> io::Result<Exit>
+where
+    B: Backend,
+    B::Error: Send + Sync + 'static,
+{
+    let mut view = LiveView::default();
+    loop {
+        let finished = loop {
+            match records.try_recv() {
+                Ok(p) => view.push(&p),
+                Err(TryRecvError::Empty) => break false,
+                Err(TryRecvError::Disconnected) => break true,
+            }
+        };
+        terminal
+            .draw(|frame| view.draw(frame, progress))
+            .map_err(io::Error::other)?;
+        if finished {
+            return Ok(Exit::Finished);
+        }
+        if matches!(next_key()?, Some(KeyCode::Char('q') | KeyCode::Esc)) {
+            cancel.store(true, Ordering::Relaxed);
+            return Ok(Exit::Quit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use std::sync::mpsc;
+
+    fn screen(terminal: &Terminal<TestBackend>) -> String {
+        let buffer = terminal.backend().buffer();
+        buffer.content().iter().map(|c| c.symbol()).collect()
+    }
+
+    #[test]
+    fn test_consumes_finite_stream() {
+        let (tx, rx) = mpsc::channel();
+        for (path, is_git) in [("alpha", true), ("tools/beta", true), ("notes", false)] {
+            tx.send(ProjectMetadata {
+                path: path.to_string(),
+                is_git,
+                ..Default::default()
+            })
+            .unwrap();
+        }
+        drop(tx);
+        let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+        let cancel = AtomicBool::new(false);
+        let exit = run_with(&mut terminal, &rx, None, &cancel, || Ok(None)).unwrap();
+        assert_eq!(exit, Exit::Finished);
+        let screen = screen(&terminal);
+        assert!(
+            screen.contains("3 found, 2 git, 2 without origin"),
+            "{screen}"
+        );
+        assert!(screen.contains("tools/beta"), "{screen}");
+        assert!(!cancel.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_q_cancels_scan() {
+        let (_tx, rx) = mpsc::channel();
+        let mut terminal = Terminal::new(TestBackend::new(80, 10)).unwrap();
+        let cancel = AtomicBool::new(false);
+        let exit = run_with(&mut terminal, &rx, None, &cancel, || {
+            Ok(Some(KeyCode::Char('q')))
+        })
+        .unwrap();
+        assert_eq!(exit, Exit::Quit);
+        assert!(cancel.load(Ordering::Relaxed));
+    }
+}
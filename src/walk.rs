@@ -0,0 +1,1702 @@
+use std::collections::HashSet;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Context, Result};
+use tokio::sync::Semaphore;
+use tokio::task::{self, JoinHandle};
+
+use crate::checkpoint::{Checkpoint, Subtree};
+use crate::extsort::ExternalSorter;
+use crate::fdlimit;
+use crate::filter::{DenyList, EntryKind, classify_entry_with_markers, is_submodule_checkout};
+use crate::fs_meta::is_special_fs;
+use crate::incremental::ScanCache;
+use crate::metadata::{ExtractOptions, ProjectMetadata, extract_metadata, refresh_worktree};
+use crate::progress::Progress;
+use crate::ratelimit::RateLimiter;
+use crate::stats::{SkipReason, SkipStats};
+use crate::timing::Timings;
+
+/// How the directory tree is traversed (`--engine`). Both engines report the same
+/// projects; which is faster depends on the shape of the tree.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Engine {
+    /// One tokio task per directory, bounded by a semaphore. Good for slow or networked disks.
+    #[default]
+    Async,
+    /// A rayon work-stealing pool with synchronous reads. Avoids per-directory task overhead
+    /// on trees of many small directories.
+    Threads,
+}
+
+/// Settings for a directory scan.
+#[derive(Debug, Default, Clone)]
+pub struct WalkOptions {
+    pub extract: ExtractOptions,
+    /// Traversal strategy (`--engine`)
+    pub engine: Engine,
+    /// Results held in memory before spilling to disk (`--sort-buffer`)
+    pub sort_buffer: Option<usize>,
+    /// Only descend into and report paths under this directory (`--home-only`)
+    pub home: Option<PathBuf>,
+    /// Extra attempts for directory reads that fail transiently (`--retries`)
+    pub retries: u32,
+    /// Extra file or directory names that mark a project root (`--repo-marker`)
+    pub markers: Vec<String>,
+    /// Extra file names whose presence prunes a directory, beyond `CACHEDIR.TAG`
+    /// (`--exclude-dir-with`)
+    pub exclude_sentinels: Vec<String>,
+    /// Extra directory names and globs to skip (`--deny-from`)
+    pub deny: DenyList,
+    /// Cap on directory reads per second (`--rate`)
+    pub rate: Option<u32>,
+    /// File recording completed top-level subtrees (`--checkpoint`)
+    pub checkpoint: Option<PathBuf>,
+    /// Skip subtrees already completed in `checkpoint` (`--resume`)
+    pub resume: bool,
+    /// Deepest level of directories examined, children of the root being 1 (`--no-recurse`)
+    pub max_depth: Option<usize>,
+    /// Only report git repos among the root's children, telling them apart with a stat of
+    /// `.git` instead of reading each child (`--flat`). Ignores `engine`.
+    pub flat: bool,
+    /// Descend into `/proc`, `/sys` and other pseudo-filesystems (`--scan-special`)
+    pub scan_special: bool,
+    /// Skip directories last modified before this time (`--newer-than`)
+    pub newer_than: Option<SystemTime>,
+    /// Report submodule checkouts as projects instead of skipping them
+    /// (`--include-dependency-checkouts`)
+    pub include_dependency_checkouts: bool,
+    /// Concurrent directory reads in the async engine (`--dir-concurrency`);
+    /// [`DEFAULT_DIR_CONCURRENCY`] when unset
+    pub dir_concurrency: Option<usize>,
+    /// In the async engine, read with fewer directories at once after running out of file
+    /// descriptors, recovering gradually (`--adaptive-concurrency`). Extraction permits
+    /// are left alone: there are few of them, and git2 doesn't report exhaustion as such.
+    pub adaptive_concurrency: bool,
+    /// Concurrent metadata extractions (history walks) in the async engine
+    /// (`--repo-concurrency`); the number of CPUs when unset
+    pub repo_concurrency: Option<usize>,
+    /// Counts directories examined, against a total from [`count_dirs`] (`--estimate`)
+    pub progress: Option<Arc<Progress>>,
+    /// Counts directories not descended into, by reason, when set (`--stats`)
+    pub stats: Option<Arc<SkipStats>>,
+    /// Collects per-project extraction times when set (`-vv`)
+    pub timings: Option<Arc<Timings>>,
+    /// Previous scan's records, reused for unchanged repos (`--since-last-scan`)
+    pub since_last_scan: Option<Arc<ScanCache>>,
+    /// Receives a copy of each record as soon as it is extracted (`--tui`)
+    pub live: Option<mpsc::Sender<ProjectMetadata>>,
+    /// Once set, no further directories are read or projects extracted; the scan ends
+    /// early with what it has
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+/// Directory reads in flight at once when `--dir-concurrency` is not given.
+pub const DEFAULT_DIR_CONCURRENCY: usize = 100;
+
+impl WalkOptions {
+    /// Directory reads the async engine allows in flight at once.
+    pub fn dir_permits(&self) -> usize {
+        self.dir_concurrency
+            .unwrap_or(DEFAULT_DIR_CONCURRENCY)
+            .max(1)
+    }
+
+    /// Metadata extractions the async engine runs at once.
+    pub fn repo_permits(&self) -> usize {
+        self.repo_concurrency
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(4, |n| n.get()))
+            .max(1)
+    }
+}
+
+/// Lists the subdirectories of a directory. The walker reads through this seam so tests
+/// can inject failures.
+pub trait DirSource: Send + Sync {
+    fn subdirs(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+}
+
+/// Reads the real filesystem.
+pub struct FsDirSource;
+
+impl DirSource for FsDirSource {
+    fn subdirs(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut dirs = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+        Ok(dirs)
+    }
+}
+
+/// Cache directory marker from <https://bford.info/cachedir/>, honored by backup tools.
+const CACHEDIR_TAG: &str = "CACHEDIR.TAG";
+const CACHEDIR_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// True when `dir` holds a valid `CACHEDIR.TAG` or any of the user's sentinel files.
+fn is_excluded(dir: &Path, sentinels: &[String]) -> bool {
+    let tagged = std::fs::read(dir.join(CACHEDIR_TAG))
+        .is_ok_and(|contents| contents.starts_with(CACHEDIR_SIGNATURE));
+    tagged || sentinels.iter().any(|s| dir.join(s).exists())
+}
+
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(20);
+
+/// Attempts at a read that keeps failing for lack of file descriptors under
+/// `--adaptive-concurrency`, each after shrinking the permit pool.
+const EXHAUSTED_RETRIES: u32 = 8;
+
+/// Successful reads between returning one withheld permit to the pool.
+const RAMP_UP_READS: usize = 16;
+
+/// Errors worth retrying on flaky (typically network) mounts. NotFound, PermissionDenied
+/// and friends are permanent and fail immediately.
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+    )
+}
+
+/// Shared state for one scan, handed to every spawned task.
+struct Walker {
+    root: PathBuf,
+    opts: WalkOptions,
+    source: Arc<dyn DirSource>,
+    /// Tasks and permits of the async engine; `None` under [`Engine::Threads`], whose
+    /// rayon pool bounds it instead
+    pool: Option<TaskPool>,
+    seen_paths: Mutex<HashSet<PathBuf>>,
+    results: Mutex<ExternalSorter>,
+    limiter: Option<RateLimiter>,
+    checkpoint: Option<Arc<Checkpoint>>,
+    /// Directories that could not be read and projects that could not be extracted
+    errors: AtomicU64,
+    /// Set the first time a directory or project is skipped because of `opts.cancel`
+    cancel_seen: AtomicBool,
+}
+
+/// The async engine's spawned tasks and the permit pools bounding them.
+struct TaskPool {
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+    /// Permits for directory reads: cheap, plentiful, keeps discovery flowing
+    semaphore: Semaphore,
+    /// Permits for extraction: few, so huge histories can't crowd out discovery
+    extract_semaphore: Semaphore,
+    /// Size of `semaphore` before any `--adaptive-concurrency` shrinking
+    dir_permits: usize,
+    /// Directory-read permits taken out of the pool by `--adaptive-concurrency`
+    withheld: AtomicUsize,
+    /// Successful reads, for pacing the return of withheld permits
+    reads: AtomicUsize,
+}
+
+impl TaskPool {
+    fn new(opts: &WalkOptions) -> Self {
+        let (dir_permits, extract_permits) = (opts.dir_permits(), opts.repo_permits());
+        TaskPool {
+            tasks: Mutex::new(Vec::new()),
+            semaphore: Semaphore::new(dir_permits),
+            extract_semaphore: Semaphore::new(extract_permits),
+            dir_permits,
+            withheld: AtomicUsize::new(0),
+            reads: AtomicUsize::new(0),
+        }
+    }
+
+    fn push(&self, task: JoinHandle<()>) {
+        self.tasks.lock().unwrap().push(task);
+    }
+
+    /// Take half the directory-read permits still in the pool out of it, always leaving
+    /// one. Waits for reads in flight to hand theirs back.
+    async fn throttle(&self) {
+        let total = self.dir_permits;
+        let Ok(previous) =
+            self.withheld
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |withheld| {
+                    let shrink = (total - withheld) / 2;
+                    (shrink > 0).then_some(withheld + shrink)
+                })
+        else {
+            return;
+        };
+        let shrink = (total - previous) / 2;
+        if let Ok(permits) = self.semaphore.acquire_many(shrink as u32).await {
+            permits.forget();
+        }
+    }
+
+    /// Return one withheld permit to the pool every [`RAMP_UP_READS`] successful reads.
+    fn ramp_up(&self) {
+        if self.withheld.load(Ordering::SeqCst) == 0
+            || self.reads.fetch_add(1, Ordering::Relaxed) % RAMP_UP_READS != RAMP_UP_READS - 1
+        {
+            return;
+        }
+        if self
+            .withheld
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |w| w.checked_sub(1))
+            .is_ok()
+        {
+            self.semaphore.add_permits(1);
+        }
+    }
+}
+
+/// Outcome of [`scan`].
+pub struct Scan {
+    /// Directory that reported paths are relative to
+    pub base: PathBuf,
+    pub results: ExternalSorter,
+    /// Directories and projects skipped after an error, already reported on stderr
+    pub errors: u64,
+    /// The walk saw [`WalkOptions::cancel`] set and skipped work, so `results` is partial
+    pub cancelled: bool,
+}
+
+/// Walk `root` asynchronously with bounded concurrency, extracting metadata for every
+/// project root found. Returns the results ready for sorted iteration.
+///
+/// The starting directory is evaluated first: if it is (or is inside) a git work tree, or
+/// carries a `--repo-marker`, that one project is reported relative to its parent and
+/// nothing is walked.
+pub async fn scan(root: PathBuf, opts: WalkOptions) -> Result<Scan> {
+    scan_with(root, opts, Arc::new(FsDirSource)).await
+}
+
+/// [`scan`] reading directories through `source`.
+pub async fn scan_with(
+    root: PathBuf,
+    opts: WalkOptions,
+    source: Arc<dyn DirSource>,
+) -> Result<Scan> {
+    let root_for_check = root.clone();
+    let markers = opts.markers.clone();
+    let enclosing =
+        task::spawn_blocking(move || enclosing_project(&root_for_check, &markers)).await?;
+    if let Some(project) = enclosing {
+        let base = project.parent().unwrap_or(&project).to_path_buf();
+        let mut results = ExternalSorter::new(opts.sort_buffer);
+        let (project_clone, base_clone) = (project.clone(), base.clone());
+        let meta = task::spawn_blocking(move || {
+            extract_metadata(&project_clone, &base_clone, &opts.extract)
+        })
+        .await??;
+        results.push(meta)?;
+        return Ok(Scan {
+            base,
+            results,
+            errors: 0,
+            cancelled: false,
+        });
+    }
+
+    if opts.flat {
+        let results = ExternalSorter::new(opts.sort_buffer);
+        let pool = TaskPool::new(&opts);
+        let walker = Arc::new(Walker::new(root, opts, source, None, results, Some(pool)));
+        scan_flat(&walker).await?;
+        let walker =
+            Arc::try_unwrap(walker).map_err(|_| anyhow::anyhow!("walker arc still held"))?;
+        return Ok(Scan {
+            base: walker.root,
+            results: walker.results.into_inner().unwrap(),
+            errors: walker.errors.into_inner(),
+            cancelled: walker.cancel_seen.into_inner(),
+        });
+    }
+
+    let (checkpoint, results) = resume_checkpoint(&root, &opts)?;
+    if opts.engine == Engine::Threads {
+        return task::spawn_blocking(move || scan_threads(root, opts, source, checkpoint, results))
+            .await?;
+    }
+
+    let pool = TaskPool::new(&opts);
+    let walker = Arc::new(Walker::new(
+        root.clone(),
+        opts,
+        source,
+        checkpoint,
+        results,
+        Some(pool),
+    ));
+    spawn_root(&walker, root);
+    drain_tasks(&walker).await;
+
+    let walker = Arc::try_unwrap(walker).map_err(|_| anyhow::anyhow!("walker arc still held"))?;
+    Ok(Scan {
+        base: walker.root,
+        results: walker.results.into_inner().unwrap(),
+        errors: walker.errors.into_inner(),
+        cancelled: walker.cancel_seen.into_inner(),
+    })
+}
+
+/// The [`WalkOptions::flat`] traversal: only the root is read. A child without a `.git`
+/// directory costs one stat; the rest go through the usual checks and are extracted.
+async fn scan_flat(walker: &Arc<Walker>) -> Result<()> {
+    let root = walker.root.clone();
+    let children = read_subdirs(walker, &root)
+        .await
+        .map_err(|e| walker.read_failed(e))
+        .with_context(|| format!("Failed to read directory: {}", root.display()))?;
+    for path in children {
+        if walker.cancelled() {
+            break;
+        }
+        if !path.join(".git").is_dir() {
+            if let Some(progress) = &walker.opts.progress {
+                progress.tick();
+            }
+            continue;
+        }
+        if matches!(walker.child_kind(&path), Some(EntryKind::Project)) {
+            spawn_record(walker, path, None);
+        }
+    }
+    drain_tasks(walker).await;
+    Ok(())
+}
+
+/// Inventory a directory of bare mirrors (`--bare-mirrors`): every immediate subdirectory
+/// named `*.git` that opens as a bare repo is reported, named without its `.git` suffix.
+/// Nothing else is walked.
+pub async fn scan_bare_mirrors(root: PathBuf, opts: WalkOptions) -> Result<Scan> {
+    let mirrors = {
+        let root = root.clone();
+        task::spawn_blocking(move || bare_mirrors(&root)).await??
+    };
+    let mut handles = Vec::new();
+    for mirror in mirrors {
+        let (root, extract, timings) = (root.clone(), opts.extract.clone(), opts.timings.clone());
+        handles.push(task::spawn_blocking(move || {
+            let run = || extract_metadata(&mirror, &root, &extract);
+            match &timings {
+                Some(timings) => timings.time(&mirror, run),
+                None => run(),
+            }
+            .with_context(|| format!("Error extracting {}", mirror.display()))
+        }));
+    }
+    let mut results = ExternalSorter::new(opts.sort_buffer);
+    let mut errors = 0;
+    for handle in handles {
+        match handle.await? {
+            Ok(meta) => results.push(meta)?,
+            Err(e) => {
+                errors += 1;
+                eprintln!("{e:?}");
+            }
+        }
+    }
+    Ok(Scan {
+        base: root,
+        results,
+        errors,
+        cancelled: false,
+    })
+}
+
+fn bare_mirrors(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut mirrors = Vec::new();
+    for entry in std::fs::read_dir(root)
+        .with_context(|| format!("Failed to read directory: {}", root.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "git")
+            && git2::Repository::open_bare(&path).is_ok_and(|repo| repo.is_bare())
+        {
+            mirrors.push(path);
+        }
+    }
+    Ok(mirrors)
+}
+
+/// The [`Engine::Threads`] traversal: same decisions as the async walker, made on a
+/// rayon pool.
+fn scan_threads(
+    root: PathBuf,
+    opts: WalkOptions,
+    source: Arc<dyn DirSource>,
+    checkpoint: Option<Arc<Checkpoint>>,
+    results: ExternalSorter,
+) -> Result<Scan> {
+    let walker = Walker::new(root.clone(), opts, source, checkpoint, results, None);
+    rayon::scope(|s| {
+        let walker = &walker;
+        s.spawn(move |s| {
+            if let Err(e) = walk_dir_threads(&root, None, walker, s) {
+                walker.failed();
+                eprintln!("Error in root: {e:?}");
+            }
+        });
+    });
+    Ok(Scan {
+        base: walker.root,
+        results: walker.results.into_inner().unwrap(),
+        errors: walker.errors.into_inner(),
+        cancelled: walker.cancel_seen.into_inner(),
+    })
+}
+
+fn walk_dir_threads<'s>(
+    dir: &Path,
+    subtree: Option<Arc<Subtree>>,
+    walker: &'s Walker,
+    scope: &rayon::Scope<'s>,
+) -> Result<()> {
+    if walker.cancelled() {
+        abandon(subtree.as_deref());
+        return Ok(());
+    }
+    let subdirs = read_subdirs_blocking(walker, dir)
+        .map_err(|e| {
+            abandon(subtree.as_deref());
+            walker.read_failed(e)
+        })
+        .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+
+    for path in subdirs {
+        if walker.resumed(dir, &path) {
+            continue;
+        }
+        let Some(kind @ (EntryKind::Project | EntryKind::Collection)) = walker.child_kind(&path)
+        else {
+            continue;
+        };
+        // Only children that are walked get a checkpoint subtree
+        let subtree = walker.subtree_for(dir, &path, &subtree);
+        if matches!(kind, EntryKind::Project) {
+            scope.spawn(move |_| walker.record(&path, subtree.as_deref()))
+        } else {
+            scope.spawn(move |s| {
+                if let Err(e) = walk_dir_threads(&path, subtree, walker, s) {
+                    walker.failed();
+                    eprintln!("Error in {}: {e:?}", path.display());
+                }
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Open the `--checkpoint` file, if any, and seed the results with records from subtrees
+/// that a previous run completed.
+fn resume_checkpoint(
+    root: &Path,
+    opts: &WalkOptions,
+) -> Result<(Option<Arc<Checkpoint>>, ExternalSorter)> {
+    let mut results = ExternalSorter::new(opts.sort_buffer);
+    let Some(path) = &opts.checkpoint else {
+        return Ok((None, results));
+    };
+    let checkpoint = Checkpoint::open(path, root, opts.resume)?;
+    for meta in checkpoint.completed_records() {
+        results.push(meta)?;
+    }
+    Ok((Some(Arc::new(checkpoint)), results))
+}
+
+impl Walker {
+    fn new(
+        root: PathBuf,
+        opts: WalkOptions,
+        source: Arc<dyn DirSource>,
+        checkpoint: Option<Arc<Checkpoint>>,
+        results: ExternalSorter,
+        pool: Option<TaskPool>,
+    ) -> Self {
+        Walker {
+            root,
+            source,
+            pool,
+            seen_paths: Mutex::new(HashSet::new()),
+            results: Mutex::new(results),
+            limiter: opts.rate.map(RateLimiter::new),
+            checkpoint,
+            errors: AtomicU64::new(0),
+            cancel_seen: AtomicBool::new(false),
+            opts,
+        }
+    }
+
+    /// The async engine's task pool.
+    fn pool(&self) -> &TaskPool {
+        self.pool
+            .as_ref()
+            .expect("the async engine always has a task pool")
+    }
+
+    /// True when `path` (a child of `dir`) is a top-level subtree that a previous run
+    /// already completed.
+    fn resumed(&self, dir: &Path, path: &Path) -> bool {
+        dir == self.root
+            && self
+                .checkpoint
+                .as_ref()
+                .is_some_and(|checkpoint| checkpoint.is_complete(path))
+    }
+
+    /// The checkpoint subtree that `path` (a child of `dir`) belongs to: children of the
+    /// root start a new one, deeper paths inherit their parent's.
+    fn subtree_for(
+        &self,
+        dir: &Path,
+        path: &Path,
+        parent: &Option<Arc<Subtree>>,
+    ) -> Option<Arc<Subtree>> {
+        match (parent, &self.checkpoint) {
+            (Some(subtree), _) => Some(subtree.clone()),
+            (None, Some(checkpoint)) if dir == self.root => Some(Arc::new(Subtree::new(
+                path.to_path_buf(),
+                checkpoint.clone(),
+            ))),
+            (None, _) => None,
+        }
+    }
+
+    /// How to treat a subdirectory found during the walk, or `None` when it is pruned by
+    /// `--home-only` or was already visited through a symlink. Shared by both engines.
+    fn child_kind(&self, path: &Path) -> Option<EntryKind> {
+        if let Some(progress) = &self.opts.progress {
+            progress.tick();
+        }
+        if let Some(home) = &self.opts.home
+            && !within_home(path, home)
+        {
+            self.skipped(SkipReason::OutsideHome);
+            return None;
+        }
+
+        // Check canonical path for cycle detection
+        if let Ok(canonical) = std::fs::canonicalize(path) {
+            let mut seen = self.seen_paths.lock().unwrap();
+            if !seen.insert(canonical) {
+                self.skipped(SkipReason::Symlink);
+                return None; // already visited via a symlink — skip
+            }
+        }
+
+        if !self.opts.scan_special && is_special_fs(path) {
+            self.skipped(SkipReason::Special);
+            return None;
+        }
+        if self.opts.deny.denies(path) {
+            self.skipped(SkipReason::Denied);
+            return Some(EntryKind::Skip);
+        }
+        if is_excluded(path, &self.opts.exclude_sentinels) {
+            self.skipped(SkipReason::Excluded);
+            return Some(EntryKind::Skip);
+        }
+
+        let mut kind = classify_entry_with_markers(path, &self.opts.markers);
+        if matches!(kind, EntryKind::Skip)
+            && self.opts.include_dependency_checkouts
+            && is_submodule_checkout(path)
+        {
+            kind = EntryKind::Project;
+        }
+        // Ancestors of home must be descended even if they look like projects
+        if let Some(home) = &self.opts.home
+            && !path.starts_with(home)
+            && !matches!(kind, EntryKind::Skip)
+        {
+            kind = EntryKind::Collection;
+        }
+        if matches!(kind, EntryKind::Collection)
+            && let Some(max_depth) = self.opts.max_depth
+            && self.depth(path) >= max_depth
+        {
+            self.skipped(SkipReason::DepthLimit);
+            return None;
+        }
+        if matches!(kind, EntryKind::Collection) && modified_before(path, self.opts.newer_than) {
+            self.skipped(SkipReason::Unmodified);
+            return None;
+        }
+        match kind {
+            EntryKind::Skip if path.join(".git").is_file() => self.skipped(SkipReason::GitFile),
+            EntryKind::Skip => self.skipped(SkipReason::BadComponent),
+            EntryKind::Project => self.skipped(SkipReason::ProjectBoundary),
+            EntryKind::Collection => {}
+        }
+        Some(kind)
+    }
+
+    /// Count an error for [`Scan::errors`].
+    fn failed(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn skipped(&self, reason: SkipReason) {
+        if let Some(stats) = &self.opts.stats {
+            stats.record(reason);
+        }
+    }
+
+    /// Count an unreadable directory for `--stats`, passing the error through.
+    fn read_failed(&self, e: io::Error) -> io::Error {
+        if e.kind() == io::ErrorKind::PermissionDenied {
+            self.skipped(SkipReason::PermissionDenied);
+        }
+        e
+    }
+
+    fn depth(&self, path: &Path) -> usize {
+        path.strip_prefix(&self.root)
+            .map(|rel| rel.components().count())
+            .unwrap_or(0)
+    }
+
+    /// True once `opts.cancel` is set. Callers skip their work when it is, so this also
+    /// records that the scan came out partial.
+    fn cancelled(&self) -> bool {
+        let cancelled = self
+            .opts
+            .cancel
+            .as_ref()
+            .is_some_and(|c| c.load(Ordering::Relaxed));
+        if cancelled {
+            self.cancel_seen.store(true, Ordering::Relaxed);
+        }
+        cancelled
+    }
+
+    /// Extract metadata for a project and buffer it, reporting failures on stderr.
+    fn record(&self, path: &Path, subtree: Option<&Subtree>) {
+        if self.cancelled() {
+            abandon(subtree);
+            return;
+        }
+        let path_display = path.display();
+        let cache = self.opts.since_last_scan.as_deref();
+        let reused = cache.and_then(|c| c.reuse(path));
+        let fresh = reused.is_none();
+        let extract = || match reused {
+            Some(mut meta) => {
+                refresh_worktree(path, &self.opts.extract, &mut meta);
+                Ok(meta)
+            }
+            None => extract_metadata(path, &self.root, &self.opts.extract),
+        };
+        let extracted = match &self.opts.timings {
+            Some(timings) => timings.time(path, extract),
+            None => extract(),
+        };
+        match extracted {
+            Ok(meta) => {
+                if let Some(cache) = cache {
+                    cache.store(path, &meta, fresh);
+                }
+                if let Some(subtree) = subtree {
+                    subtree.push(meta.clone());
+                }
+                if let Some(live) = &self.opts.live {
+                    // The viewer may have gone; the scan carries on regardless
+                    let _ = live.send(meta.clone());
+                }
+                if let Err(e) = self.results.lock().unwrap().push(meta) {
+                    self.failed();
+                    eprintln!("Error buffering {path_display}: {e:?}");
+                }
+            }
+            Err(e) => {
+                abandon(subtree);
+                self.failed();
+                eprintln!("Error extracting {path_display}: {e:?}");
+            }
+        }
+    }
+}
+
+/// Keep `subtree`, if any, out of the checkpoint.
+fn abandon(subtree: Option<&Subtree>) {
+    if let Some(subtree) = subtree {
+        subtree.abandon();
+    }
+}
+
+/// Cheap first pass for `--estimate`: the number of directories a scan of `root` would
+/// examine, classifying entries the same way but opening no repos and reading no metadata.
+/// Ignores `--home-only`, so it may overestimate there.
+pub fn count_dirs(root: &Path, opts: &WalkOptions) -> u64 {
+    let mut seen = HashSet::new();
+    let mut pending = vec![root.to_path_buf()];
+    let mut count = 0;
+    while let Some(dir) = pending.pop() {
+        let Ok(subdirs) = FsDirSource.subdirs(&dir) else {
+            continue;
+        };
+        for path in subdirs {
+            count += 1;
+            if std::fs::canonicalize(&path).is_ok_and(|canonical| !seen.insert(canonical)) {
+                continue;
+            }
+            if (!opts.scan_special && is_special_fs(&path))
+                || opts.deny.denies(&path)
+                || is_excluded(&path, &opts.exclude_sentinels)
+            {
+                continue;
+            }
+            let depth = path
+                .strip_prefix(root)
+                .map_or(0, |rel| rel.components().count());
+            if matches!(
+                classify_entry_with_markers(&path, &opts.markers),
+                EntryKind::Collection
+            ) && opts.max_depth.is_none_or(|max| depth < max)
+                && !modified_before(&path, opts.newer_than)
+            {
+                pending.push(path);
+            }
+        }
+    }
+    count
+}
+
+/// True when `dir` was last modified before `cutoff`. Adding or removing an entry bumps a
+/// directory's mtime, but editing a file deeper down does not, so this only notices new
+/// and deleted children.
+fn modified_before(dir: &Path, cutoff: Option<SystemTime>) -> bool {
+    cutoff.is_some_and(|cutoff| {
+        std::fs::metadata(dir)
+            .and_then(|m| m.modified())
+            .is_ok_and(|mtime| mtime < cutoff)
+    })
+}
+
+/// The project the scan root itself belongs to: the enclosing git work tree (the root or
+/// an ancestor of it), or the root when it contains one of `markers`.
+fn enclosing_project(root: &Path, markers: &[String]) -> Option<PathBuf> {
+    if let Ok(repo) = git2::Repository::discover(root)
+        && let Some(workdir) = repo.workdir()
+    {
+        return Some(workdir.to_path_buf());
+    }
+    if markers.iter().any(|m| root.join(m).exists()) {
+        return Some(root.to_path_buf());
+    }
+    None
+}
+
+/// `--home-only` pruning: a directory is worth entering if it is under `home` or on the
+/// way down to it.
+fn within_home(path: &Path, home: &Path) -> bool {
+    path.starts_with(home) || home.starts_with(path)
+}
+
+/// Read `dir` via the walker's source, retrying transient failures with exponential backoff.
+/// Under `--adaptive-concurrency`, running out of file descriptors shrinks the permit pool
+/// before retrying.
+async fn read_subdirs(walker: &Arc<Walker>, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut attempt = 0;
+    let mut exhausted = 0;
+    loop {
+        if let Some(limiter) = &walker.limiter {
+            limiter.acquire().await;
+        }
+        let source = walker.source.clone();
+        let dir_owned = dir.to_path_buf();
+        // The permit guards only the read itself; spawning children and backing off hold
+        // none, so every permit is available to actual directory I/O.
+        let result = {
+            let _permit = walker
+                .pool()
+                .semaphore
+                .acquire()
+                .await
+                .map_err(io::Error::other)?;
+            task::spawn_blocking(move || source.subdirs(&dir_owned))
+                .await
+                .map_err(io::Error::other)?
+        };
+        match result {
+            Err(e) if attempt < walker.opts.retries && is_transient(&e) => {
+                tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt)).await;
+                attempt += 1;
+            }
+            Err(e)
+                if walker.opts.adaptive_concurrency
+                    && exhausted < EXHAUSTED_RETRIES
+                    && fdlimit::is_exhausted(&e) =>
+            {
+                walker.pool().throttle().await;
+                tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(exhausted.min(4))).await;
+                exhausted += 1;
+            }
+            Ok(subdirs) => {
+                walker.pool().ramp_up();
+                return Ok(subdirs);
+            }
+            other => return other,
+        }
+    }
+}
+
+/// Blocking [`read_subdirs`] for the thread-pool engine.
+fn read_subdirs_blocking(walker: &Walker, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut attempt = 0;
+    loop {
+        if let Some(limiter) = &walker.limiter {
+            limiter.acquire_blocking();
+        }
+        match walker.source.subdirs(dir) {
+            Err(e) if attempt < walker.opts.retries && is_transient(&e) => {
+                std::thread::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt));
+                attempt += 1;
+            }
+            other => return other,
+        }
+    }
+}
+
+/// Start the async walk at `root`.
+fn spawn_root(walker: &Arc<Walker>, root: PathBuf) {
+    let walker_clone = walker.clone();
+    let initial_task = task::spawn(async move {
+        if let Err(e) = walk_dir(root, None, walker_clone.clone()).await {
+            walker_clone.failed();
+            eprintln!("Error in root: {e:?}");
+        }
+    });
+    walker.pool().push(initial_task);
+}
+
+/// Extract `path` on the blocking pool once an extraction permit is free.
+fn spawn_record(walker: &Arc<Walker>, path: PathBuf, subtree: Option<Arc<Subtree>>) {
+    let walker_clone = walker.clone();
+    let path_display = path.display().to_string();
+    let new_task = task::spawn(async move {
+        // Waiting here holds no directory permit, so discovery continues
+        let Ok(_permit) = walker_clone.pool().extract_semaphore.acquire().await else {
+            abandon(subtree.as_deref());
+            return;
+        };
+        let (recorder, watched) = (walker_clone.clone(), subtree.clone());
+        let result = task::spawn_blocking(move || recorder.record(&path, subtree.as_deref())).await;
+        if let Err(e) = result {
+            abandon(watched.as_deref());
+            walker_clone.failed();
+            eprintln!("Task panic for {path_display}: {e:?}");
+        }
+    });
+    walker.pool().push(new_task);
+}
+
+/// Await spawned tasks, including those they spawn in turn, until none remain.
+async fn drain_tasks(walker: &Walker) {
+    loop {
+        let current_tasks = {
+            let mut locked = walker.pool().tasks.lock().unwrap();
+            if locked.is_empty() {
+                break;
+            }
+            std::mem::take(&mut *locked)
+        };
+        for handle in current_tasks {
+            let _ = handle.await;
+        }
+    }
+}
+
+fn walk_dir(
+    dir: PathBuf,
+    subtree: Option<Arc<Subtree>>,
+    walker: Arc<Walker>,
+) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> {
+    Box::pin(async move {
+        if walker.cancelled() {
+            abandon(subtree.as_deref());
+            return Ok(());
+        }
+        let subdirs = read_subdirs(&walker, &dir)
+            .await
+            .map_err(|e| {
+                abandon(subtree.as_deref());
+                walker.read_failed(e)
+            })
+            .with_context(|| format!("Failed to read directory: {}", dir.display()))?;
+
+        for path in subdirs {
+            if walker.resumed(&dir, &path) {
+                continue;
+            }
+            let Some(kind @ (EntryKind::Project | EntryKind::Collection)) =
+                walker.child_kind(&path)
+            else {
+                continue;
+            };
+            // Only children that are walked get a checkpoint subtree
+            let subtree = walker.subtree_for(&dir, &path, &subtree);
+            if matches!(kind, EntryKind::Project) {
+                spawn_record(&walker, path, subtree);
+                continue;
+            }
+            let walker_clone = walker.clone();
+            let path_display = path.display().to_string();
+            let new_task = task::spawn(async move {
+                if let Err(e) = walk_dir(path, subtree, walker_clone.clone()).await {
+                    walker_clone.failed();
+                    eprintln!("Error in {path_display}: {e:?}");
+                }
+            });
+            walker.pool().push(new_task);
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tempfile::TempDir;
+
+    /// Fails the first `failures` reads with `kind`, then reads the real filesystem.
+    struct FlakySource {
+        failures: u32,
+        kind: io::ErrorKind,
+        calls: AtomicU32,
+    }
+
+    impl DirSource for FlakySource {
+        fn subdirs(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(io::Error::from(self.kind));
+            }
+            FsDirSource.subdirs(dir)
+        }
+    }
+
+    /// Reads the real filesystem, except that reading `broken` is always denied.
+    struct BrokenDirSource {
+        broken: PathBuf,
+    }
+
+    impl DirSource for BrokenDirSource {
+        fn subdirs(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+            if dir == self.broken {
+                return Err(io::Error::from(io::ErrorKind::PermissionDenied));
+            }
+            FsDirSource.subdirs(dir)
+        }
+    }
+
+    /// Reads the real filesystem slowly, tracking the most reads ever in flight at once.
+    #[derive(Default)]
+    struct SlowSource {
+        in_flight: AtomicU32,
+        peak: AtomicU32,
+    }
+
+    impl DirSource for SlowSource {
+        fn subdirs(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            let result = FsDirSource.subdirs(dir);
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            result
+        }
+    }
+
+    /// Reads the real filesystem slowly, failing with "Too many open files" whenever more
+    /// than `limit` reads are in flight.
+    #[cfg(unix)]
+    struct ExhaustingSource {
+        limit: u32,
+        in_flight: AtomicU32,
+    }
+
+    #[cfg(unix)]
+    impl DirSource for ExhaustingSource {
+        fn subdirs(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            std::thread::sleep(Duration::from_millis(30));
+            let result = if now > self.limit {
+                Err(io::Error::from_raw_os_error(libc::EMFILE))
+            } else {
+                FsDirSource.subdirs(dir)
+            };
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            result
+        }
+    }
+
+    fn project_tree() -> TempDir {
+        let root = TempDir::new().unwrap();
+        let proj = root.path().join("myproj");
+        std::fs::create_dir(&proj).unwrap();
+        std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        root
+    }
+
+    fn paths(sorter: ExternalSorter) -> Vec<String> {
+        sorter
+            .into_sorted()
+            .unwrap()
+            .map(|m| m.unwrap().path)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_read_errors() {
+        let root = project_tree();
+        let source = Arc::new(FlakySource {
+            failures: 2,
+            kind: io::ErrorKind::Interrupted,
+            calls: AtomicU32::new(0),
+        });
+        let opts = WalkOptions {
+            retries: 2,
+            ..Default::default()
+        };
+        let scan = scan_with(root.path().to_path_buf(), opts, source.clone())
+            .await
+            .unwrap();
+        assert_eq!(paths(scan.results), vec!["myproj"]);
+        assert_eq!(source.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_permanent_errors() {
+        let root = project_tree();
+        let source = Arc::new(FlakySource {
+            failures: 1,
+            kind: io::ErrorKind::PermissionDenied,
+            calls: AtomicU32::new(0),
+        });
+        let opts = WalkOptions {
+            retries: 3,
+            ..Default::default()
+        };
+        let scan = scan_with(root.path().to_path_buf(), opts, source.clone())
+            .await
+            .unwrap();
+        assert!(paths(scan.results).is_empty());
+        assert_eq!(source.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_within_home() {
+        let home = Path::new("/home/pete");
+        assert!(within_home(Path::new("/home"), home));
+        assert!(within_home(Path::new("/home/pete"), home));
+        assert!(within_home(Path::new("/home/pete/projects/foo"), home));
+        assert!(!within_home(Path::new("/usr"), home));
+        assert!(!within_home(Path::new("/home/other"), home));
+    }
+
+    #[tokio::test]
+    async fn test_scan_rooted_at_repo_reports_it() {
+        let root = TempDir::new().unwrap();
+        let repo_dir = root.path().join("norigin");
+        std::fs::create_dir_all(repo_dir.join("src").join("nested")).unwrap();
+        git2::Repository::init(&repo_dir).unwrap();
+
+        let scan = scan(repo_dir.clone(), WalkOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(scan.base, root.path());
+        assert_eq!(paths(scan.results), vec!["norigin"]);
+    }
+
+    #[tokio::test]
+    async fn test_scan_rooted_at_marker_reports_it() {
+        let root = TempDir::new().unwrap();
+        let hg = root.path().join("hgproj");
+        std::fs::create_dir_all(hg.join(".hg")).unwrap();
+        std::fs::create_dir_all(hg.join("sub")).unwrap();
+
+        let opts = WalkOptions {
+            markers: vec![".hg".to_string()],
+            ..Default::default()
+        };
+        let scan = scan(hg, opts).await.unwrap();
+        assert_eq!(paths(scan.results), vec!["hgproj"]);
+    }
+
+    #[tokio::test]
+    async fn test_engines_find_same_projects() {
+        let root = TempDir::new().unwrap();
+        for dir in ["a/one", "a/two", "b/c/three", "b/c/d/four"] {
+            let proj = root.path().join(dir);
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        }
+        git2::Repository::init(root.path().join("repo")).unwrap();
+        std::fs::write(root.path().join("repo").join("main.rs"), "fn main() {}\n").unwrap();
+
+        let mut found = Vec::new();
+        for engine in [Engine::Async, Engine::Threads] {
+            let opts = WalkOptions {
+                engine,
+                ..Default::default()
+            };
+            let scan = scan(root.path().to_path_buf(), opts).await.unwrap();
+            found.push(paths(scan.results));
+        }
+        assert_eq!(found[0].len(), 5);
+        assert_eq!(found[0], found[1]);
+    }
+
+    #[tokio::test]
+    async fn test_cachedir_tag_prunes_directory() {
+        let root = TempDir::new().unwrap();
+        for dir in ["cache/proj", "build/proj"] {
+            let proj = root.path().join(dir);
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        }
+        std::fs::write(
+            root.path().join("cache").join(CACHEDIR_TAG),
+            "Signature: 8a477f597d28d172789f06886806bc55\n# a cache\n",
+        )
+        .unwrap();
+        std::fs::write(root.path().join("build").join(".nobackup"), "").unwrap();
+        // Without the signature the tag is not honored; the file makes `fake` a project
+        std::fs::create_dir(root.path().join("fake")).unwrap();
+        std::fs::write(root.path().join("fake").join(CACHEDIR_TAG), "hello").unwrap();
+
+        let opts = WalkOptions {
+            exclude_sentinels: vec![".nobackup".to_string()],
+            ..Default::default()
+        };
+        let scan = scan(root.path().to_path_buf(), opts).await.unwrap();
+        assert_eq!(paths(scan.results), vec!["fake"]);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limits_directory_reads() {
+        let root = TempDir::new().unwrap();
+        for i in 0..14 {
+            std::fs::create_dir(root.path().join(format!("empty{i}"))).unwrap();
+        }
+        // 15 reads (root + 14) at 10/s with a burst of 10: at least 0.5s
+        for engine in [Engine::Async, Engine::Threads] {
+            let opts = WalkOptions {
+                engine,
+                rate: Some(10),
+                ..Default::default()
+            };
+            let start = std::time::Instant::now();
+            scan(root.path().to_path_buf(), opts).await.unwrap();
+            assert!(
+                start.elapsed() >= Duration::from_millis(450),
+                "{engine:?} took {:?}",
+                start.elapsed()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_skips_completed_subtree() {
+        let root = TempDir::new().unwrap();
+        let state = TempDir::new().unwrap();
+        let checkpoint = state.path().join("scan.checkpoint");
+        let make_project = |dir: &str| {
+            let proj = root.path().join(dir);
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        };
+        let opts = |resume| WalkOptions {
+            checkpoint: Some(checkpoint.clone()),
+            resume,
+            ..Default::default()
+        };
+
+        // First run is "interrupted" after subtree a: b did not exist yet
+        make_project("a/one");
+        let first = scan(root.path().to_path_buf(), opts(false)).await.unwrap();
+        assert_eq!(paths(first.results), vec!["a/one"]);
+
+        // A new project in a shows whether a is walked again on resume
+        make_project("a/two");
+        make_project("b/three");
+        for engine in [Engine::Async, Engine::Threads] {
+            let resumed = WalkOptions {
+                engine,
+                ..opts(true)
+            };
+            let scan = scan(root.path().to_path_buf(), resumed).await.unwrap();
+            assert_eq!(paths(scan.results), vec!["a/one", "b/three"], "{engine:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_subtree_is_not_checkpointed() {
+        let root = TempDir::new().unwrap();
+        let state = TempDir::new().unwrap();
+        let checkpoint = state.path().join("scan.checkpoint");
+        for dir in ["a/one", "b/inner/two"] {
+            let proj = root.path().join(dir);
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        }
+        std::fs::create_dir(root.path().join("node_modules")).unwrap();
+        let source = Arc::new(BrokenDirSource {
+            broken: root.path().join("b/inner"),
+        });
+        for engine in [Engine::Async, Engine::Threads] {
+            let opts = WalkOptions {
+                engine,
+                checkpoint: Some(checkpoint.clone()),
+                ..Default::default()
+            };
+            let scan = scan_with(root.path().to_path_buf(), opts, source.clone())
+                .await
+                .unwrap();
+            assert_eq!(paths(scan.results), vec!["a/one"], "{engine:?}");
+
+            let saved = Checkpoint::open(&checkpoint, root.path(), true).unwrap();
+            assert!(saved.is_complete(&root.path().join("a")), "{engine:?}");
+            assert!(!saved.is_complete(&root.path().join("b")), "{engine:?}");
+            assert!(
+                !saved.is_complete(&root.path().join("node_modules")),
+                "{engine:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_depth_one_skips_nested_projects() {
+        let root = TempDir::new().unwrap();
+        for dir in ["top", "group/nested"] {
+            let proj = root.path().join(dir);
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        }
+        for engine in [Engine::Async, Engine::Threads] {
+            let opts = WalkOptions {
+                engine,
+                max_depth: Some(1),
+                ..Default::default()
+            };
+            let scan = scan(root.path().to_path_buf(), opts).await.unwrap();
+            assert_eq!(paths(scan.results), vec!["top"], "{engine:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flat_matches_general_walker_on_repos() {
+        let root = TempDir::new().unwrap();
+        for repo in ["alpha", "beta", "group/nested", "node_modules/dep"] {
+            git2::Repository::init(root.path().join(repo)).unwrap();
+        }
+        for dir in ["notes", "group/docs"] {
+            let proj = root.path().join(dir);
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        }
+        let extract = ExtractOptions {
+            skip_loc: true,
+            ..Default::default()
+        };
+        let general = WalkOptions {
+            max_depth: Some(1),
+            extract: extract.clone(),
+            ..Default::default()
+        };
+        let scan_general = scan(root.path().to_path_buf(), general).await.unwrap();
+        let general_repos: Vec<String> = scan_general
+            .results
+            .into_sorted()
+            .unwrap()
+            .map(|m| m.unwrap())
+            .filter(|m| m.is_git)
+            .map(|m| m.path)
+            .collect();
+        let flat = WalkOptions {
+            flat: true,
+            extract,
+            ..Default::default()
+        };
+        let scan_flat = scan(root.path().to_path_buf(), flat).await.unwrap();
+        assert_eq!(paths(scan_flat.results), general_repos);
+        assert_eq!(general_repos, vec!["alpha", "beta"]);
+    }
+
+    #[tokio::test]
+    async fn test_newer_than_skips_older_subtrees() {
+        let root = TempDir::new().unwrap();
+        let marker = root.path().join("marker");
+        let at = |secs| std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        let touch = |path: &Path, time| {
+            std::fs::File::open(path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap()
+        };
+        for (subtree, project) in [("before", "before/app"), ("after", "after/app")] {
+            let proj = root.path().join(project);
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+            if subtree == "before" {
+                touch(&root.path().join(subtree), at(1_000));
+                std::fs::write(&marker, "").unwrap();
+                touch(&marker, at(2_000));
+            } else {
+                touch(&root.path().join(subtree), at(3_000));
+            }
+        }
+        let newer_than = std::fs::metadata(&marker).unwrap().modified().unwrap();
+        for engine in [Engine::Async, Engine::Threads] {
+            let stats = Arc::new(SkipStats::new());
+            let opts = WalkOptions {
+                engine,
+                newer_than: Some(newer_than),
+                stats: Some(stats.clone()),
+                ..Default::default()
+            };
+            let scan = scan(root.path().to_path_buf(), opts).await.unwrap();
+            assert_eq!(paths(scan.results), vec!["after/app"], "{engine:?}");
+            assert_eq!(stats.count(SkipReason::Unmodified), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deny_file_prunes_matching_dirs() {
+        let root = TempDir::new().unwrap();
+        for dir in [
+            "web/node_modules/left-pad",
+            "web/src",
+            "scratch-1/app",
+            "tools",
+        ] {
+            let proj = root.path().join(dir);
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        }
+        let deny_file = root.path().join("deny.txt");
+        std::fs::write(&deny_file, "# shared skips\nnode_modules\nscratch-*\n").unwrap();
+        let stats = Arc::new(SkipStats::new());
+        let opts = WalkOptions {
+            deny: DenyList::load(&deny_file).unwrap(),
+            stats: Some(stats.clone()),
+            ..Default::default()
+        };
+        let scan = scan(root.path().to_path_buf(), opts).await.unwrap();
+        assert_eq!(paths(scan.results), vec!["tools", "web/src"]);
+        assert_eq!(stats.count(SkipReason::Denied), 2);
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_bad_components() {
+        let root = project_tree();
+        for dir in ["a/target", "b/target"] {
+            std::fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        let stats = Arc::new(SkipStats::new());
+        let opts = WalkOptions {
+            stats: Some(stats.clone()),
+            ..Default::default()
+        };
+        scan(root.path().to_path_buf(), opts).await.unwrap();
+        assert_eq!(stats.count(SkipReason::BadComponent), 2);
+        assert_eq!(stats.count(SkipReason::ProjectBoundary), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stats_count_permission_denied() {
+        let root = project_tree();
+        let source = Arc::new(FlakySource {
+            failures: 1,
+            kind: io::ErrorKind::PermissionDenied,
+            calls: AtomicU32::new(0),
+        });
+        let stats = Arc::new(SkipStats::new());
+        let opts = WalkOptions {
+            stats: Some(stats.clone()),
+            ..Default::default()
+        };
+        scan_with(root.path().to_path_buf(), opts, source)
+            .await
+            .unwrap();
+        assert_eq!(stats.count(SkipReason::PermissionDenied), 1);
+    }
+
+    #[tokio::test]
+    async fn test_count_dirs_matches_examined_dirs() {
+        let root = TempDir::new().unwrap();
+        for dir in ["a/b", "a/c", "proj/src", "target/x"] {
+            std::fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        std::fs::write(root.path().join("proj").join("main.py"), "").unwrap();
+        // a, a/b, a/c, proj and target; proj and target are not descended into
+        let opts = WalkOptions::default();
+        assert_eq!(count_dirs(root.path(), &opts), 5);
+
+        let progress = Arc::new(Progress::new(5));
+        let opts = WalkOptions {
+            progress: Some(progress.clone()),
+            ..Default::default()
+        };
+        scan(root.path().to_path_buf(), opts).await.unwrap();
+        assert_eq!(progress.fraction(), 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_discovery_continues_while_extraction_blocked() {
+        let root = TempDir::new().unwrap();
+        std::fs::create_dir(root.path().join("heavy")).unwrap();
+        std::fs::write(root.path().join("heavy").join("main.rs"), "").unwrap();
+        for dir in ["deep/a/b/c", "deep/d/e"] {
+            std::fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        let source = Arc::new(FlakySource {
+            failures: 0,
+            kind: io::ErrorKind::Other,
+            calls: AtomicU32::new(0),
+        });
+        let opts = WalkOptions {
+            repo_concurrency: Some(1),
+            ..Default::default()
+        };
+        let pool = TaskPool::new(&opts);
+        let walker = Arc::new(Walker::new(
+            root.path().to_path_buf(),
+            opts,
+            source.clone(),
+            None,
+            ExternalSorter::new(None),
+            Some(pool),
+        ));
+        // Stand in for a huge history walk holding the only extraction permit
+        let slow_repo = walker.pool().extract_semaphore.acquire().await.unwrap();
+        spawn_root(&walker, root.path().to_path_buf());
+
+        // root, deep, deep/a, deep/a/b, deep/a/b/c, deep/d, deep/d/e
+        let discovered = async {
+            while source.calls.load(Ordering::SeqCst) < 7 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), discovered)
+            .await
+            .expect("directory discovery stalled behind extraction");
+
+        drop(slow_repo);
+        drain_tasks(&walker).await;
+        let walker = Arc::try_unwrap(walker).ok().unwrap();
+        assert_eq!(paths(walker.results.into_inner().unwrap()), vec!["heavy"]);
+    }
+
+    #[tokio::test]
+    async fn test_wide_tree_reaches_dir_concurrency() {
+        let root = TempDir::new().unwrap();
+        for i in 0..12 {
+            std::fs::create_dir_all(root.path().join(format!("wide/d{i}"))).unwrap();
+        }
+        let source = Arc::new(SlowSource::default());
+        let opts = WalkOptions {
+            dir_concurrency: Some(4),
+            ..Default::default()
+        };
+        scan_with(root.path().to_path_buf(), opts, source.clone())
+            .await
+            .unwrap();
+        assert_eq!(source.peak.load(Ordering::SeqCst), 4);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_adaptive_concurrency_survives_fd_exhaustion() {
+        let root = TempDir::new().unwrap();
+        for i in 0..12 {
+            let proj = root.path().join(format!("d{i}/proj"));
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        }
+        let run = |adaptive_concurrency| {
+            let opts = WalkOptions {
+                dir_concurrency: Some(8),
+                adaptive_concurrency,
+                ..Default::default()
+            };
+            let source = Arc::new(ExhaustingSource {
+                limit: 2,
+                in_flight: AtomicU32::new(0),
+            });
+            scan_with(root.path().to_path_buf(), opts, source)
+        };
+
+        let fixed = run(false).await.unwrap();
+        assert!(fixed.errors > 0);
+
+        let adaptive = run(true).await.unwrap();
+        assert_eq!(adaptive.errors, 0);
+        assert_eq!(paths(adaptive.results).len(), 12);
+    }
+
+    #[tokio::test]
+    async fn test_since_last_scan_recomputes_only_changed_repos() {
+        let root = TempDir::new().unwrap();
+        let state = TempDir::new().unwrap();
+        let state_file = state.path().join("scan.state");
+        let old = std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for name in ["changed", "dirtied", "static"] {
+            let dir = root.path().join(name);
+            let repo = git2::Repository::init(&dir).unwrap();
+            let sig = git2::Signature::now("T", "t@t.com").unwrap();
+            let tree = repo
+                .find_tree(repo.index().unwrap().write_tree().unwrap())
+                .unwrap();
+            repo.commit(Some("HEAD"), &sig, &sig, "first", &tree, &[])
+                .unwrap();
+            // Backdate the git dir so the next commit's ref update is strictly newer
+            for sub in [".git", ".git/refs", ".git/refs/heads", ".git/refs/tags"] {
+                std::fs::File::open(dir.join(sub))
+                    .unwrap()
+                    .set_modified(old)
+                    .unwrap();
+            }
+        }
+        let scan_once = || async {
+            let extract = ExtractOptions {
+                dirty: true,
+                ..Default::default()
+            };
+            let cache = Arc::new(ScanCache::open(&state_file, root.path(), &extract).unwrap());
+            let opts = WalkOptions {
+                since_last_scan: Some(cache.clone()),
+                extract,
+                ..Default::default()
+            };
+            let scan = scan(root.path().to_path_buf(), opts).await.unwrap();
+            cache.save().unwrap();
+            let records: Vec<crate::metadata::ProjectMetadata> = scan
+                .results
+                .into_sorted()
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            (cache.recomputed(), records)
+        };
+
+        let (recomputed, records) = scan_once().await;
+        assert_eq!(recomputed, 3);
+        assert!(records.iter().all(|p| p.is_dirty == Some(false)));
+
+        let repo = git2::Repository::open(root.path().join("changed")).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let sig = git2::Signature::now("T", "t@t.com").unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &sig,
+            &sig,
+            "second",
+            &head.tree().unwrap(),
+            &[&head],
+        )
+        .unwrap();
+        // An untracked file leaves .git alone, so the record is reused but re-checked
+        std::fs::write(root.path().join("dirtied/notes.txt"), "wip").unwrap();
+
+        let (recomputed, records) = scan_once().await;
+        assert_eq!(recomputed, 1);
+        let commits: Vec<(&str, Option<u32>, Option<bool>)> = records
+            .iter()
+            .map(|p| (p.path.as_str(), p.total_commits, p.is_dirty))
+            .collect();
+        assert_eq!(
+            commits,
+            [
+                ("changed", Some(2), Some(false)),
+                ("dirtied", Some(1), Some(true)),
+                ("static", Some(1), Some(false)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_slow_scan_early() {
+        let root = TempDir::new().unwrap();
+        let mut dir = root.path().to_path_buf();
+        for level in 0..40 {
+            dir = dir.join(format!("d{level}"));
+            let proj = dir.join("proj");
+            std::fs::create_dir_all(&proj).unwrap();
+            std::fs::write(proj.join("main.py"), "print('hi')\n").unwrap();
+        }
+        let state = TempDir::new().unwrap();
+        let checkpoint = state.path().join("scan.checkpoint");
+        let cancel = Arc::new(AtomicBool::new(false));
+        let opts = WalkOptions {
+            cancel: Some(cancel.clone()),
+            checkpoint: Some(checkpoint.clone()),
+            ..Default::default()
+        };
+        let timer = {
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+                cancel.store(true, Ordering::Relaxed);
+            })
+        };
+        let started = std::time::Instant::now();
+        let source = Arc::new(SlowSource::default());
+        let scan = scan_with(root.path().to_path_buf(), opts, source)
+            .await
+            .unwrap();
+        timer.await.unwrap();
+        // 40 sequential reads of 50ms each would take 2s
+        assert!(started.elapsed() < Duration::from_millis(1500));
+        assert!(scan.cancelled);
+        let found = paths(scan.results);
+        assert!(!found.is_empty() && found.len() < 40, "{found:?}");
+        assert!(found.contains(&"d0/proj".to_string()));
+        // The cut-short subtree is walked again on --resume
+        let saved = Checkpoint::open(&checkpoint, root.path(), true).unwrap();
+        assert!(!saved.is_complete(&root.path().join("d0")));
+    }
+
+    #[tokio::test]
+    async fn test_uncancelled_scan_is_complete() {
+        let root = project_tree();
+        for engine in [Engine::Async, Engine::Threads] {
+            let opts = WalkOptions {
+                engine,
+                cancel: Some(Arc::new(AtomicBool::new(false))),
+                ..Default::default()
+            };
+            let scan = scan(root.path().to_path_buf(), opts).await.unwrap();
+            assert!(!scan.cancelled, "{engine:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_live_records_and_cancel() {
+        let root = project_tree();
+        let (tx, rx) = mpsc::channel();
+        let opts = WalkOptions {
+            live: Some(tx),
+            ..Default::default()
+        };
+        let scan = scan(root.path().to_path_buf(), opts).await.unwrap();
+        let live: Vec<String> = rx.iter().map(|p| p.path).collect();
+        assert_eq!(live, paths(scan.results));
+
+        let opts = WalkOptions {
+            cancel: Some(Arc::new(AtomicBool::new(true))),
+            ..Default::default()
+        };
+        let scan = super::scan(root.path().to_path_buf(), opts).await.unwrap();
+        assert!(paths(scan.results).is_empty());
+    }
+}
@@ -12,6 +12,12 @@ use tempfile::TempDir;
 /// `init.defaultBranch` setting.
 fn init_repo_with_commits(path: &Path, commit_times: &[i64]) -> Repository {
     let repo = Repository::init(path).expect("init repo");
+    add_commits(&repo, commit_times);
+    repo
+}
+
+/// Chains one commit per entry in `commit_times` onto `refs/heads/main` of `repo`.
+fn add_commits(repo: &Repository, commit_times: &[i64]) {
     {
         // Block ensures tree and parent_commit borrows are dropped before repo is returned.
         let tree_oid = repo
@@ -41,7 +47,6 @@ fn init_repo_with_commits(path: &Path, commit_times: &[i64]) -> Repository {
             parent_oid = Some(oid);
         }
     }
-    repo
 }
 
 fn format_date(secs: i64) -> String {
@@ -403,6 +408,1452 @@ fn test_discover_from_inside_repo() {
     let json: serde_json::Value = serde_json::from_str(&stdout).expect("valid JSON");
     let arr = json.as_array().unwrap();
     assert_eq!(arr.len(), 1, "expected exactly one project, got:\n{stdout}");
-    assert_eq!(arr[0]["name"], "myrepo", "should report repo root, not src subdir");
+    assert_eq!(
+        arr[0]["name"], "myrepo",
+        "should report repo root, not src subdir"
+    );
     assert_eq!(arr[0]["is_git"], true);
 }
+
+#[test]
+fn test_only_detached() {
+    let root = TempDir::new().unwrap();
+    let on_branch = root.path().join("onbranch");
+    std::fs::create_dir(&on_branch).unwrap();
+    std::fs::write(on_branch.join("main.rs"), "fn main() {}").unwrap();
+    let repo = init_repo_with_commits(&on_branch, &[1_700_000_000]);
+    repo.set_head("refs/heads/main").unwrap();
+
+    let detached = root.path().join("detached");
+    std::fs::create_dir(&detached).unwrap();
+    std::fs::write(detached.join("main.rs"), "fn main() {}").unwrap();
+    let repo = init_repo_with_commits(&detached, &[1_700_000_000, 1_700_100_000]);
+    let oid = repo.refname_to_id("refs/heads/main").unwrap();
+    repo.set_head_detached(oid).unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--only-detached", "--json"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&stdout).expect("valid JSON");
+    let arr = json.as_array().unwrap();
+    assert_eq!(
+        arr.len(),
+        1,
+        "expected only the detached repo, got:\n{stdout}"
+    );
+    assert_eq!(arr[0]["name"], "detached");
+    assert_eq!(arr[0]["head_detached"], true);
+}
+
+#[test]
+fn test_show_upstream_default_from_origin_head() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("cloned");
+    std::fs::create_dir(&repo_dir).unwrap();
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    let repo = init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    repo.remote("origin", "https://example.com/cloned.git")
+        .unwrap();
+    let oid = repo.refname_to_id("refs/heads/main").unwrap();
+    repo.reference("refs/remotes/origin/main", oid, false, "fetch")
+        .unwrap();
+    repo.reference_symbolic(
+        "refs/remotes/origin/HEAD",
+        "refs/remotes/origin/main",
+        false,
+        "clone",
+    )
+    .unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--show-upstream-default"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next(),
+        Some("repository,oldest,newest,count,upstream_default")
+    );
+    let row = lines
+        .find(|l| l.starts_with("cloned,"))
+        .expect("cloned row present");
+    assert!(row.ends_with(",main"), "expected upstream main, got: {row}");
+}
+
+#[test]
+fn test_sort_buffer_spills_and_keeps_order() {
+    let root = TempDir::new().unwrap();
+    for name in ["delta", "alpha", "echo", "charlie", "bravo"] {
+        let repo_dir = root.path().join(name);
+        std::fs::create_dir(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+        init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    }
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--sort-buffer", "2"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let names: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').next().unwrap())
+        .collect();
+    assert_eq!(names, vec!["alpha", "bravo", "charlie", "delta", "echo"]);
+}
+
+#[test]
+fn test_home_only_prunes_outside_home() {
+    let root = TempDir::new().unwrap();
+    let home = root.path().join("home");
+    for repo_dir in [home.join("inside"), root.path().join("outside")] {
+        std::fs::create_dir_all(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+        init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lsproj"))
+        .arg(root.path())
+        .args(["--csv", "--home-only"])
+        .env("HOME", &home)
+        .output()
+        .expect("run lsproj");
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.lines().any(|l| l.starts_with("home/inside,")),
+        "repo under $HOME should be reported, got:\n{stdout}"
+    );
+    assert!(
+        !stdout.contains("outside"),
+        "repo outside $HOME should be pruned, got:\n{stdout}"
+    );
+}
+
+#[test]
+fn test_json_pretty_and_jsonl() {
+    let root = TempDir::new().unwrap();
+    for name in ["alpha", "bravo"] {
+        let repo_dir = root.path().join(name);
+        std::fs::create_dir(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+        init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    }
+
+    let compact = run_lsproj_with_args(root.path(), &["--json", "--compact"]);
+    let compact = String::from_utf8(compact.stdout).unwrap();
+    assert_eq!(compact.trim_end().lines().count(), 1, "got:\n{compact}");
+
+    for args in [&["--json"][..], &["--json", "--pretty"]] {
+        let pretty = run_lsproj_with_args(root.path(), args);
+        assert!(pretty.status.success(), "lsproj failed: {:?}", pretty);
+        let pretty = String::from_utf8(pretty.stdout).unwrap();
+        assert!(
+            pretty.contains("\n  {"),
+            "expected indentation by default, got:\n{pretty}"
+        );
+        let json: serde_json::Value = serde_json::from_str(&pretty).expect("valid JSON");
+        assert_eq!(json.as_array().unwrap().len(), 2);
+    }
+
+    let jsonl = run_lsproj_with_args(root.path(), &["--jsonl"]);
+    let jsonl = String::from_utf8(jsonl.stdout).unwrap();
+    let names: Vec<String> = jsonl
+        .lines()
+        .map(|l| {
+            serde_json::from_str::<serde_json::Value>(l).expect("valid JSON line")["name"]
+                .to_string()
+        })
+        .collect();
+    assert_eq!(names, vec!["\"alpha\"", "\"bravo\""]);
+
+    let rejected = run_lsproj_with_args(root.path(), &["--jsonl", "--pretty"]);
+    assert!(
+        !rejected.status.success(),
+        "--pretty with --jsonl should be rejected"
+    );
+}
+
+#[test]
+fn test_only_interrupted_reports_merge_state() {
+    let root = TempDir::new().unwrap();
+    let clean = root.path().join("clean");
+    std::fs::create_dir(&clean).unwrap();
+    std::fs::write(clean.join("main.rs"), "fn main() {}").unwrap();
+    init_repo_with_commits(&clean, &[1_700_000_000]);
+
+    let merging = root.path().join("merging");
+    std::fs::create_dir(&merging).unwrap();
+    std::fs::write(merging.join("main.rs"), "fn main() {}").unwrap();
+    let repo = init_repo_with_commits(&merging, &[1_700_000_000]);
+    let oid = repo.refname_to_id("refs/heads/main").unwrap();
+    std::fs::write(repo.path().join("MERGE_HEAD"), format!("{oid}\n")).unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--only-interrupted"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "repository,oldest,newest,count,state");
+    assert_eq!(
+        lines.len(),
+        2,
+        "expected only the merging repo, got:\n{stdout}"
+    );
+    assert!(lines[1].starts_with("merging,"));
+    assert!(lines[1].ends_with(",merge"), "got: {}", lines[1]);
+}
+
+#[test]
+fn test_min_age_reports_only_stale_repos() {
+    let root = TempDir::new().unwrap();
+    let now = chrono::Utc::now().timestamp();
+    let stale = root.path().join("stale");
+    let fresh = root.path().join("fresh");
+    for (dir, when) in [(&stale, now - 400 * 86_400), (&fresh, now - 86_400)] {
+        std::fs::create_dir(dir).unwrap();
+        std::fs::write(dir.join("main.rs"), "fn main() {}").unwrap();
+        init_repo_with_commits(dir, &[when]);
+    }
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--min-age", "1y"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("stale,"), "stale repo missing:\n{stdout}");
+    assert!(!stdout.contains("fresh,"), "fresh repo reported:\n{stdout}");
+}
+
+#[test]
+fn test_trusted_remote_excludes_mirrored_repos() {
+    let root = TempDir::new().unwrap();
+    for (name, url) in [
+        ("mirrored", "ssh://git@backup.example.com/srv/mirrored.git"),
+        ("elsewhere", "https://git.example.org/elsewhere.git"),
+    ] {
+        let repo_dir = root.path().join(name);
+        std::fs::create_dir(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+        let repo = init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+        repo.remote("origin", url).unwrap();
+    }
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--csv", "--trusted-remote", "backup.example.com"],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        !stdout.contains("mirrored,"),
+        "trusted repo reported:\n{stdout}"
+    );
+    assert!(
+        stdout.contains("elsewhere,"),
+        "untrusted repo missing:\n{stdout}"
+    );
+}
+
+#[test]
+fn test_emit_script_quotes_paths_with_spaces() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("my repo");
+    std::fs::create_dir(&repo_dir).unwrap();
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+
+    let output = run_lsproj_with_args(root.path(), &["--emit-script", "pete"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let canonical = std::fs::canonicalize(&repo_dir).unwrap();
+    assert!(
+        stdout.starts_with("#!/usr/bin/env bash\n"),
+        "got:\n{stdout}"
+    );
+    assert!(stdout.contains("set -euo pipefail"));
+    assert!(stdout.contains("gh repo create 'pete/my-repo' --private"));
+    let remote_add = format!(
+        "git -C '{}' remote add origin 'git@github.com:pete/my-repo.git'",
+        canonical.display()
+    );
+    assert!(
+        stdout.contains(&remote_add),
+        "missing {remote_add:?} in:\n{stdout}"
+    );
+}
+
+#[test]
+fn test_show_language_reports_dominant_extension() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("rusty");
+    std::fs::create_dir_all(repo_dir.join("src")).unwrap();
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    for name in ["a.rs", "b.rs", "c.rs"] {
+        std::fs::write(repo_dir.join("src").join(name), "").unwrap();
+    }
+    std::fs::write(repo_dir.join("notes.txt"), "").unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--show-language"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next(),
+        Some("repository,oldest,newest,count,language")
+    );
+    let row = lines.find(|l| l.starts_with("rusty,")).expect("rusty row");
+    assert!(row.ends_with(",rs"), "got: {row}");
+}
+
+#[test]
+fn test_repo_marker_reports_hg_checkout() {
+    let root = TempDir::new().unwrap();
+    let hg = root.path().join("hgproj");
+    std::fs::create_dir_all(hg.join(".hg")).unwrap();
+
+    let stdout = run_lsproj(root.path());
+    assert!(!stdout.contains("hgproj"), "unexpected hgproj:\n{stdout}");
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--repo-marker", ".hg"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.lines().any(|l| l == "hgproj,,,0"),
+        "expected hgproj row, got:\n{stdout}"
+    );
+}
+
+#[test]
+fn test_count_only_prints_number() {
+    let root = TempDir::new().unwrap();
+    for name in ["one", "two", "three"] {
+        let repo_dir = root.path().join("group").join(name);
+        std::fs::create_dir_all(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+        init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    }
+
+    let output = run_lsproj_with_args(root.path(), &["--count-only"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+}
+
+#[test]
+fn test_relative_to_modes() {
+    let home = TempDir::new().unwrap();
+    let home_path = home.path().canonicalize().unwrap();
+    let repo_dir = home_path.join("projects").join("foo").join("bar");
+    std::fs::create_dir_all(&repo_dir).unwrap();
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+
+    let first_column = |mode: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_lsproj"))
+            .current_dir(&home_path)
+            .args(["projects", "--csv", "--relative-to", mode])
+            .output()
+            .expect("run lsproj");
+        assert!(output.status.success(), "lsproj failed: {:?}", output);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split(',')
+            .next()
+            .unwrap()
+            .to_string()
+    };
+
+    assert_eq!(first_column("root"), "foo/bar");
+    assert_eq!(first_column("cwd"), "projects/foo/bar");
+    assert_eq!(first_column("absolute"), repo_dir.display().to_string());
+}
+
+#[test]
+fn test_min_pack_size_filters_on_history_size() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("bloated");
+    std::fs::create_dir(&repo_dir).unwrap();
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    let pack_dir = repo_dir.join(".git").join("objects").join("pack");
+    std::fs::create_dir_all(&pack_dir).unwrap();
+    std::fs::write(pack_dir.join("pack-test.pack"), vec![0u8; 10_000]).unwrap();
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--csv", "--min-pack-size", "9999", "--show-pack-size"],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("repository,oldest,newest,count,pack_size\n"));
+    assert!(
+        stdout
+            .lines()
+            .any(|l| l.starts_with("bloated,") && l.ends_with(",10000")),
+        "expected bloated row, got:\n{stdout}"
+    );
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--min-pack-size", "10000"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("bloated"), "unexpected bloated:\n{stdout}");
+}
+
+#[test]
+fn test_show_dirty_header_matches_rows() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("wip");
+    std::fs::create_dir(&repo_dir).unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--show-dirty"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+    assert_eq!(header, ["repository", "oldest", "newest", "count", "dirty"]);
+    let row: Vec<&str> = lines.next().unwrap().split(',').collect();
+    assert_eq!(row.len(), header.len());
+    assert_eq!(row[0], "wip");
+    assert_eq!(row[4], "yes");
+}
+
+#[test]
+fn test_last_author_selects_by_newest_committer() {
+    let root = TempDir::new().unwrap();
+    for (name, email) in [
+        ("departed", "pat@oldco.com"),
+        ("active", "sam@newco.com"),
+        ("handed-over", "sam@newco.com"),
+    ] {
+        let repo_dir = root.path().join(name);
+        std::fs::create_dir(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+        let repo = init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+        // handed-over was last touched by pat before sam's newer commit
+        let emails: &[&str] = if name == "handed-over" {
+            &["pat@oldco.com", email]
+        } else {
+            &[email]
+        };
+        for (i, email) in emails.iter().enumerate() {
+            let sig =
+                Signature::new("Dev", email, &Time::new(1_700_100_000 + i as i64, 0)).unwrap();
+            let parent_oid = repo.refname_to_id("refs/heads/main").unwrap();
+            let parent = repo.find_commit(parent_oid).unwrap();
+            let tree = parent.tree().unwrap();
+            repo.commit(
+                Some("refs/heads/main"),
+                &sig,
+                &sig,
+                "work",
+                &tree,
+                &[&parent],
+            )
+            .unwrap();
+        }
+    }
+
+    for pattern in ["PAT@oldco.com", r"/@oldco\.com$/"] {
+        let output = run_lsproj_with_args(root.path(), &["--csv", "--last-author", pattern]);
+        assert!(output.status.success(), "lsproj failed: {:?}", output);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let repos: Vec<&str> = stdout
+            .lines()
+            .skip(1)
+            .map(|l| l.split(',').next().unwrap())
+            .collect();
+        assert_eq!(repos, ["departed"], "pattern {pattern}");
+    }
+}
+
+#[test]
+fn test_output_gz_is_valid_csv() {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("myrepo");
+    std::fs::create_dir(&repo_dir).unwrap();
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    let out_dir = TempDir::new().unwrap();
+    let out_path = out_dir.path().join("inventory.csv.gz");
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--csv", "--output", out_path.to_str().unwrap()],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    assert!(output.stdout.is_empty());
+
+    let mut csv = String::new();
+    GzDecoder::new(std::fs::File::open(&out_path).unwrap())
+        .read_to_string(&mut csv)
+        .unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "repository,oldest,newest,count");
+    assert!(lines[1].starts_with("myrepo,"), "got:\n{csv}");
+}
+
+#[test]
+fn test_gc_candidates_flags_bloated_git_dir() {
+    let root = TempDir::new().unwrap();
+    for name in ["bloated", "lean"] {
+        let repo_dir = root.path().join(name);
+        std::fs::create_dir(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("main.rs"), vec![b'/'; 10_000]).unwrap();
+        init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    }
+    let pack_dir = root.path().join("bloated/.git/objects/pack");
+    std::fs::create_dir_all(&pack_dir).unwrap();
+    std::fs::write(pack_dir.join("pack-pad.pack"), vec![0u8; 200_000]).unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--gc-candidates"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        "repository,oldest,newest,count,git_size,worktree_size"
+    );
+    let rows: Vec<&str> = lines.collect();
+    assert_eq!(rows.len(), 1, "got:\n{stdout}");
+    assert!(rows[0].starts_with("bloated,"));
+    assert!(rows[0].ends_with(",10000"));
+}
+
+#[test]
+fn test_no_recurse_reports_only_direct_children() {
+    let root = TempDir::new().unwrap();
+    for dir in ["direct", "group/nested"] {
+        let repo_dir = root.path().join(dir);
+        std::fs::create_dir_all(&repo_dir).unwrap();
+        std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+        init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    }
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--no-recurse"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.lines().any(|l| l.starts_with("direct,")),
+        "got:\n{stdout}"
+    );
+    assert!(
+        !stdout.contains("nested"),
+        "unexpected nested repo:\n{stdout}"
+    );
+}
+
+#[test]
+fn test_bare_mirrors_reports_each_mirror() {
+    let root = TempDir::new().unwrap();
+    for (name, commits) in [("alpha.git", 2), ("beta.git", 3)] {
+        let repo = Repository::init_bare(root.path().join(name)).unwrap();
+        let times: Vec<i64> = (0..commits).map(|i| 1_700_000_000 + i * 60).collect();
+        add_commits(&repo, &times);
+    }
+    // Not a mirror: wrong suffix, and a non-bare directory with the suffix
+    Repository::init_bare(root.path().join("gamma")).unwrap();
+    std::fs::create_dir(root.path().join("notes.git")).unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--bare-mirrors", "--jsonl"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let records: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    let summary: Vec<(&str, u64)> = records
+        .iter()
+        .map(|r| {
+            (
+                r["path"].as_str().unwrap(),
+                r["total_commits"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(summary, [("alpha", 2), ("beta", 3)]);
+}
+
+#[test]
+fn test_author_local_uses_commit_timezone() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("faraway");
+    std::fs::create_dir(&repo_dir).unwrap();
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    let repo = Repository::init(&repo_dir).unwrap();
+    let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+    let tree = repo.find_tree(tree_oid).unwrap();
+    // 2023-11-14T22:13:20Z, recorded at UTC+14 (already the 15th there)
+    let sig = Signature::new("T", "t@t.com", &Time::new(1_700_000_000, 14 * 60)).unwrap();
+    repo.commit(Some("refs/heads/main"), &sig, &sig, "t", &tree, &[])
+        .unwrap();
+
+    let row = |extra: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_lsproj"))
+            .arg(root.path())
+            .arg("--csv")
+            .args(extra)
+            .env("TZ", "UTC")
+            .output()
+            .expect("run lsproj");
+        assert!(output.status.success(), "lsproj failed: {:?}", output);
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .nth(1)
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(row(&[]), "faraway,23-11-14,23-11-14,1");
+    assert_eq!(row(&["--author-local"]), "faraway,23-11-15,23-11-15,1");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_no_canonicalize_keeps_symlinked_root() {
+    let real = TempDir::new().unwrap();
+    let repo_dir = real.path().join("myrepo");
+    std::fs::create_dir(&repo_dir).unwrap();
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    let links = TempDir::new().unwrap();
+    let link = links.path().join("projects-link");
+    std::os::unix::fs::symlink(real.path(), &link).unwrap();
+
+    let first_column = |extra: &[&str]| {
+        let output = run_lsproj_with_args(
+            &link,
+            &[&["--csv", "--relative-to", "absolute"], extra].concat(),
+        );
+        assert!(output.status.success(), "lsproj failed: {:?}", output);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        stdout
+            .lines()
+            .nth(1)
+            .unwrap()
+            .split(',')
+            .next()
+            .unwrap()
+            .to_string()
+    };
+
+    let canonical = real.path().canonicalize().unwrap().join("myrepo");
+    assert_eq!(first_column(&[]), canonical.display().to_string());
+    assert_eq!(
+        first_column(&["--no-canonicalize"]),
+        link.join("myrepo").display().to_string()
+    );
+}
+
+#[test]
+fn test_show_head_matches_repo_head() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("proj");
+    std::fs::create_dir(&repo_dir).unwrap();
+    let repo = init_repo_with_commits(&repo_dir, &[1_700_000_000, 1_700_100_000]);
+    repo.set_head("refs/heads/main").unwrap();
+    let head = repo.head().unwrap().target().unwrap().to_string();
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--show-head"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let row: Vec<&str> = stdout.lines().nth(1).unwrap().split(',').collect();
+    assert_eq!(row[4], &head[..7]);
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--show-head", "--full-hash"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout
+            .lines()
+            .nth(1)
+            .unwrap()
+            .ends_with(&format!(",{head}"))
+    );
+}
+
+#[test]
+fn test_unreleased_skips_tagged_repos() {
+    let root = TempDir::new().unwrap();
+    let times: Vec<i64> = (0..4).map(|i| 1_700_000_000 + i * 60).collect();
+    for name in ["tagged", "untagged", "tiny"] {
+        std::fs::create_dir(root.path().join(name)).unwrap();
+    }
+    let tagged = init_repo_with_commits(&root.path().join("tagged"), &times);
+    let tip = tagged.refname_to_id("refs/heads/main").unwrap();
+    let target = tagged.find_object(tip, None).unwrap();
+    tagged.tag_lightweight("v0.1.0", &target, false).unwrap();
+    init_repo_with_commits(&root.path().join("untagged"), &times);
+    init_repo_with_commits(&root.path().join("tiny"), &times[..2]);
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--csv", "--unreleased", "--unreleased-min-commits", "3"],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let repos: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').next().unwrap())
+        .collect();
+    assert_eq!(repos, ["untagged"]);
+}
+
+#[test]
+fn test_very_verbose_lists_slowest_projects() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("history");
+    std::fs::create_dir(&repo_dir).unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "-vv"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("slowest projects:"), "stderr: {stderr}");
+    assert!(stderr.contains("history"), "stderr: {stderr}");
+
+    let quiet = run_lsproj_with_args(root.path(), &["--csv", "-v"]);
+    assert!(!String::from_utf8(quiet.stderr).unwrap().contains("slowest"));
+}
+
+#[test]
+fn test_submodule_checkout_skipped_unless_included() {
+    let tmp = TempDir::new().unwrap();
+    let scan_root = tmp.path().join("scan");
+    let dep = scan_root.join("dep");
+    std::fs::create_dir_all(&dep).unwrap();
+    // The superproject lives outside the scan; its module dir backs the checkout
+    let module_dir = tmp
+        .path()
+        .join("parent")
+        .join(".git")
+        .join("modules")
+        .join("dep");
+    std::fs::create_dir_all(&module_dir).unwrap();
+    let repo = Repository::init_bare(&module_dir).unwrap();
+    repo.set_workdir(&dep, false).unwrap();
+    add_commits(&repo, &[1_700_000_000]);
+    std::fs::write(dep.join(".git"), "gitdir: ../../parent/.git/modules/dep\n").unwrap();
+    std::fs::write(dep.join("lib.rs"), "").unwrap();
+
+    let stdout = run_lsproj(&scan_root);
+    assert!(!stdout.contains("dep"), "got:\n{stdout}");
+
+    let output = run_lsproj_with_args(&scan_root, &["--csv", "--include-dependency-checkouts"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.lines().any(|l| l.starts_with("dep,")),
+        "got:\n{stdout}"
+    );
+}
+
+#[test]
+fn test_columns_select_and_order() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("proj");
+    std::fs::create_dir(&repo_dir).unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000, 1_700_100_000]);
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--columns", "count,repository"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines, ["count,repository", "2,proj"]);
+
+    let output = run_lsproj_with_args(root.path(), &["--columns", "count,nope"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("nope"));
+}
+
+#[test]
+fn test_dedup_by_origin_reports_first_clone() {
+    let root = TempDir::new().unwrap();
+    for (name, url) in [
+        ("a-clone", "git@github.com:pete/tool.git"),
+        ("b-clone", "https://github.com/pete/tool"),
+        ("other", "https://github.com/pete/other.git"),
+    ] {
+        let dir = root.path().join(name);
+        std::fs::create_dir(&dir).unwrap();
+        let repo = init_repo_with_commits(&dir, &[1_700_000_000]);
+        repo.remote("origin", url).unwrap();
+    }
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--dedup-by-origin"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let repos: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').next().unwrap())
+        .collect();
+    assert_eq!(repos, ["a-clone", "other"]);
+}
+
+#[test]
+fn test_name_base_parent_prefixes_root_name() {
+    let tmp = TempDir::new().unwrap();
+    let root = tmp.path().join("projects");
+    let repo_dir = root.join("foo");
+    std::fs::create_dir_all(&repo_dir).unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+
+    let output = run_lsproj_with_args(&root, &["--csv", "--name-base", "parent"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().nth(1).unwrap().starts_with("projects/foo,"));
+
+    // A repo at the root itself still gets a non-empty name
+    let output = run_lsproj_with_args(&repo_dir, &["--csv", "--name-base", "parent"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().nth(1).unwrap().starts_with("foo,"));
+}
+
+#[test]
+fn test_create_requires_confirm_or_dry_run() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("local");
+    std::fs::create_dir(&repo_dir).unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--create", "pete"]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("--confirm")
+    );
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--create", "pete", "--dry-run"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("gh 'repo' 'create' 'pete/local' '--private'"),
+        "{stderr}"
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().nth(1).unwrap().ends_with(",would create"));
+}
+
+#[test]
+fn test_truly_unbacked_ignores_pushed_repos() {
+    let root = TempDir::new().unwrap();
+    for name in ["pushed", "ahead"] {
+        let dir = root.path().join(name);
+        std::fs::create_dir(&dir).unwrap();
+        let repo = init_repo_with_commits(&dir, &[1_700_000_000]);
+        repo.set_head("refs/heads/main").unwrap();
+        repo.remote("origin", &format!("https://example.com/{name}.git"))
+            .unwrap();
+        let tip = repo.refname_to_id("refs/heads/main").unwrap();
+        repo.reference("refs/remotes/origin/main", tip, false, "fetch")
+            .unwrap();
+    }
+    // One more local commit puts `ahead` past its remote
+    let ahead = Repository::open(root.path().join("ahead")).unwrap();
+    let tip = ahead.refname_to_id("refs/heads/main").unwrap();
+    let old = ahead.find_commit(tip).unwrap();
+    let sig = Signature::new("Test", "test@example.com", &Time::new(1_700_200_000, 0)).unwrap();
+    ahead
+        .commit(
+            Some("HEAD"),
+            &sig,
+            &sig,
+            "local",
+            &old.tree().unwrap(),
+            &[&old],
+        )
+        .unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--truly-unbacked"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let repos: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').next().unwrap())
+        .collect();
+    assert_eq!(repos, ["ahead"]);
+}
+
+#[test]
+fn test_message_matches_selects_repos_by_commit_message() {
+    let root = TempDir::new().unwrap();
+    for (name, message) in [("wip", "WIP: parser"), ("clean", "Add parser")] {
+        let dir = root.path().join(name);
+        std::fs::create_dir(&dir).unwrap();
+        let repo = Repository::init(&dir).unwrap();
+        let sig = Signature::new("Test", "test@example.com", &Time::new(1_700_000_000, 0)).unwrap();
+        let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree_oid).unwrap();
+        repo.commit(Some("refs/heads/main"), &sig, &sig, message, &tree, &[])
+            .unwrap();
+    }
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &[
+            "--csv",
+            "--message-matches",
+            "(?i)^wip",
+            "--only-matching-commits",
+        ],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{stdout}");
+    assert!(lines[0].ends_with(",matching_commits"));
+    assert!(lines[1].starts_with("wip,") && lines[1].ends_with(",1"));
+}
+
+#[test]
+fn test_split_by_top_writes_file_per_group() {
+    let root = TempDir::new().unwrap();
+    for dir in ["team-a/one", "team-a/two", "team-b/three"] {
+        let repo_dir = root.path().join(dir);
+        std::fs::create_dir_all(&repo_dir).unwrap();
+        init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+        std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    }
+    let out = TempDir::new().unwrap();
+    let split_dir = out.path().join("by-team");
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--split-by-top", split_dir.to_str().unwrap()],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stdout.is_empty());
+
+    let team_a = std::fs::read_to_string(split_dir.join("team-a.csv")).unwrap();
+    let rows: Vec<&str> = team_a.lines().skip(1).collect();
+    assert_eq!(rows.len(), 2);
+    assert!(rows.iter().all(|r| r.starts_with("team-a/")));
+    let team_b = std::fs::read_to_string(split_dir.join("team-b.csv")).unwrap();
+    assert_eq!(
+        team_b.lines().next(),
+        Some("repository,oldest,newest,count")
+    );
+    assert!(team_b.lines().nth(1).unwrap().starts_with("team-b/three,"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_non_utf8_directory_names() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let root = TempDir::new().unwrap();
+    let group = root.path().join(OsStr::from_bytes(b"caf\xe9"));
+    let repo_dir = group.join("app");
+    std::fs::create_dir_all(&repo_dir).unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    std::fs::write(repo_dir.join("main.rs"), "fn main() {}").unwrap();
+    // Skipped by suffix even though the name isn't UTF-8
+    let skipped = root.path().join(OsStr::from_bytes(b"\xff.xcodeproj"));
+    std::fs::create_dir_all(&skipped).unwrap();
+    init_repo_with_commits(&skipped, &[1_700_000_000]);
+
+    let output = run_lsproj_with_args(root.path(), &["--jsonl"]);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let paths: Vec<String> = stdout
+        .lines()
+        .map(|l| {
+            serde_json::from_str::<serde_json::Value>(l).unwrap()["path"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(paths, ["caf\u{FFFD}/app"]);
+}
+
+#[test]
+fn test_enrich_github_columns_from_mock_api() {
+    use std::io::{BufRead, BufReader, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let api = format!("http://{}", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 2 {
+            line.clear();
+        }
+        let body =
+            r#"{"description":"Mocked, with comma","topics":["cli"],"visibility":"private"}"#;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .unwrap();
+    });
+
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("tool");
+    std::fs::create_dir(&repo_dir).unwrap();
+    let repo = init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    repo.remote("origin", "git@github.com:pete/tool.git")
+        .unwrap();
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &[
+            "--csv",
+            "--columns",
+            "repository,description,visibility",
+            "--enrich-github",
+            "--github-api-url",
+            &api,
+        ],
+    );
+    assert!(output.status.success(), "{output:?}");
+    server.join().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("repository,description,visibility"));
+    assert_eq!(lines.next(), Some("tool,\"Mocked, with comma\",private"));
+}
+
+#[test]
+fn test_latest_per_group_keeps_newest_repo() {
+    let root = TempDir::new().unwrap();
+    for (name, secs) in [
+        ("work/older", 1_700_000_000),
+        ("work/newer", 1_700_100_000),
+        ("play/only", 1_600_000_000),
+    ] {
+        let dir = root.path().join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        init_repo_with_commits(&dir, &[secs]);
+    }
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--latest-per-group"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let repos: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').next().unwrap())
+        .collect();
+    assert_eq!(repos, ["play/only", "work/newer"]);
+}
+
+#[test]
+fn test_only_behind_reports_repos_needing_pull() {
+    let root = TempDir::new().unwrap();
+    for (name, behind) in [("stale", true), ("current", false)] {
+        let dir = root.path().join(name);
+        std::fs::create_dir(&dir).unwrap();
+        let repo = init_repo_with_commits(&dir, &[1_700_000_000, 1_700_000_100]);
+        repo.remote("origin", "https://example.com/x.git").unwrap();
+        // As if fetched: origin/main at the newest commit, local main maybe one behind
+        let tip = repo.refname_to_id("refs/heads/main").unwrap();
+        repo.reference("refs/remotes/origin/main", tip, true, "fetch")
+            .unwrap();
+        if behind {
+            let parent = repo.find_commit(tip).unwrap().parent_id(0).unwrap();
+            repo.reference("refs/heads/main", parent, true, "reset")
+                .unwrap();
+        }
+        repo.find_branch("main", git2::BranchType::Local)
+            .unwrap()
+            .set_upstream(Some("origin/main"))
+            .unwrap();
+    }
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--csv", "--columns", "repository,behind", "--only-behind"],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout, "repository,behind\nstale,1\n");
+}
+
+#[test]
+fn test_only_stub_reports_uncommitted_inits() {
+    let root = TempDir::new().unwrap();
+    let stub = root.path().join("stub");
+    std::fs::create_dir(&stub).unwrap();
+    Repository::init(&stub).unwrap();
+    let real = root.path().join("real");
+    std::fs::create_dir(&real).unwrap();
+    init_repo_with_commits(&real, &[1_700_000_000]);
+    let plain = root.path().join("plain");
+    std::fs::create_dir(&plain).unwrap();
+    std::fs::write(plain.join("notes.txt"), "hi").unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--only-stub"]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let repos: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').next().unwrap())
+        .collect();
+    assert_eq!(repos, ["stub"]);
+}
+
+#[test]
+fn test_diff_against_reports_added_and_removed() {
+    let root = TempDir::new().unwrap();
+    for name in ["kept", "fresh"] {
+        let dir = root.path().join(name);
+        std::fs::create_dir(&dir).unwrap();
+        init_repo_with_commits(&dir, &[1_700_000_000]);
+    }
+    let prior_dir = TempDir::new().unwrap();
+    let prior = prior_dir.path().join("last.csv");
+    std::fs::write(
+        &prior,
+        "repository,oldest,newest,count\nkept,,,1\ngone,,,0\n",
+    )
+    .unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--diff-against", prior.to_str().unwrap()]);
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "added (1):\n  fresh\nremoved (1):\n  gone\nchanged (0):\n"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_filter_cmd_keeps_accepted_projects() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = TempDir::new().unwrap();
+    for name in ["keep-me", "drop-me"] {
+        let dir = root.path().join(name);
+        std::fs::create_dir(&dir).unwrap();
+        init_repo_with_commits(&dir, &[1_700_000_000]);
+    }
+    let bin = TempDir::new().unwrap();
+    let script = bin.path().join("only-keep.sh");
+    std::fs::write(
+        &script,
+        "#!/bin/sh\ncase \"$1\" in */keep-*) exit 0 ;; *) exit 1 ;; esac\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--csv", "--filter-cmd", script.to_str().unwrap()],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let repos: Vec<&str> = stdout
+        .lines()
+        .skip(1)
+        .map(|l| l.split(',').next().unwrap())
+        .collect();
+    assert_eq!(repos, ["keep-me"]);
+}
+
+#[test]
+fn test_normalize_origin_reports_comparable_urls() {
+    let root = TempDir::new().unwrap();
+    for (name, url) in [
+        ("ssh-clone", "git@github.com:pete/tool.git"),
+        ("https-clone", "https://GitHub.com/pete/tool"),
+    ] {
+        let dir = root.path().join(name);
+        std::fs::create_dir(&dir).unwrap();
+        init_repo_with_commits(&dir, &[1_700_000_000])
+            .remote("origin", url)
+            .unwrap();
+    }
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &[
+            "--csv",
+            "--columns",
+            "repository,origin",
+            "--normalize-origin",
+        ],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        stdout,
+        "repository,origin\nhttps-clone,github.com/pete/tool\nssh-clone,github.com/pete/tool\n"
+    );
+}
+
+#[test]
+fn test_flat_reports_only_repos() {
+    let tmp = TempDir::new().unwrap();
+    Repository::init(tmp.path().join("repo")).unwrap();
+    let notes = tmp.path().join("notes");
+    std::fs::create_dir(&notes).unwrap();
+    std::fs::write(notes.join("todo.txt"), "milk\n").unwrap();
+
+    let output = run_lsproj_with_args(tmp.path(), &["--flat", "--json"]);
+    assert!(output.status.success(), "{output:?}");
+    let records: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let names: Vec<&str> = records
+        .iter()
+        .map(|r| r["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["repo"]);
+}
+
+#[test]
+fn test_json_remotes_lists_every_remote() {
+    let tmp = TempDir::new().unwrap();
+    let repo_dir = tmp.path().join("app");
+    std::fs::create_dir(&repo_dir).unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    let repo = Repository::open(&repo_dir).unwrap();
+    repo.remote("origin", "git@github.com:pete/app.git")
+        .unwrap();
+    repo.remote("upstream", "https://github.com/acme/app.git")
+        .unwrap();
+
+    let output = run_lsproj_with_args(tmp.path(), &["--json", "--json-remotes"]);
+    assert!(output.status.success(), "{output:?}");
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        json[0]["remotes"],
+        serde_json::json!([
+            {"name": "origin", "url": "git@github.com:pete/app.git"},
+            {"name": "upstream", "url": "https://github.com/acme/app.git"},
+        ])
+    );
+
+    let output = run_lsproj_with_args(tmp.path(), &["--csv", "--json-remotes"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_require_clean_exit_fails_on_corrupt_repo() {
+    let root = TempDir::new().unwrap();
+    let good = root.path().join("good");
+    std::fs::create_dir(&good).unwrap();
+    init_repo_with_commits(&good, &[1_700_000_000]);
+    let corrupt = root.path().join("corrupt/.git");
+    std::fs::create_dir_all(&corrupt).unwrap();
+    std::fs::write(corrupt.join("HEAD"), "garbage").unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--json"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let output = run_lsproj_with_args(root.path(), &["--json", "--require-clean-exit"]);
+    assert!(!output.status.success());
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let good = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r["name"] == "good")
+        .unwrap();
+    assert_eq!(good["is_git"], true);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("could not be read"), "{stderr}");
+}
+
+#[test]
+fn test_deadline_reports_partial_results() {
+    let root = TempDir::new().unwrap();
+    let repo_dir = root.path().join("app");
+    std::fs::create_dir(&repo_dir).unwrap();
+    init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+
+    let output = run_lsproj_with_args(root.path(), &["--json", "--deadline", "0"]);
+    assert!(output.status.success(), "{output:?}");
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(json.is_array());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("results are partial"), "{stderr}");
+}
+
+#[test]
+fn test_has_remote_reports_repos_with_named_remote() {
+    let root = TempDir::new().unwrap();
+    for (name, remotes) in [
+        ("mirrored", &["origin", "backup"][..]),
+        ("plain", &["origin"][..]),
+    ] {
+        let repo_dir = root.path().join(name);
+        std::fs::create_dir(&repo_dir).unwrap();
+        init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+        let repo = Repository::open(&repo_dir).unwrap();
+        for remote in remotes {
+            repo.remote(remote, &format!("https://example.com/{remote}/{name}.git"))
+                .unwrap();
+        }
+    }
+
+    let output = run_lsproj_with_args(root.path(), &["--json", "--has-remote", "backup"]);
+    assert!(output.status.success(), "{output:?}");
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let names: Vec<&str> = json
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["mirrored"]);
+}
+
+#[test]
+fn test_columns_without_counts_skip_history_walks() {
+    let root = TempDir::new().unwrap();
+    let proj = root.path().join("deep");
+    std::fs::create_dir(&proj).unwrap();
+    let repo = init_repo_with_commits(&proj, &[1_700_000_000, 1_700_050_000, 1_700_100_000]);
+    // Delete the root commit's loose object: a full history walk can no longer finish
+    let tip = repo.refname_to_id("refs/heads/main").unwrap();
+    let first = repo
+        .find_commit(tip)
+        .unwrap()
+        .parent(0)
+        .unwrap()
+        .parent_id(0)
+        .unwrap()
+        .to_string();
+    std::fs::remove_file(
+        repo.path()
+            .join("objects")
+            .join(&first[..2])
+            .join(&first[2..]),
+    )
+    .unwrap();
+
+    let output = run_lsproj_with_args(root.path(), &["--csv", "--columns", "repository,newest"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().nth(1), Some("deep,23-11-16"), "{stdout}");
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--csv", "--columns", "repository,newest,count"],
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_ne!(stdout.lines().nth(1), Some("deep,23-11-16,"), "{stdout}");
+}
+
+#[test]
+fn test_group_depth_zero_is_rejected() {
+    let root = TempDir::new().unwrap();
+    let output = run_lsproj_with_args(root.path(), &["--group-depth", "0"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("--group-depth"), "{stderr}");
+}
+
+#[test]
+fn test_message_matches_combines_with_show_contributors() {
+    let root = TempDir::new().unwrap();
+    let dir = root.path().join("proj");
+    std::fs::create_dir(&dir).unwrap();
+    init_repo_with_commits(&dir, &[1_700_000_000, 1_700_100_000]);
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &[
+            "--csv",
+            "--message-matches",
+            "test",
+            "--show-contributors",
+            "--max-commits",
+            "10",
+        ],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{stdout}");
+    assert!(lines[0].ends_with(",contributors"), "{stdout}");
+    assert!(
+        lines[1].starts_with("proj,") && lines[1].ends_with(",1"),
+        "{stdout}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_filter_cmd_output_stays_off_stdout() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = TempDir::new().unwrap();
+    let dir = root.path().join("proj");
+    std::fs::create_dir(&dir).unwrap();
+    init_repo_with_commits(&dir, &[1_700_000_000]);
+    let bin = TempDir::new().unwrap();
+    let script = bin.path().join("chatty.sh");
+    std::fs::write(&script, "#!/bin/sh\necho \"checking $1\"\nexit 0\n").unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--json", "--filter-cmd", script.to_str().unwrap()],
+    );
+    assert!(output.status.success(), "lsproj failed: {:?}", output);
+    let records: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(records.len(), 1);
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("checking ")
+    );
+}
+
+#[test]
+fn test_adaptive_concurrency_requires_async_engine() {
+    let root = TempDir::new().unwrap();
+    let output = run_lsproj_with_args(
+        root.path(),
+        &["--adaptive-concurrency", "--engine", "threads"],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("--engine async"), "{stderr}");
+}
+
+#[test]
+fn test_split_by_top_groups_by_root_relative_path() {
+    let root = TempDir::new().unwrap();
+    for dir in ["a/r1", "b/r2"] {
+        let repo_dir = root.path().join(dir);
+        std::fs::create_dir_all(&repo_dir).unwrap();
+        init_repo_with_commits(&repo_dir, &[1_700_000_000]);
+    }
+    let out = TempDir::new().unwrap();
+    let split_dir = out.path().join("split");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lsproj"))
+        .current_dir(root.path().join("a"))
+        .args([
+            "..",
+            "--split-by-top",
+            split_dir.to_str().unwrap(),
+            "--relative-to",
+            "cwd",
+        ])
+        .output()
+        .expect("run lsproj");
+    assert!(output.status.success(), "{output:?}");
+
+    let mut files: Vec<String> = std::fs::read_dir(&split_dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    assert_eq!(files, ["a.csv", "b.csv"]);
+    let a = std::fs::read_to_string(split_dir.join("a.csv")).unwrap();
+    assert!(a.lines().nth(1).unwrap().starts_with("r1,"));
+    let b = std::fs::read_to_string(split_dir.join("b.csv")).unwrap();
+    assert!(b.lines().nth(1).unwrap().starts_with("../b/r2,"));
+}